@@ -3,6 +3,9 @@
 use std::{env, fs, path::Path};
 
 fn main() {
+    #[cfg(feature = "ffi")]
+    generate_c_header();
+
     // Detect target triple dynamically
     let target = env::var("TARGET").unwrap();
     if !target.contains("wasm32") {
@@ -35,3 +38,22 @@ fn main() {
         version
     );
 }
+
+/// Generates `pixt.h` in `OUT_DIR` from the `extern "C"` items in
+/// `src/ffi.rs`. The copy in `include/` is checked in; the ffi tests fail
+/// when it falls behind this one.
+#[cfg(feature = "ffi")]
+fn generate_c_header() {
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let config = cbindgen::Config::from_file(Path::new(&crate_dir).join("cbindgen.toml"))
+        .expect("Failed to read cbindgen.toml");
+    cbindgen::Builder::new()
+        .with_src(Path::new(&crate_dir).join("src/ffi.rs"))
+        .with_config(config)
+        .generate()
+        .expect("Failed to generate C bindings")
+        .write_to_file(Path::new(&out_dir).join("pixt.h"));
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+}
@@ -0,0 +1,55 @@
+/*
+ * Renders an image file with the pixt C API.
+ *
+ *   cargo build --release --features ffi
+ *   cc examples/c/render.c -Iinclude -Ltarget/release -lpixt -o pixt-c
+ *   LD_LIBRARY_PATH=target/release ./pixt-c image.png
+ */
+#include <stdio.h>
+#include <stdlib.h>
+
+#include "pixt.h"
+
+int main(int argc, char **argv) {
+    if (argc < 2) {
+        fprintf(stderr, "usage: %s <image>\n", argv[0]);
+        return 2;
+    }
+    FILE *file = fopen(argv[1], "rb");
+    if (!file) {
+        perror(argv[1]);
+        return 1;
+    }
+    fseek(file, 0, SEEK_END);
+    long len = ftell(file);
+    rewind(file);
+    uint8_t *bytes = malloc(len);
+    if (!bytes || fread(bytes, 1, len, file) != (size_t)len) {
+        fprintf(stderr, "%s: read failed\n", argv[1]);
+        return 1;
+    }
+    fclose(file);
+
+    PixtOptions opts = {0};
+    opts.style = "block";
+    opts.width = 60;
+    opts.color = PIXT_COLOR_AVG_FG;
+    opts.format = PIXT_FORMAT_ANSI;
+
+    char *out = NULL;
+    size_t out_len = 0;
+    PixtStatus status = pixt_render(bytes, len, &opts, &out, &out_len);
+    free(bytes);
+    if (status != PIXT_STATUS_OK) {
+        fprintf(stderr, "pixt error %d: %s\n", status, pixt_last_error_message());
+        return 1;
+    }
+    fwrite(out, 1, out_len, stdout);
+    pixt_free(out);
+
+    /* Errors are reported, never crash the host. */
+    opts.style = "no-such-style";
+    status = pixt_render((const uint8_t *)"", 0, &opts, &out, &out_len);
+    fprintf(stderr, "expected failure %d: %s\n", status, pixt_last_error_message());
+    return 0;
+}
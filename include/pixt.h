@@ -0,0 +1,155 @@
+#ifndef PIXT_H
+#define PIXT_H
+
+/* Generated by cbindgen from src/ffi.rs, see build.rs. Do not edit. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+/*
+ Result of a pixt C API call.
+ */
+typedef enum PixtStatus {
+  PIXT_STATUS_OK = 0,
+  /*
+   A required pointer argument was null.
+   */
+  PIXT_STATUS_NULL_ARGUMENT = 1,
+  /*
+   An option had an unknown or out-of-range value.
+   */
+  PIXT_STATUS_INVALID_OPTION = 2,
+  /*
+   The image bytes could not be decoded.
+   */
+  PIXT_STATUS_DECODE = 3,
+  /*
+   Rendering failed.
+   */
+  PIXT_STATUS_RENDER = 4,
+  /*
+   pixt panicked; this is a bug.
+   */
+  PIXT_STATUS_PANIC = 5,
+} PixtStatus;
+
+/*
+ Colors emitted for every cell.
+ */
+typedef enum PixtColor {
+  PIXT_COLOR_NONE = 0,
+  /*
+   Average of both pixels as the foreground color.
+   */
+  PIXT_COLOR_AVG_FG = 1,
+  /*
+   Average of both pixels as the background color.
+   */
+  PIXT_COLOR_AVG_BG = 2,
+  /*
+   Top pixel as foreground, bottom pixel as background.
+   */
+  PIXT_COLOR_FG_TOP_BG_DOWN = 3,
+  /*
+   Top pixel as background, bottom pixel as foreground.
+   */
+  PIXT_COLOR_BG_TOP_FG_DOWN = 4,
+  /*
+   Top pixel as the foreground color.
+   */
+  PIXT_COLOR_TOP_FG = 5,
+  /*
+   Bottom pixel as the foreground color.
+   */
+  PIXT_COLOR_BOTTOM_FG = 6,
+} PixtColor;
+
+/*
+ Output format.
+ */
+typedef enum PixtFormat {
+  /*
+   Plain text, colors are ignored.
+   */
+  PIXT_FORMAT_TEXT = 0,
+  /*
+   Text with ANSI escape sequences.
+   */
+  PIXT_FORMAT_ANSI = 1,
+  /*
+   A standalone HTML page.
+   */
+  PIXT_FORMAT_HTML = 2,
+} PixtFormat;
+
+/*
+ Options for [`pixt_render`]. Zeroed options render 80 columns of plain
+ ASCII text.
+ */
+typedef struct PixtOptions {
+  /*
+   Style name (`ascii`, `block`, `pixel`, `braills`, `dots`, `solid`,
+   `half-block`, `quadrant`, `sextant` or `braille-dots`), or null for
+   `ascii`.
+   */
+  const char *style;
+  /*
+   Custom characters ordered from darkest to brightest (UTF-8). Takes
+   precedence over `style` when not null.
+   */
+  const char *charset;
+  /*
+   Output width in characters, `0` to derive it from `height`.
+   */
+  uint32_t width;
+  /*
+   Output height in characters, `0` to derive it from `width`.
+   */
+  uint32_t height;
+  /*
+   One of [`PixtColor`].
+   */
+  uint32_t color;
+  /*
+   One of [`PixtFormat`].
+   */
+  uint32_t format;
+} PixtOptions;
+
+/*
+ Renders an encoded image (PNG, JPEG, ...) to text.
+
+ On success `*out` receives a NUL-terminated buffer owned by pixt, which
+ must be released with [`pixt_free`], and `*out_len` its length without the
+ terminator. `opts` may be null for the defaults.
+
+ # Safety
+
+ `image_bytes` must point to `len` readable bytes, `opts` must be null or
+ point to a valid [`PixtOptions`] whose strings are NUL-terminated, and
+ `out`/`out_len` must be valid for writes.
+ */
+enum PixtStatus pixt_render(const uint8_t *image_bytes,
+                            size_t len,
+                            const struct PixtOptions *opts,
+                            char **out,
+                            size_t *out_len);
+
+/*
+ Releases a buffer returned by [`pixt_render`]. Null is ignored.
+
+ # Safety
+
+ `out` must be null or a pointer obtained from [`pixt_render`] that has
+ not been freed yet.
+ */
+void pixt_free(char *out);
+
+/*
+ Message describing the last error on the calling thread, or null if no
+ call failed yet. The pointer stays valid until the next failing call on
+ the same thread.
+ */
+const char *pixt_last_error_message(void);
+
+#endif  /* PIXT_H */
@@ -0,0 +1,524 @@
+//! Terminal animations played over a resolved [`CellGrid`]: the frame clock
+//! that paces them, the alternate screen they run on and the effects built
+//! on top.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event::{self, Event, KeyEventKind},
+    execute, queue,
+    style::{Color, Print, ResetColor, SetForegroundColor},
+    terminal::{
+        self, BeginSynchronizedUpdate, Clear, ClearType, EndSynchronizedUpdate,
+        EnterAlternateScreen, LeaveAlternateScreen,
+    },
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{
+    color::{ColorDepth, ColorMatrix, ansi16, ansi256},
+    grid::CellGrid,
+    img::{OutputType, PixtImg},
+};
+
+/// Small deterministic generator (SplitMix64), so an animation plays the
+/// same way every time for the same seed.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+    /// A value in `0..n`; `0` when `n` is `0`.
+    pub fn below(&mut self, n: u32) -> u32 {
+        (((self.next_u64() >> 32) * n as u64) >> 32) as u32
+    }
+}
+
+/// Order in which [`reveal_order`] uncovers the cells of a grid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RevealOrder {
+    /// Left to right, top to bottom, like typing.
+    Sequential,
+    /// Same order as [`Sequential`](Self::Sequential), revealed a whole row
+    /// at a time.
+    Rows,
+    /// Shuffled with the given seed (a dissolve).
+    Random(u64),
+    /// By distance from the center outwards.
+    CenterOut,
+}
+
+/// Positions `(x, y)` of a `cols`×`rows` grid, each exactly once, in the
+/// order `order` reveals them.
+pub fn reveal_order(cols: u32, rows: u32, order: RevealOrder) -> Vec<(u32, u32)> {
+    let mut cells = (0..rows)
+        .flat_map(|y| (0..cols).map(move |x| (x, y)))
+        .collect::<Vec<_>>();
+    match order {
+        RevealOrder::Sequential | RevealOrder::Rows => {}
+        RevealOrder::Random(seed) => {
+            let mut rng = Rng::new(seed);
+            for i in (1..cells.len()).rev() {
+                cells.swap(i, rng.below(i as u32 + 1) as usize);
+            }
+        }
+        RevealOrder::CenterOut => {
+            // Twice the offsets, so the center of even sizes stays integral;
+            // cells are about twice as tall as wide.
+            let (cx, cy) = (cols as i64 - 1, rows as i64 - 1);
+            cells.sort_by_key(|&(x, y)| {
+                let (dx, dy) = (2 * x as i64 - cx, 2 * (2 * y as i64 - cy));
+                dx * dx + dy * dy
+            });
+        }
+    }
+    cells
+}
+
+/// Paces the frames of an animation and watches for the key press that
+/// stops it.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct FrameClock {
+    interval: Duration,
+    next: Instant,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FrameClock {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            next: Instant::now() + interval,
+        }
+    }
+    /// Changes the time between frames, starting with the frame due next:
+    /// it comes `interval` after the last one instead.
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.next = self.next - self.interval + interval;
+        self.interval = interval;
+    }
+    /// Waits until the next frame is due. Returns `false` if a key was
+    /// pressed meanwhile, Ctrl+C included, meaning the animation should
+    /// stop. A zero interval doesn't wait, but still checks for a key.
+    pub fn tick(&mut self) -> io::Result<bool> {
+        if self.interval.is_zero() {
+            return Ok(!(event::poll(Duration::ZERO)? && is_key_press(event::read()?)));
+        }
+        loop {
+            let now = Instant::now();
+            if now >= self.next {
+                // Skip frames we are late for instead of rushing through them.
+                self.next = (self.next + self.interval).max(now);
+                return Ok(true);
+            }
+            if event::poll(self.next - now)? && is_key_press(event::read()?) {
+                return Ok(false);
+            }
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn is_key_press(event: Event) -> bool {
+    matches!(event, Event::Key(key) if key.kind == KeyEventKind::Press)
+}
+
+/// The alternate screen an animation is drawn on, in raw mode with the
+/// cursor hidden. Dropping it restores the terminal, also on errors.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct Stage<W: Write> {
+    out: W,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<W: Write> Stage<W> {
+    pub fn enter(mut out: W) -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        if let Err(err) = execute!(out, EnterAlternateScreen, Hide) {
+            let _ = terminal::disable_raw_mode();
+            return Err(err);
+        }
+        Ok(Self { out })
+    }
+    pub fn out(&mut self) -> &mut W {
+        &mut self.out
+    }
+    /// Terminal size in `(columns, rows)`.
+    pub fn size(&self) -> io::Result<(u16, u16)> {
+        terminal::size()
+    }
+    /// Blocks until a key is pressed.
+    pub fn wait_key(&mut self) -> io::Result<()> {
+        while !is_key_press(event::read()?) {}
+        Ok(())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<W: Write> Drop for Stage<W> {
+    fn drop(&mut self) {
+        let _ = execute!(self.out, ResetColor, Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Draws the cells of `grid` at `positions` `(x, y)`, skipping those outside
+/// the `(columns, rows)` of the screen.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn draw_cells(
+    pi: &PixtImg,
+    grid: &CellGrid,
+    out_type: &OutputType,
+    positions: &[(u32, u32)],
+    (cols, rows): (u16, u16),
+    mut out: impl Write,
+) -> io::Result<()> {
+    let cell_width = pi.cell_width();
+    for &(x, y) in positions {
+        let Some(cell) = grid.get(y as usize).and_then(|row| row.get(x as usize)) else {
+            continue;
+        };
+        if x * cell_width >= cols as u32 || y >= rows as u32 {
+            continue;
+        }
+        queue!(out, MoveTo((x * cell_width) as u16, y as u16))?;
+        pi.write_run(std::slice::from_ref(cell), out_type, &mut out)?;
+    }
+    out.flush()
+}
+
+/// Draws a sequence of frames over each other on the terminal, writing
+/// only the cells that changed since the frame before, so unchanged parts
+/// neither flicker nor get sent again.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Default)]
+pub struct FrameDiff {
+    previous: CellGrid,
+    sync: bool,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FrameDiff {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Brackets every frame in synchronized update sequences (mode 2026),
+    /// so terminals supporting them show it at once instead of tearing
+    /// halfway through. Others ignore the sequences.
+    pub fn with_sync(mut self, sync: bool) -> Self {
+        self.sync = sync;
+        self
+    }
+    /// Draws the cells of `grid` that differ from the last frame, each run
+    /// of neighbouring ones with a single cursor move, skipping those
+    /// outside the `(columns, rows)` of the screen; then keeps `grid` as
+    /// the last frame. The first frame is drawn whole, and so is one of
+    /// another size than the last, on a cleared screen.
+    pub fn draw(
+        &mut self,
+        pi: &PixtImg,
+        grid: CellGrid,
+        out_type: &OutputType,
+        (cols, rows): (u16, u16),
+        mut out: impl Write,
+    ) -> io::Result<()> {
+        let size = |grid: &CellGrid| (grid.len(), grid.first().map_or(0, |row| row.len()));
+        let same_size = size(&grid) == size(&self.previous);
+        if self.sync {
+            queue!(out, BeginSynchronizedUpdate)?;
+        }
+        if !same_size && !self.previous.is_empty() {
+            queue!(out, Clear(ClearType::All))?;
+        }
+        let cell_width = pi.cell_width();
+        let visible = (cols as u32).div_ceil(cell_width.max(1)) as usize;
+        for (y, row) in grid.iter().enumerate().take(rows as usize) {
+            let unchanged = |x: usize| same_size && self.previous[y][x] == row[x];
+            let end = row.len().min(visible);
+            let mut x = 0;
+            while x < end {
+                if unchanged(x) {
+                    x += 1;
+                    continue;
+                }
+                let start = x;
+                while x < end && !unchanged(x) {
+                    x += 1;
+                }
+                queue!(out, MoveTo((start as u32 * cell_width) as u16, y as u16))?;
+                pi.write_run(&row[start..x], out_type, &mut out)?;
+            }
+        }
+        self.previous = grid;
+        if self.sync {
+            queue!(out, EndSynchronizedUpdate)?;
+        }
+        out.flush()
+    }
+}
+
+/// Plays a reveal of `grid` on `stage`, `per_frame` cells (rows with
+/// [`RevealOrder::Rows`]) at a time, finishing with the complete image.
+/// Returns `false` when a key press cut it short.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn reveal<W: Write>(
+    stage: &mut Stage<W>,
+    pi: &PixtImg,
+    grid: &CellGrid,
+    out_type: &OutputType,
+    order: RevealOrder,
+    per_frame: usize,
+    clock: &mut FrameClock,
+) -> io::Result<bool> {
+    let cols = grid.first().map_or(0, |row| row.len() as u32);
+    let positions = reveal_order(cols, grid.len() as u32, order);
+    let chunk = match order {
+        RevealOrder::Rows => per_frame * cols as usize,
+        _ => per_frame,
+    };
+    let screen = stage.size()?;
+    for frame in positions.chunks(chunk.max(1)) {
+        draw_cells(pi, grid, out_type, frame, screen, stage.out())?;
+        if !clock.tick()? {
+            draw_cells(pi, grid, out_type, &positions, screen, stage.out())?;
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Color of the falling glyphs of [`matrix_rain`]; their heads are white.
+#[cfg(not(target_arch = "wasm32"))]
+const RAIN_GREEN: [u8; 3] = [0, 255, 70];
+
+/// One column of [`matrix_rain`].
+#[cfg(not(target_arch = "wasm32"))]
+struct RainColumn {
+    /// Row of the head; negative while the drop is still waiting to start.
+    head: i64,
+    /// Rows the head falls per frame.
+    speed: i64,
+    /// Rows of glyphs trailing behind the head.
+    trail: i64,
+}
+
+/// Plays the digital-rain effect: green glyphs fall down every column and
+/// the cells of `grid` lock in behind them, until the complete image is
+/// shown. The same `seed` always plays the same rain. Returns `false` when
+/// a key press cut it short.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn matrix_rain<W: Write>(
+    stage: &mut Stage<W>,
+    pi: &PixtImg,
+    grid: &CellGrid,
+    out_type: &OutputType,
+    seed: u64,
+    clock: &mut FrameClock,
+) -> io::Result<bool> {
+    let cols = grid.first().map_or(0, |row| row.len() as u32);
+    let screen = stage.size()?;
+    let rows = (grid.len() as i64).min(screen.1 as i64);
+    let cell_width = pi.cell_width();
+    let mut rng = Rng::new(seed);
+    let mut columns = (0..cols)
+        .map(|_| RainColumn {
+            head: -(rng.below(rows as u32 + 1) as i64),
+            speed: 1 + rng.below(2) as i64,
+            trail: 3 + rng.below(rows as u32 / 2 + 1) as i64,
+        })
+        .collect::<Vec<_>>();
+    let positions = reveal_order(cols, rows as u32, RevealOrder::Sequential);
+    loop {
+        let mut falling = false;
+        let mut locked = Vec::new();
+        for (x, column) in (0..).zip(columns.iter_mut()) {
+            if column.head - column.trail > rows {
+                continue;
+            }
+            falling = true;
+            let before = column.head;
+            column.head += column.speed;
+            // Cells the trail left behind this frame lock into the image.
+            let passed =
+                (before - column.trail).max(0)..(column.head - column.trail).clamp(0, rows);
+            locked.extend(passed.map(|y| (x, y as u32)));
+            if x * cell_width >= screen.0 as u32 {
+                continue;
+            }
+            let trail = (column.head - column.trail).max(0)..=column.head.min(rows - 1);
+            if trail.is_empty() {
+                continue;
+            }
+            for y in trail {
+                let glyph = rain_glyph(&mut rng);
+                let color = match (y == column.head, pi.color_depth()) {
+                    (true, _) => Color::White,
+                    (false, ColorDepth::TrueColor) => {
+                        let [r, g, b] = RAIN_GREEN;
+                        Color::Rgb { r, g, b }
+                    }
+                    (false, ColorDepth::Ansi256) => Color::AnsiValue(ansi256(RAIN_GREEN)),
+                    (false, ColorDepth::Ansi16) => {
+                        Color::AnsiValue(ansi16(RAIN_GREEN, &pi.ansi_palette()))
+                    }
+                };
+                queue!(
+                    stage.out(),
+                    MoveTo((x * cell_width) as u16, y as u16),
+                    SetForegroundColor(color),
+                )?;
+                for _ in 0..cell_width {
+                    queue!(stage.out(), Print(glyph))?;
+                }
+            }
+            queue!(stage.out(), ResetColor)?;
+        }
+        draw_cells(pi, grid, out_type, &locked, screen, stage.out())?;
+        if !falling {
+            return Ok(true);
+        }
+        if !clock.tick()? {
+            draw_cells(pi, grid, out_type, &positions, screen, stage.out())?;
+            return Ok(false);
+        }
+    }
+}
+
+/// A random half-width katakana or digit.
+#[cfg(not(target_arch = "wasm32"))]
+fn rain_glyph(rng: &mut Rng) -> char {
+    match rng.below(4) {
+        0 => char::from(b'0' + rng.below(10) as u8),
+        _ => char::from_u32(0xFF66 + rng.below(56)).unwrap_or('0'),
+    }
+}
+
+/// Cycles the hues of `grid` until a key is pressed, one full turn every
+/// `period`. Characters never change: every frame only redraws the rows
+/// with rotated colors.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn hue_cycle<W: Write>(
+    stage: &mut Stage<W>,
+    pi: &PixtImg,
+    grid: &CellGrid,
+    out_type: &OutputType,
+    period: Duration,
+    clock: &mut FrameClock,
+) -> io::Result<()> {
+    let (cols, rows) = stage.size()?;
+    let visible = (cols as u32 / pi.cell_width()) as usize;
+    let start = Instant::now();
+    let mut frame = grid.clone();
+    loop {
+        let turn = start.elapsed().as_secs_f32() / period.as_secs_f32().max(f32::EPSILON);
+        let rotation = ColorMatrix::hue_rotation(turn.fract() * 360.0);
+        for (y, (src, dst)) in (0..rows).zip(grid.iter().zip(frame.iter_mut())) {
+            for (cell, out) in src.iter().zip(dst.iter_mut()).take(visible) {
+                out.top.color = rotation.apply(cell.top.into()).into();
+                out.bottom.color = rotation.apply(cell.bottom.into()).into();
+            }
+            queue!(stage.out(), MoveTo(0, y))?;
+            pi.write_run(&dst[..dst.len().min(visible)], out_type, stage.out())?;
+        }
+        stage.out().flush()?;
+        if !clock.tick()? {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use image::{DynamicImage, Rgb, RgbImage};
+
+    use super::*;
+    use crate::{img::ColorType, style::ImgStyle};
+
+    const SCREEN: (u16, u16) = (80, 24);
+
+    /// A colored 16×8 cell grid and the renderer it came from.
+    fn frame() -> (PixtImg, CellGrid) {
+        let pi = PixtImg::new(ImgStyle::Ascii, OutputType::Term(ColorType::AvgFgOnly));
+        let img = RgbImage::from_fn(16, 16, |x, y| Rgb([(x * 16) as u8, (y * 16) as u8, 128]));
+        let grid = pi.cells(&DynamicImage::ImageRgb8(img));
+        (pi, grid)
+    }
+
+    fn draw(diff: &mut FrameDiff, pi: &PixtImg, grid: &CellGrid) -> String {
+        let mut out = Vec::new();
+        diff.draw(pi, grid.clone(), pi.output_type(), SCREEN, &mut out)
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    /// Cursor moves (`ESC [ row ; column H`) in `out`.
+    fn moves(out: &str) -> usize {
+        out.split("\x1b[")
+            .skip(1)
+            .filter(|esc| {
+                let end = esc.find(|c: char| !c.is_ascii_digit() && c != ';');
+                end.is_some_and(|end| esc[end..].starts_with('H'))
+            })
+            .count()
+    }
+
+    #[test]
+    fn unchanged_frames_write_nothing() {
+        let (pi, grid) = frame();
+        let mut diff = FrameDiff::new();
+        assert_eq!(moves(&draw(&mut diff, &pi, &grid)), grid.len());
+        assert_eq!(draw(&mut diff, &pi, &grid), "");
+    }
+
+    #[test]
+    fn one_changed_cell_is_one_move_and_write() {
+        let (pi, grid) = frame();
+        let mut diff = FrameDiff::new();
+        draw(&mut diff, &pi, &grid);
+        let mut changed = grid.clone();
+        changed[2][3] = grid[7][12].clone();
+        assert_ne!(changed[2][3], grid[2][3]);
+        let out = draw(&mut diff, &pi, &changed);
+
+        let mut cell = Vec::new();
+        queue!(cell, MoveTo(3, 2)).unwrap();
+        pi.write_run(&changed[2][3..4], pi.output_type(), &mut cell)
+            .unwrap();
+        assert_eq!(out.as_bytes(), cell);
+        assert_eq!(moves(&out), 1);
+        // And changing it back is one more.
+        assert_eq!(moves(&draw(&mut diff, &pi, &grid)), 1);
+    }
+
+    #[test]
+    fn sync_brackets_each_frame_once() {
+        let (pi, grid) = frame();
+        let (begin, end) = ("\x1b[?2026h", "\x1b[?2026l");
+        let mut diff = FrameDiff::new().with_sync(true);
+        let mut changed = grid.clone();
+        changed[0][0] = grid[7][15].clone();
+        for grid in [&grid, &grid, &changed] {
+            let out = draw(&mut diff, &pi, grid);
+            assert!(out.starts_with(begin) && out.ends_with(end), "{:?}", out);
+            assert_eq!(out.matches(begin).count(), 1);
+            assert_eq!(out.matches(end).count(), 1);
+        }
+        let out = draw(&mut FrameDiff::new(), &pi, &grid);
+        assert!(!out.contains(begin) && !out.contains(end));
+    }
+}
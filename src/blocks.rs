@@ -0,0 +1,401 @@
+//! Block glyphs that split a cell into several subpixels.
+
+use std::{fmt, io, str::FromStr};
+
+use crate::{img::parse_name, preprocess::Luminance};
+
+/// Quadrant glyphs indexed by a mask of the filled quadrants: bit 0 top
+/// left, bit 1 top right, bit 2 bottom left, bit 3 bottom right.
+pub const QUADRANTS: [char; 16] = [
+    ' ', '▘', '▝', '▀', '▖', '▌', '▞', '▛', '▗', '▚', '▐', '▜', '▄', '▙', '▟', '█',
+];
+
+/// Braille dot bit (offset from U+2800) of every pixel of a 2×4 cell, row
+/// by row: dots 1-3 run down the left column and 4-6 down the right one,
+/// with the bottom row (dots 7 and 8) added later.
+pub const BRAILLE_DOTS: [u8; 8] = [0x01, 0x08, 0x02, 0x10, 0x04, 0x20, 0x40, 0x80];
+
+/// Smallest channel difference between the two colors of a block cell;
+/// blocks with less contrast are drawn uniform, so smooth gradients don't
+/// turn into noise.
+const BLOCK_CONTRAST: u8 = 24;
+
+/// How a cell is split into subpixels, each either part of the glyph or of
+/// the background.
+///
+/// Cells of `columns`×`rows` pixels (see [`size`](Self::size)) pick their
+/// glyph by a pattern with bit `i` set for every lit pixel `i`, counted row
+/// by row from the top left; [`glyphs`](Self::glyphs) lists the glyph for
+/// every pattern.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subpixels {
+    /// 2×2 pixels, split into the two clusters closest to their mean colors.
+    Quadrant,
+    /// 2×3 pixels from the Symbols for Legacy Computing block (Unicode 13),
+    /// lit by [`BlockThreshold`]. Needs a font that has them.
+    Sextant,
+    /// 2×4 pixels as the dots of a braille pattern (U+2800 to U+28FF), lit
+    /// by [`BlockThreshold`].
+    Braille,
+}
+
+/// How the character of a cell is chosen.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionStrategy {
+    /// By the intensity of the cell (or the pattern of [`Subpixels`]
+    /// styles).
+    #[default]
+    Intensity,
+    /// By shape: every cell is sampled as a [`BITMAP_SIZE`] bitmap, lit by
+    /// [`BlockThreshold`], and gets the charset character whose
+    /// [`glyph_bitmap`] differs in the fewest pixels; the pixels under its
+    /// ink and under the rest give the two cell colors. Characters without
+    /// a known shape are never picked.
+    Structural,
+}
+
+impl SelectionStrategy {
+    pub const ALL: [Self; 2] = [Self::Intensity, Self::Structural];
+    /// Canonical name, as used by [`Display`](fmt::Display) and
+    /// [`FromStr`].
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Intensity => "intensity",
+            Self::Structural => "structural",
+        }
+    }
+}
+
+impl fmt::Display for SelectionStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for SelectionStrategy {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Self> {
+        parse_name("selection strategy", s, &Self::ALL, Self::name, &[])
+    }
+}
+
+/// Which pixels of a [`Subpixels::Sextant`] or [`Subpixels::Braille`] cell,
+/// or of a [`SelectionStrategy::Structural`] bitmap, are lit.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BlockThreshold {
+    /// Pixels brighter than the mean of their cell; flat cells are all lit
+    /// when bright and all unlit when dark.
+    #[default]
+    Mean,
+    /// Pixels from the intensity that best separates the whole image into
+    /// dark and bright (Otsu's method).
+    Otsu,
+    /// Pixels at least this intensity.
+    Fixed(u8),
+}
+
+impl BlockThreshold {
+    pub const ALL: [Self; 3] = [Self::Mean, Self::Otsu, Self::Fixed(128)];
+    /// Canonical name, as used by [`Display`](fmt::Display) and
+    /// [`FromStr`]. [`Fixed`](Self::Fixed) displays as its value.
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Mean => "mean",
+            Self::Otsu => "otsu",
+            Self::Fixed(_) => "fixed",
+        }
+    }
+}
+
+impl fmt::Display for BlockThreshold {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fixed(v) => write!(f, "{}", v),
+            _ => f.write_str(self.name()),
+        }
+    }
+}
+
+/// Also accepts a plain intensity (`0`-`255`) for [`Fixed`](BlockThreshold::Fixed).
+impl FromStr for BlockThreshold {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Self> {
+        if let Ok(v) = s.trim().parse() {
+            return Ok(Self::Fixed(v));
+        }
+        parse_name("threshold", s, &[Self::Mean, Self::Otsu], Self::name, &[])
+    }
+}
+
+impl Subpixels {
+    /// Pixels `(columns, rows)` every cell covers.
+    pub const fn size(self) -> (u32, u32) {
+        match self {
+            Self::Quadrant => (2, 2),
+            Self::Sextant => (2, 3),
+            Self::Braille => (2, 4),
+        }
+    }
+    /// Glyph for every pattern.
+    pub fn glyphs(self) -> Vec<char> {
+        match self {
+            Self::Quadrant => QUADRANTS.to_vec(),
+            Self::Sextant => (0..64).map(sextant).collect(),
+            Self::Braille => (0..256).map(braille).collect(),
+        }
+    }
+    /// Pattern of the lit pixels among `px` (row by row), see
+    /// [`lit_pixels`]; quadrants split by color clusters instead of a
+    /// threshold.
+    pub(crate) fn pattern(
+        self,
+        px: &[[u8; 3]],
+        formula: Luminance,
+        invert: bool,
+        level: Option<u8>,
+    ) -> usize {
+        let bits = match self {
+            Self::Quadrant => orient(px, split_clusters(px), formula, invert),
+            _ => lit_pixels(px, formula, invert, level),
+        };
+        bits as usize
+    }
+}
+
+/// The lit pixels among up to 64 `px` as bits, the bright ones by
+/// `formula`, or the dark ones with `invert`. Pixels at least `level` are
+/// lit when it is given; otherwise those brighter than the mean of `px`,
+/// and a block whose two parts differ by less than [`BLOCK_CONTRAST`] in
+/// every channel counts as uniform: all lit when bright and all unlit when
+/// dark.
+pub(crate) fn lit_pixels(
+    px: &[[u8; 3]],
+    formula: Luminance,
+    invert: bool,
+    level: Option<u8>,
+) -> u64 {
+    let luma: Vec<u8> = px.iter().map(|&p| formula.luma(p)).collect();
+    let lit = |lit: &dyn Fn(u8) -> bool| {
+        (0..px.len())
+            .filter(|&i| lit(luma[i]))
+            .fold(0, |bits, i| bits | 1 << i)
+    };
+    match level {
+        Some(level) => {
+            let bright = lit(&|v| v >= level);
+            if invert { !bright & full(px) } else { bright }
+        }
+        None => {
+            let mean = luma.iter().map(|&v| v as u32).sum::<u32>() as f32 / px.len() as f32;
+            orient(px, lit(&|v| v as f32 > mean), formula, invert)
+        }
+    }
+}
+
+/// `bits` or their complement, whichever are the brighter (or with
+/// `invert` the darker) part of `px`; uniform when the parts are too close.
+fn orient(px: &[[u8; 3]], bits: u64, formula: Luminance, invert: bool) -> u64 {
+    let all = full(px);
+    let luma = |bits: u64| {
+        let [r, g, b] = mean_color(px, bits).map(|v| v.round() as u8);
+        formula.luma([r, g, b])
+    };
+    let bright = match bits {
+        bits if bits == 0 || bits == all || !distinct(px, bits) => match luma(all) >= 128 {
+            true => all,
+            false => 0,
+        },
+        bits if luma(bits) >= luma(!bits & all) => bits,
+        bits => !bits & all,
+    };
+    if invert { !bright & all } else { bright }
+}
+
+/// Bits of all pixels of `px`.
+fn full(px: &[[u8; 3]]) -> u64 {
+    u64::MAX >> (64 - px.len())
+}
+
+/// The Unicode sextant for `pattern` (bit 0 top left to bit 5 bottom
+/// right). The four patterns that already exist as block elements (empty,
+/// full, left and right half) are skipped by the U+1FB00 range and map to
+/// those.
+pub fn sextant(pattern: usize) -> char {
+    match pattern & 0b111111 {
+        0 => ' ',
+        0b010101 => '▌',
+        0b101010 => '▐',
+        0b111111 => '█',
+        p => {
+            let skipped = (p > 0b010101) as u32 + (p > 0b101010) as u32;
+            char::from_u32(0x1FB00 + p as u32 - 1 - skipped).unwrap_or(' ')
+        }
+    }
+}
+
+/// The braille pattern for `pattern` (bit 0 top left to bit 7 bottom
+/// right), see [`BRAILLE_DOTS`].
+pub fn braille(pattern: usize) -> char {
+    let dots = (0..8)
+        .filter(|i| pattern & (1 << i) != 0)
+        .fold(0, |dots, i| dots | BRAILLE_DOTS[i] as u32);
+    char::from_u32(0x2800 + dots).unwrap_or(' ')
+}
+
+/// The intensity that splits `histogram` into the two classes with the most
+/// variance between them (Otsu's method); pixels at least it are bright.
+pub(crate) fn otsu(histogram: &[u64; 256]) -> u8 {
+    let total: u64 = histogram.iter().sum();
+    let sum: f64 = (0..256).map(|v| v as f64 * histogram[v] as f64).sum();
+    let (mut below, mut below_sum) = (0u64, 0f64);
+    let (mut best, mut best_var) = (128usize, -1f64);
+    for (v, &count) in histogram.iter().enumerate() {
+        // `v` is the first bright intensity; everything before it is dark.
+        if below > 0 && below < total {
+            let (w0, w1) = (below as f64, (total - below) as f64);
+            let (m0, m1) = (below_sum / w0, (sum - below_sum) / w1);
+            let var = w0 * w1 * (m0 - m1).powi(2);
+            if var > best_var {
+                (best, best_var) = (v, var);
+            }
+        }
+        below += count;
+        below_sum += v as f64 * count as f64;
+    }
+    best as u8
+}
+
+/// Mean color of the pixels of `px` set in `bits`.
+fn mean_color(px: &[[u8; 3]], bits: u64) -> [f32; 3] {
+    let picked = (0..px.len()).filter(|i| bits & (1 << i) != 0);
+    let n = picked.clone().count() as f32;
+    let sum = picked.fold([0.0f32; 3], |acc, i| {
+        [0, 1, 2].map(|c| acc[c] + px[i][c] as f32)
+    });
+    sum.map(|v| v / n)
+}
+
+/// Whether the pixels set in `bits` and the others differ by at least
+/// [`BLOCK_CONTRAST`] in some channel.
+fn distinct(px: &[[u8; 3]], bits: u64) -> bool {
+    let all = full(px);
+    let (a, b) = (mean_color(px, bits), mean_color(px, !bits & all));
+    (0..3).any(|c| (a[c] - b[c]).abs() >= BLOCK_CONTRAST as f32)
+}
+
+/// Splits `px` into the two clusters with the least squared distance to
+/// their mean colors, tried exhaustively; `0` when no split with enough
+/// contrast beats keeping them together.
+fn split_clusters(px: &[[u8; 3]]) -> u64 {
+    let all = full(px);
+    let error = |bits: u64| -> f32 {
+        let mean = mean_color(px, bits);
+        (0..px.len())
+            .filter(|i| bits & (1 << i) != 0)
+            .map(|i| {
+                (0..3)
+                    .map(|c| (px[i][c] as f32 - mean[c]).powi(2))
+                    .sum::<f32>()
+            })
+            .sum()
+    };
+    // The last pixel always lies in the cluster left unset, so these masks
+    // cover every split once; `0` is no split at all.
+    (0..=all >> 1)
+        .filter(|&bits| bits == 0 || distinct(px, bits))
+        .map(|bits| {
+            let err = match bits {
+                0 => error(all),
+                _ => error(bits) + error(!bits & all),
+            };
+            (bits, err)
+        })
+        .fold((0, f32::INFINITY), |best, (bits, err)| {
+            if err < best.1 { (bits, err) } else { best }
+        })
+        .0
+}
+
+/// Columns and rows of the bitmaps [`glyph_bitmap`] draws glyphs on.
+pub const BITMAP_SIZE: (u32, u32) = (4, 8);
+
+/// Bits of the bitmap pixels in columns `x0..x1` of rows `y0..y1`.
+const fn rect(x0: u32, y0: u32, x1: u32, y1: u32) -> u32 {
+    let mut bits = 0;
+    let mut y = y0;
+    while y < y1 {
+        let mut x = x0;
+        while x < x1 {
+            bits |= 1 << (y * BITMAP_SIZE.0 + x);
+            x += 1;
+        }
+        y += 1;
+    }
+    bits
+}
+
+/// Shape of `ch` on a [`BITMAP_SIZE`] grid, bit `y * 4 + x` set where the
+/// glyph is inked, for spaces, block elements (halves, eighths, quadrants
+/// and shades) and braille patterns; `None` for any other character.
+///
+/// Shades are drawn as even patterns of their coverage, and braille dots
+/// fill the 2×2 square they sit in.
+pub fn glyph_bitmap(ch: char) -> Option<u32> {
+    const FULL: u32 = rect(0, 0, 4, 8);
+    const TOP: u32 = rect(0, 0, 4, 4);
+    const BOTTOM: u32 = rect(0, 4, 4, 8);
+    const LEFT: u32 = rect(0, 0, 2, 8);
+    const RIGHT: u32 = rect(2, 0, 4, 8);
+    const TL: u32 = TOP & LEFT;
+    const TR: u32 = TOP & RIGHT;
+    const BL: u32 = BOTTOM & LEFT;
+    const BR: u32 = BOTTOM & RIGHT;
+    // Every other pixel, and every fourth in a diagonal grid.
+    const HALF: u32 = 0xA5A5_A5A5;
+    const QUARTER: u32 = 0x4141_4141;
+    Some(match ch {
+        ' ' => 0,
+        '█' => FULL,
+        '▀' => TOP,
+        '▔' => rect(0, 0, 4, 1),
+        '▕' => rect(3, 0, 4, 8),
+        '▐' => RIGHT,
+        // Lower eighths, `▁` to `▇`, and `▄`.
+        '\u{2581}'..='\u{2587}' => {
+            let eighths = ch as u32 - 0x2580;
+            rect(0, 8 - eighths, 4, 8)
+        }
+        // Left eighths, `▉` (seven) down to `▏` (one), `▌` among them,
+        // rounded up to whole columns.
+        '\u{2589}'..='\u{258F}' => {
+            let eighths = 0x2590 - ch as u32;
+            rect(0, 0, eighths.div_ceil(2), 8)
+        }
+        '░' => QUARTER,
+        '▒' => HALF,
+        '▓' => !QUARTER,
+        '▖' => BL,
+        '▗' => BR,
+        '▘' => TL,
+        '▙' => TL | BL | BR,
+        '▚' => TL | BR,
+        '▛' => TL | TR | BL,
+        '▜' => TL | TR | BR,
+        '▝' => TR,
+        '▞' => TR | BL,
+        '▟' => TR | BL | BR,
+        '\u{2800}'..='\u{28FF}' => {
+            let dots = ch as u32 - 0x2800;
+            (0..8)
+                .filter(|&i| dots & BRAILLE_DOTS[i] as u32 != 0)
+                .map(|i| {
+                    let (x, y) = (i as u32 % 2 * 2, i as u32 / 2 * 2);
+                    rect(x, y, x + 2, y + 2)
+                })
+                .fold(0, |bits, dot| bits | dot)
+        }
+        _ => return None,
+    })
+}
@@ -0,0 +1,171 @@
+//! Live frames from a V4L2 camera, for `--camera`.
+
+use std::{
+    io,
+    sync::{Arc, Condvar, Mutex, mpsc},
+    thread,
+};
+
+use image::{DynamicImage, ImageFormat, RgbImage};
+use v4l::{
+    Device, FourCC,
+    buffer::Type,
+    io::{mmap::Stream, traits::CaptureStream},
+    video::Capture,
+};
+
+/// Buffers the driver fills in turn while a frame is handed over.
+const BUFFERS: u32 = 4;
+
+/// The frame the capture thread left last, or why it stopped. Each new
+/// frame replaces one not taken yet.
+#[derive(Default)]
+struct Slot {
+    frame: Mutex<Option<io::Result<Vec<u8>>>>,
+    filled: Condvar,
+}
+
+impl Slot {
+    fn put(&self, frame: io::Result<Vec<u8>>) {
+        *self.frame.lock().unwrap_or_else(|err| err.into_inner()) = Some(frame);
+        self.filled.notify_one();
+    }
+    fn take(&self) -> io::Result<Vec<u8>> {
+        let mut frame = self.frame.lock().unwrap_or_else(|err| err.into_inner());
+        loop {
+            if let Some(frame) = frame.take() {
+                return frame;
+            }
+            frame = self
+                .filled
+                .wait(frame)
+                .unwrap_or_else(|err| err.into_inner());
+        }
+    }
+}
+
+/// Frames of a camera, captured on a thread of their own. Only the latest
+/// frame waits to be taken: a newer one replaces it, so a slow terminal
+/// shows the latest picture instead of falling behind.
+pub struct Camera {
+    frames: Arc<Slot>,
+    fourcc: FourCC,
+    width: u32,
+    height: u32,
+    stride: u32,
+    /// Why capturing stopped, once it has.
+    pub error: Option<io::Error>,
+}
+
+impl Camera {
+    /// Opens camera `index` (`/dev/video<index>`) and starts capturing,
+    /// as Motion-JPEG if the camera offers it, else as YUYV.
+    pub fn open(index: usize) -> io::Result<Self> {
+        let dev = Device::new(index)?;
+        let mut format = dev.format()?;
+        format.fourcc = FourCC::new(b"MJPG");
+        let format = dev.set_format(&format)?;
+        if !matches!(&format.fourcc.repr, b"MJPG" | b"YUYV") {
+            return Err(io::Error::other(format!(
+                "camera {}: unsupported pixel format {}",
+                index, format.fourcc
+            )));
+        }
+        let frames = Arc::new(Slot::default());
+        let slot = Arc::clone(&frames);
+        let (ready, started) = mpsc::channel();
+        thread::spawn(move || {
+            let mut stream = match Stream::with_buffers(&dev, Type::VideoCapture, BUFFERS) {
+                Ok(stream) => stream,
+                Err(err) => {
+                    let _ = ready.send(Err(err));
+                    return;
+                }
+            };
+            let _ = ready.send(Ok(()));
+            // Capture until the camera is dropped, leaving only the slot
+            // here.
+            while Arc::strong_count(&slot) > 1 {
+                match stream.next() {
+                    Ok((buf, meta)) => {
+                        slot.put(Ok(buf[..(meta.bytesused as usize).min(buf.len())].to_vec()))
+                    }
+                    Err(err) => {
+                        slot.put(Err(err));
+                        break;
+                    }
+                }
+            }
+        });
+        started.recv().map_err(io::Error::other)??;
+        Ok(Self {
+            frames,
+            fourcc: format.fourcc,
+            width: format.width,
+            height: format.height,
+            stride: format.stride,
+            error: None,
+        })
+    }
+    /// Frame size in `(width, height)` pixels.
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+    fn decode(&self, buf: &[u8]) -> Option<DynamicImage> {
+        match &self.fourcc.repr {
+            b"MJPG" => image::load_from_memory_with_format(buf, ImageFormat::Jpeg).ok(),
+            _ => {
+                yuyv_to_rgb(buf, self.width, self.height, self.stride).map(DynamicImage::ImageRgb8)
+            }
+        }
+    }
+}
+
+impl Iterator for Camera {
+    type Item = DynamicImage;
+
+    /// Waits for the next frame; `None` once capturing stopped, see
+    /// [`Camera::error`]. Frames that don't decode are skipped.
+    fn next(&mut self) -> Option<DynamicImage> {
+        if self.error.is_some() {
+            return None;
+        }
+        loop {
+            match self.frames.take() {
+                Ok(buf) => {
+                    if let Some(frame) = self.decode(&buf) {
+                        return Some(frame);
+                    }
+                }
+                Err(err) => {
+                    self.error = Some(err);
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+/// Converts packed YUYV 4:2:2 (`Y0 U Y1 V` for every two pixels) with rows
+/// `stride` bytes apart to RGB, with the BT.601 coefficients.
+fn yuyv_to_rgb(buf: &[u8], width: u32, height: u32, stride: u32) -> Option<RgbImage> {
+    let stride = (stride as usize).max(width as usize * 2);
+    if buf.len() < stride * (height as usize).saturating_sub(1) + width as usize * 2 {
+        return None;
+    }
+    let mut rgb = Vec::with_capacity(width as usize * height as usize * 3);
+    for row in buf.chunks(stride).take(height as usize) {
+        for px in row[..width as usize * 2].chunks_exact(4) {
+            let (u, v) = (px[1] as f32 - 128.0, px[3] as f32 - 128.0);
+            for y in [px[0], px[2]] {
+                let y = y as f32;
+                rgb.extend([
+                    (y + 1.402 * v).clamp(0.0, 255.0) as u8,
+                    (y - 0.344_136 * u - 0.714_136 * v).clamp(0.0, 255.0) as u8,
+                    (y + 1.772 * u).clamp(0.0, 255.0) as u8,
+                ]);
+            }
+        }
+    }
+    RgbImage::from_raw(width, height, rgb)
+}
@@ -0,0 +1,328 @@
+use std::io;
+
+use serde::{Deserialize, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+    blocks::{BITMAP_SIZE, glyph_bitmap},
+    img::{Graphemes, PixtData},
+};
+
+/// Approximate ink coverage, in percent of the cell, of the printable ASCII
+/// characters `' '` to `'~'` in common monospace fonts.
+#[rustfmt::skip]
+const ASCII_DENSITY: [u8; 95] = [
+    //  ! "   #   $   %   &   '   (   )   *   +   ,  -  .   /
+    0, 10, 9, 32, 33, 31, 34, 4, 13, 13, 17, 13, 6, 6, 4, 13,
+    // 0 1   2   3   4   5   6   7   8   9   :  ;   <   =   >   ?   @
+    31, 20, 25, 25, 26, 27, 30, 19, 33, 30, 8, 10, 13, 14, 13, 18, 38,
+    // A B   C   D   E   F   G   H   I   J   K   L   M   N   O   P   Q   R   S   T   U   V   W   X   Y   Z
+    31, 36, 25, 32, 30, 24, 31, 33, 20, 20, 29, 18, 38, 36, 31, 27, 36, 33, 28, 20, 29, 26, 38, 29, 21, 29,
+    // [ \   ]   ^  _  `
+    17, 13, 17, 10, 8, 4,
+    // a b   c   d   e   f   g   h   i   j   k   l   m   n   o   p   q   r   s   t   u   v   w   x   y   z
+    27, 30, 20, 30, 27, 21, 33, 27, 16, 18, 26, 17, 32, 23, 24, 30, 30, 15, 22, 19, 23, 19, 29, 21, 24, 21,
+    // { |   }  ~
+    18, 12, 18, 9,
+];
+
+/// Density assumed for characters [`ink_density`] has no estimate for,
+/// about that of a lowercase letter.
+pub const DEFAULT_INK_DENSITY: f32 = 0.25;
+
+/// Estimated share (`0.0`-`1.0`) of its cell that `ch` inks: from a
+/// built-in table for printable ASCII, and from the exact shape for block
+/// elements and braille patterns (see [`glyph_bitmap`]). Whitespace is `0`;
+/// anything else gets [`DEFAULT_INK_DENSITY`].
+pub fn ink_density(ch: char) -> f32 {
+    if let Some(shape) = glyph_bitmap(ch) {
+        let (w, h) = BITMAP_SIZE;
+        return shape.count_ones() as f32 / (w * h) as f32;
+    }
+    match ch {
+        ' '..='~' => ASCII_DENSITY[ch as usize - ' ' as usize] as f32 / 100.0,
+        ch if ch.is_whitespace() => 0.0,
+        _ => DEFAULT_INK_DENSITY,
+    }
+}
+
+/// Which axis of a 2-D charset the top pixel of a cell walks along.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Orientation {
+    /// The top pixel picks the column (character within a row), the bottom
+    /// pixel picks the row.
+    #[default]
+    Columns,
+    /// The top pixel picks the row, the bottom pixel picks the column.
+    Rows,
+}
+
+/// Number of source pixels covered by one character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CellSize {
+    pub w: u32,
+    pub h: u32,
+}
+
+impl Default for CellSize {
+    fn default() -> Self {
+        Self { w: 1, h: 2 }
+    }
+}
+
+/// A declarative charset definition, usually loaded from a `.toml` file:
+///
+/// ```toml
+/// name = "ascii"
+/// rows = [" .-~+*%#@"]
+/// thresholds = [20, 50, 80, 110, 140, 170, 200, 230]
+/// orientation = "columns"
+/// cell = { w = 1, h = 2 }
+/// wide = false
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CharsetSpec {
+    #[serde(default)]
+    pub name: String,
+    /// Characters ordered from darkest to brightest, one string per row.
+    /// Every grapheme cluster counts as one character, so flags or emoji
+    /// with modifiers can be used as levels.
+    pub rows: Vec<String>,
+    /// Ascending intensity boundaries between the characters of a row: a
+    /// cell with intensity below `thresholds[i]` uses character `i`. Must
+    /// have one entry fewer than each row has characters.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thresholds: Option<Vec<u8>>,
+    #[serde(default)]
+    pub orientation: Orientation,
+    #[serde(default)]
+    pub cell: CellSize,
+    #[serde(default)]
+    pub wide: bool,
+}
+
+impl CharsetSpec {
+    /// Creates a spec from character rows with default settings.
+    pub fn new<T: Into<String>>(name: T, rows: Vec<Vec<char>>) -> Self {
+        Self {
+            name: name.into(),
+            rows: rows.into_iter().map(|v| v.into_iter().collect()).collect(),
+            thresholds: None,
+            orientation: Orientation::default(),
+            cell: CellSize::default(),
+            wide: false,
+        }
+    }
+
+    /// Parses the plain format: one row of characters per line, surrounding
+    /// whitespace trimmed and blank lines skipped.
+    pub fn from_plain<T: Into<String>>(name: T, input: &str) -> io::Result<Self> {
+        let rows = input
+            .lines()
+            .map(|v| v.trim().chars().collect())
+            .filter(|v: &Vec<char>| !v.is_empty())
+            .collect();
+        let spec = Self::new(name, rows);
+        spec.validate()?;
+        Ok(spec)
+    }
+
+    /// Parses the TOML format.
+    pub fn from_toml(input: &str) -> io::Result<Self> {
+        let spec: Self = toml::from_str(input).map_err(|err| invalid(err.to_string()))?;
+        spec.validate()?;
+        Ok(spec)
+    }
+
+    /// Parses either format, picking TOML when `toml` is set or when the
+    /// content looks like TOML (its first meaningful line is a `key = value`
+    /// pair).
+    pub fn parse<T: Into<String>>(name: T, input: &str, toml: bool) -> io::Result<Self> {
+        let looks_like_toml = input
+            .lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty() && !l.starts_with('#'))
+            .and_then(|l| l.split_once('='))
+            .is_some_and(|(key, _)| {
+                let key = key.trim();
+                !key.is_empty()
+                    && key
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            });
+        if toml || looks_like_toml {
+            Self::from_toml(input)
+        } else {
+            Self::from_plain(name, input)
+        }
+    }
+
+    /// Serializes the spec to TOML.
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("CharsetSpec is always representable as TOML")
+    }
+
+    fn validate(&self) -> io::Result<()> {
+        let Some(first) = self.rows.first() else {
+            return Err(invalid("field `rows`: charset has no characters"));
+        };
+        let cols = first.graphemes(true).count();
+        for (i, row) in self.rows.iter().enumerate() {
+            if row.is_empty() {
+                return Err(invalid(format!("field `rows[{}]`: row is empty", i)));
+            }
+            let len = row.graphemes(true).count();
+            if len != cols {
+                return Err(invalid(format!(
+                    "field `rows[{}]`: expected {} characters like `rows[0]`, found {}",
+                    i, cols, len
+                )));
+            }
+        }
+        if let Some(thresholds) = &self.thresholds {
+            if thresholds.len() + 1 != cols {
+                return Err(invalid(format!(
+                    "field `thresholds`: expected {} entries (one fewer than characters per row), found {}",
+                    cols - 1,
+                    thresholds.len()
+                )));
+            }
+            if thresholds.windows(2).any(|w| w[0] > w[1]) {
+                return Err(invalid("field `thresholds`: values must be ascending"));
+            }
+        }
+        if self.cell.w == 0 || self.cell.h == 0 {
+            return Err(invalid("field `cell`: `w` and `h` must be at least 1"));
+        }
+        Ok(())
+    }
+
+    /// Character rows as laid out for [`PixtData`], honoring `orientation`.
+    pub fn graphemes(&self) -> Graphemes {
+        let Graphemes(rows) = Graphemes::new(&self.rows);
+        match self.orientation {
+            Orientation::Columns => Graphemes(rows),
+            Orientation::Rows => Graphemes(
+                (0..rows[0].len())
+                    .map(|c| rows.iter().map(|row| row[c].clone()).collect())
+                    .collect(),
+            ),
+        }
+    }
+
+    pub fn to_pixt_data(&self) -> PixtData {
+        let mut data = PixtData::from(self.graphemes());
+        if let Some(thresholds) = &self.thresholds {
+            data.set_thresholds(thresholds.clone());
+        }
+        data
+    }
+}
+
+impl From<CharsetSpec> for PixtData {
+    fn from(value: CharsetSpec) -> Self {
+        value.to_pixt_data()
+    }
+}
+
+fn invalid<E: Into<Box<dyn std::error::Error + Send + Sync>>>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{img::IntoPixtData, style::ImgStyle};
+
+    fn braille_dots(ch: char) -> u32 {
+        if ch == ' ' {
+            return 0;
+        }
+        assert!(('\u{2800}'..='\u{28FF}').contains(&ch), "{:?}", ch);
+        (ch as u32 - 0x2800).count_ones()
+    }
+
+    /// Every step right along a row and down along a column of `style`'s
+    /// ramp, as `(before, after)` pairs.
+    fn steps(style: ImgStyle) -> Vec<(char, char)> {
+        let rows = style.into_pixt_data();
+        let across = rows
+            .iter()
+            .flat_map(|row| row.windows(2).map(|w| (w[0], w[1])));
+        let down = rows
+            .windows(2)
+            .flat_map(|pair| pair[0].iter().copied().zip(pair[1].iter().copied()));
+        across.chain(down).collect()
+    }
+
+    #[test]
+    fn built_in_ramps_never_get_lighter() {
+        for style in ImgStyle::ALL {
+            // Subpixel charsets are indexed by pattern, not intensity.
+            if style.subpixels().is_some() {
+                continue;
+            }
+            for (a, b) in steps(style) {
+                assert!(
+                    ink_density(a) <= ink_density(b),
+                    "{:?}: {:?} is denser than the following {:?}",
+                    style,
+                    a,
+                    b
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn braille_ramps_add_dots_every_step() {
+        for style in [ImgStyle::Braills, ImgStyle::Dots] {
+            let rows = style.into_pixt_data();
+            let mut seen: Vec<char> = rows.iter().flatten().copied().collect();
+            seen.sort_unstable();
+            seen.dedup();
+            assert_eq!(
+                seen.len(),
+                rows.iter().map(Vec::len).sum::<usize>(),
+                "{:?}",
+                style
+            );
+            for (a, b) in steps(style) {
+                assert!(
+                    braille_dots(a) < braille_dots(b),
+                    "{:?}: {:?} -> {:?}",
+                    style,
+                    a,
+                    b
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn saved_charsets_load_back_unchanged() {
+        let mut specs: Vec<CharsetSpec> = ImgStyle::ALL
+            .iter()
+            .map(|style| CharsetSpec::new(style.to_string(), style.into_pixt_data()))
+            .collect();
+        specs.push(CharsetSpec {
+            name: "quoted".into(),
+            rows: vec![" '\"\\#=".into(), "👋🏻👋🏽🇺🇳e\u{301}ab".into()],
+            thresholds: Some(vec![10, 60, 120, 180, 240]),
+            orientation: Orientation::Rows,
+            cell: CellSize { w: 2, h: 3 },
+            wide: true,
+        });
+        for spec in specs {
+            let saved = spec.to_toml();
+            // Detected as TOML without the extension hint, too.
+            for toml in [true, false] {
+                let loaded = CharsetSpec::parse("ignored", &saved, toml).unwrap();
+                assert_eq!(loaded, spec, "{}", saved);
+            }
+        }
+    }
+}
@@ -1,45 +1,620 @@
-use clap::{Parser, ValueEnum};
+use clap::{
+    CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum, error::ErrorKind,
+    parser::ValueSource,
+};
 use pixt::{
-    img::{ColorType, OutputType, PixtImg},
+    anim::{FrameClock, FrameDiff, RevealOrder, Stage, hue_cycle, matrix_rain, reveal},
+    blocks::{BlockThreshold, SelectionStrategy},
+    charset::CharsetSpec,
+    color::{AttrRamp, Background, ColorDepth, FilterPreset, Lowlight, Palette, parse_hex},
+    dither::DitherMode,
+    edges::EdgeOptions,
+    grid::{CellGrid, Mirror},
+    html::{self, HtmlFit, HtmlOptions},
+    img::IntoPixtData,
+    img::{CellRect, ColorType, Graphemes, OutputType, PixtData, PixtImg},
+    mask::{DEFAULT_MASK_THRESHOLD, Mask},
+    metadata::{self, Metadata},
+    metrics::{HISTOGRAM_BUCKETS, IntensityHistogram, PERCENTILES},
+    preprocess::{
+        BlendMode, Luminance, area_resize, blend, block_average, clahe, intensity_plane,
+        trim_transparent,
+    },
+    quantize::{dominant_colors, quantize},
+    render::render_frames,
+    size::{target_size, validate_dimension},
     style::ImgStyle,
+    svg::SvgMode,
 };
 
 use std::{
-    fs,
-    io::{self, BufReader, BufWriter, Read},
-    path::PathBuf,
+    env, fs,
+    io::{self, BufWriter, Cursor, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
+    process::{self, Child, ChildStdin, Stdio},
+    time::Duration,
+};
+
+use crossterm::{
+    cursor::Show,
+    style::ResetColor,
+    terminal::{DisableLineWrap, EnableLineWrap},
+};
+use image::{
+    AnimationDecoder, DynamicImage, ImageFormat, ImageReader, RgbImage, RgbaImage,
+    codecs::gif::GifDecoder, imageops::FilterType,
 };
 
-use image::{ImageReader, imageops::FilterType};
+/// Distance used by `--bg auto`.
+const DEFAULT_BG_DISTANCE: f32 = 32.0;
 
 #[derive(Debug, Clone, Parser)]
+#[command(about, version, args_conflicts_with_subcommands = true)]
 pub struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Output width in terminal characters
-    #[arg(short = 'w', long = "width")]
+    #[arg(short = 'w', long = "width", env = "PIXT_WIDTH", value_parser = parse_width)]
     width: Option<u32>,
 
     /// Output height in terminal characters
-    #[arg(short = 'H', long = "height")]
+    #[arg(short = 'H', long = "height", env = "PIXT_HEIGHT", value_parser = parse_height)]
     height: Option<u32>,
 
+    /// Output width used when no size is given and the terminal size can't
+    /// be queried, e.g. without a controlling terminal
+    #[arg(
+        long = "default-width",
+        env = "PIXT_DEFAULT_WIDTH",
+        value_parser = parse_width,
+        default_value_t = 80,
+    )]
+    default_width: u32,
+
     /// Enable colored output
     #[arg(short = 'c', long = "colored")]
     colored: bool,
 
+    /// When to emit color escapes: `auto` only on a color-capable terminal
+    /// and with `NO_COLOR` unset, `always` even into pipes and files and
+    /// despite `NO_COLOR`, `never` strips colors regardless of `-c`
+    #[arg(
+        long = "color",
+        env = "PIXT_COLOR_MODE",
+        value_enum,
+        default_value_t = ColorMode::default(),
+    )]
+    color: ColorMode,
+
+    /// Which pixel colors `-c` applies to each cell, instead of the style's
+    /// own choice
+    #[arg(long = "color-mode", value_enum)]
+    color_mode: Option<ColorTypeOps>,
+
     /// Style of Output Image
     #[arg(
         short = 's',
         long = "style",
+        env = "PIXT_STYLE",
         value_enum,
         default_value_t = StyleOps::default(),
     )]
     style: StyleOps,
 
-    /// Optput path.<txt|html|svg>
+    /// Charset definition file (TOML, or one row of characters per line)
+    #[arg(long = "charset-file", env = "PIXT_CHARSET")]
+    charset_file: Option<PathBuf>,
+
+    /// Color filter preset applied to the output colors
+    #[arg(
+        long = "filter-preset",
+        value_enum,
+        default_value_t = FilterOps::default(),
+    )]
+    filter_preset: FilterOps,
+
+    /// Colors of terminal output: 24-bit `truecolor`, or the xterm `256`
+    /// color palette for terminals and logs without truecolor support
+    #[arg(
+        long = "color-depth",
+        value_enum,
+        default_value_t = ColorDepthOps::default(),
+    )]
+    color_depth: ColorDepthOps,
+
+    /// Palette file (`#rrggbb` per line, or GIMP .gpl) of the 16 colors
+    /// `--color-depth 16` matches against, in ANSI order: black, red,
+    /// green, yellow, blue, magenta, cyan, white, then the bright ones.
+    /// Defaults to the VGA colors
+    #[arg(long = "ansi-palette", value_name = "FILE")]
+    ansi_palette: Option<PathBuf>,
+
+    /// How source pixels are sampled into output cells
+    #[arg(
+        long = "sampling",
+        value_enum,
+        default_value_t = SamplingOps::default(),
+    )]
+    sampling: SamplingOps,
+
+    /// Map every WxH block of source pixels to one character, skipping the resize
+    #[arg(
+        long = "block-size",
+        value_parser = parse_size,
+        conflicts_with_all = ["width", "height", "sampling"],
+    )]
+    block_size: Option<(u32, u32)>,
+
+    /// What to do with partial blocks at the right/bottom edges
+    #[arg(
+        long = "block-remainder",
+        value_enum,
+        default_value_t = RemainderOps::default(),
+    )]
+    block_remainder: RemainderOps,
+
+    /// Keep an explicit --width wider than the terminal instead of clamping
+    /// it; line wrapping is disabled while the image is printed
+    #[arg(long = "no-clamp")]
+    no_clamp: bool,
+
+    /// Width:height ratio of one character cell as displayed by your font
+    /// [default: 0.5, or the output format's own cell metrics]
+    #[arg(long = "cell-aspect", value_parser = parse_positive_f32)]
+    cell_aspect: Option<f32>,
+
+    /// Emit every cell twice so rendered pixels look square
+    #[arg(long = "wide")]
+    wide: bool,
+
+    /// Drop trailing spaces and trailing blank rows
+    #[arg(long = "trim-lines")]
+    trim_lines: bool,
+
+    /// Boost local contrast (CLAHE) before picking characters; colors are untouched
+    #[arg(
+        long = "clahe",
+        value_name = "CLIP_LIMIT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "2.0",
+        value_parser = parse_positive_f32,
+    )]
+    clahe: Option<f32>,
+
+    /// Render cells within DISTANCE (RGB, 0-442) of COLOR as plain spaces;
+    /// COLOR may be `auto` to sample the image corners
+    #[arg(long = "bg-threshold", value_name = "COLOR:DISTANCE", value_parser = parse_bg_threshold)]
+    bg_threshold: Option<(Option<[u8; 3]>, f32)>,
+
+    /// Shorthand for `--bg-threshold auto:32`
+    #[arg(long = "bg", value_enum, conflicts_with = "bg_threshold")]
+    bg: Option<BgOps>,
+
+    /// Draw cells darker than DIM dim and cells at least BOLD bright bold, in
+    /// terminal and HTML output
+    #[arg(
+        long = "attr-ramp",
+        value_name = "DIM,BOLD",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "85,171",
+        value_parser = parse_attr_ramp,
+    )]
+    attr_ramp: Option<AttrRamp>,
+
+    /// Keep full color only inside this rectangle of output cells (repeatable)
+    #[arg(long = "highlight", value_name = "X,Y,WxH", value_parser = parse_highlight)]
+    highlight: Vec<CellRect>,
+
+    /// How cells outside `--highlight` rectangles are drawn
+    #[arg(
+        long = "highlight-outside",
+        value_enum,
+        default_value_t = LowlightOps::default(),
+    )]
+    highlight_outside: LowlightOps,
+
+    /// Composite another image over the source before converting, at
+    /// opacity ALPHA (0-1, default 1)
+    #[arg(long = "blend", value_name = "IMAGE[:ALPHA]", value_parser = parse_blend)]
+    blend: Option<(PathBuf, f32)>,
+
+    /// How `--blend` combines the two images
+    #[arg(
+        long = "blend-mode",
+        value_enum,
+        requires = "blend",
+        default_value_t = BlendModeOps::default(),
+    )]
+    blend_mode: BlendModeOps,
+
+    /// Image whose dark parts blank out the art, resized to the source
+    #[arg(long = "mask")]
+    mask: Option<PathBuf>,
+
+    /// Blank out the light parts of `--mask` instead
+    #[arg(long = "mask-invert", requires = "mask")]
+    mask_invert: bool,
+
+    /// Mask brightness (0-255) from which the art shows
+    #[arg(
+        long = "mask-threshold",
+        requires = "mask",
+        default_value_t = DEFAULT_MASK_THRESHOLD,
+    )]
+    mask_threshold: u8,
+
+    /// Character drawn in place of masked-out cells
+    #[arg(long = "mask-fill", requires = "mask", default_value_t = ' ')]
+    mask_fill: char,
+
+    /// Leave pixels with an alpha below N out, so the terminal or page
+    /// shows through transparent parts [default N: 128]
+    #[arg(
+        long = "alpha-threshold",
+        value_name = "N",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "128",
+        value_parser = clap::value_parser!(u8).range(1..),
+    )]
+    alpha_threshold: Option<u8>,
+
+    /// Draw cells on strong edges with `|`, `/`, `-`, `\` or `_` along the
+    /// edge instead of the charset, from a gradient magnitude of N (0-255)
+    /// [default N: 96]
+    #[arg(
+        long = "edges",
+        value_name = "N",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "96",
+    )]
+    edges: Option<u8>,
+
+    /// Character drawn in place of fully transparent cells
+    #[arg(
+        long = "alpha-fill",
+        requires = "alpha_threshold",
+        default_value_t = ' '
+    )]
+    alpha_fill: char,
+
+    /// Crop away margins of pixels with an alpha of at most ALPHA before
+    /// resizing [default ALPHA: 0]
+    #[arg(
+        long = "trim",
+        value_name = "ALPHA",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "0",
+    )]
+    trim: Option<u8>,
+
+    /// Repeat the rendered art in a grid of C columns by R rows
+    #[arg(long = "tile", value_name = "CxR", value_parser = parse_size)]
+    tile: Option<(u32, u32)>,
+
+    /// Mirror the rendered art
+    #[arg(long = "mirror", value_enum)]
+    mirror: Option<MirrorOps>,
+
+    /// Composite transparent pixels over COLOR (`#RRGGBB`) [default: the
+    /// --html-bg color for HTML and SVG, black otherwise]
+    #[arg(long = "alpha-bg", value_name = "COLOR", value_parser = parse_color)]
+    alpha_bg: Option<[u8; 3]>,
+
+    /// Reverse the brightness ramp, for terminals with a light background:
+    /// white renders blank instead of as the densest character
+    #[arg(long = "invert")]
+    invert: bool,
+
+    /// Gamma applied to brightness before characters are picked; above 1
+    /// brightens midtones
+    #[arg(long = "gamma", default_value_t = 1.0, value_parser = parse_positive_f32)]
+    gamma: f32,
+
+    /// How RGB channels are weighted into the brightness characters are
+    /// picked by
+    #[arg(long = "luminance", value_enum, default_value_t = LuminanceOps::default())]
+    luminance: LuminanceOps,
+
+    /// How intensities are spread over the characters: none, blue-noise,
+    /// ordered[:2|4|8] (Bayer matrix) or floyd-steinberg
+    #[arg(
+        long = "dither",
+        value_name = "MODE",
+        default_value = "none",
+        value_parser = parse_dither,
+    )]
+    dither: DitherMode,
+
+    /// Which pixels of sextant and braille-dots cells are lit: those above
+    /// the `mean` of their cell, above the `otsu` threshold of the whole
+    /// image, or at least a fixed intensity (0-255)
+    #[arg(
+        long = "threshold",
+        value_name = "MODE",
+        default_value = "mean",
+        value_parser = parse_block_threshold,
+    )]
+    threshold: BlockThreshold,
+
+    /// How characters are chosen: by the `intensity` of each cell, or
+    /// `structural`ly by the glyph whose shape best matches the cell (block,
+    /// quadrant and braille characters only), colored by the pixels under
+    /// and around it
+    #[arg(long = "match", value_enum, default_value_t = MatchOps::default())]
+    selection: MatchOps,
+
+    /// Order the characters of a custom or `--charset` ramp from the least
+    /// to the most inked, so they need not be typed in order
+    #[arg(long = "sort-charset")]
+    sort_charset: bool,
+
+    /// TrueType/OpenType font the art is viewed in: characters are picked
+    /// by their measured ink coverage in it instead of evenly spaced
+    /// intensities
+    #[cfg(feature = "font")]
+    #[arg(long = "font", value_name = "FILE")]
+    font: Option<PathBuf>,
+
+    /// Animate the art appearing on the terminal, N cells per frame (rows
+    /// with `--reveal-order rows`)
+    #[arg(
+        long = "reveal",
+        value_name = "N",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "8",
+        value_parser = clap::value_parser!(u32).range(1..),
+    )]
+    reveal: Option<u32>,
+
+    /// Order in which `--reveal` uncovers the cells
+    #[arg(
+        long = "reveal-order",
+        value_enum,
+        default_value_t = RevealOps::default(),
+    )]
+    reveal_order: RevealOps,
+
+    /// Animate the art on the terminal: `matrix` rains it in,
+    /// `hue-cycle[:SECONDS]` rotates its colors until a key is pressed (one
+    /// turn every SECONDS, 4 by default)
+    #[arg(
+        long = "effect",
+        value_name = "EFFECT",
+        value_parser = parse_effect,
+        conflicts_with = "reveal",
+    )]
+    effect: Option<EffectOps>,
+
+    /// Seed for randomized animations, to replay one exactly [default: random]
+    #[arg(long = "seed")]
+    seed: Option<u64>,
+
+    /// Delay between animation frames in milliseconds
+    #[arg(long = "frame-delay", value_name = "MS", default_value_t = 16)]
+    frame_delay: u64,
+
+    /// Show terminal output taller than the terminal through $PAGER
+    /// (default `less -R`); never used for animations or when stdout isn't
+    /// a terminal
+    #[arg(
+        long = "pager",
+        value_enum,
+        default_value_t = PagerOps::default(),
+    )]
+    pager: PagerOps,
+
+    /// Keep a finished animation on screen until a key is pressed
+    #[arg(long = "hold")]
+    hold: bool,
+
+    /// Play animated GIFs on the terminal at their own frame delays before
+    /// printing the first frame; any key stops them. `auto` plays those with
+    /// several frames when stdout is a terminal, `always` (also `--animate`
+    /// alone) fails for inputs it can't play
+    #[arg(
+        long = "animate",
+        value_name = "WHEN",
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_value_t = AnimateOps::default(),
+        default_missing_value = "always",
+        conflicts_with_all = ["reveal", "effect"],
+    )]
+    animate: AnimateOps,
+
+    /// Present every frame of `--animate` at once on terminals supporting
+    /// synchronized output (kitty, WezTerm, iTerm2 and others), instead of
+    /// tearing; harmless elsewhere
+    #[arg(long = "sync")]
+    sync: bool,
+
+    /// Play `--animate` at this many frames per second instead of the
+    /// delays stored in the GIF; also the rate of `--raw-video` and
+    /// `--camera`
+    #[arg(long = "fps", value_parser = parse_positive_f32)]
+    fps: Option<f32>,
+
+    /// How many times `--animate` plays, or `infinite` (also `0`) to repeat
+    /// until a key is pressed
+    #[arg(
+        long = "loop",
+        value_name = "N",
+        default_value = "1",
+        value_parser = parse_loops,
+    )]
+    loops: u32,
+
+    /// Render only frame N (from 0) of an animated GIF, without playing it
+    #[arg(long = "frame", value_name = "N", conflicts_with = "animate")]
+    frame: Option<u32>,
+
+    /// Play raw video frames of WxH pixels read from stdin instead of
+    /// images, e.g. `ffmpeg -i clip.mp4 -f rawvideo -pix_fmt rgb24 -`, at
+    /// FPS frames per second (or `--fps`), else as fast as they arrive
+    #[arg(
+        long = "raw-video",
+        value_name = "WxH[@FPS]",
+        value_parser = parse_raw_video,
+        conflicts_with_all = ["animate", "frame", "output"],
+    )]
+    raw_video: Option<RawVideo>,
+
+    /// Show a live preview of camera N (`/dev/videoN`), at `--fps` frames
+    /// per second; any key stops it
+    #[cfg(all(feature = "camera", target_os = "linux"))]
+    #[arg(
+        long = "camera",
+        value_name = "N",
+        conflicts_with_all = ["raw_video", "animate", "frame", "output"],
+    )]
+    camera: Option<usize>,
+
+    /// Pixel layout of the `--raw-video` frames
+    #[arg(
+        long = "pixel-format",
+        value_enum,
+        default_value_t = PixelFormatOps::default(),
+        requires = "raw_video"
+    )]
+    pixel_format: PixelFormatOps,
+
+    /// Quantize the image to at most this many colors before rendering
+    #[arg(long = "colors", value_parser = clap::value_parser!(u16).range(1..))]
+    colors: Option<u16>,
+
+    /// Snap output colors to a palette file (`#rrggbb` per line, or GIMP .gpl)
+    #[arg(long = "palette-file", conflicts_with = "palette")]
+    palette_file: Option<PathBuf>,
+
+    /// Snap output colors to a built-in palette
+    #[arg(long = "palette", value_enum)]
+    palette: Option<PaletteOps>,
+
+    /// Print MSE/SSIM scores comparing the render against the source
+    #[arg(long = "quality-report")]
+    quality_report: bool,
+
+    /// Print extra information (e.g. the chosen palette) to stderr
+    #[arg(short = 'v', long = "verbose")]
+    verbose: bool,
+
+    /// Print the sizes, formats and outputs a render would produce, without
+    /// decoding the images or writing anything
+    #[arg(long = "dry-run")]
+    dry_run: bool,
+
+    /// Output path; its extension (txt, ans, html or svg) picks the format
+    /// unless --format is given, anything else is plain text. Repeat to
+    /// write several formats from one render
     #[arg(short = 'o', long = "output")]
-    output: Option<PathBuf>,
+    output: Vec<PathBuf>,
+
+    /// Format of every output, whatever the file extensions say
+    /// [default: from the extension, or the terminal for stdout]
+    #[arg(long = "format", env = "PIXT_FORMAT", value_enum)]
+    format: Option<FormatOps>,
+
+    /// In HTML output, show the source image next to the art, downscaled to
+    /// at most MAX_EDGE pixels
+    #[arg(
+        long = "html-include-source",
+        value_name = "MAX_EDGE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "512",
+        value_parser = clap::value_parser!(u32).range(1..),
+    )]
+    html_include_source: Option<u32>,
+
+    /// In HTML output, give every character a tooltip with its cell
+    /// position, colors and charset level (about triples the size)
+    #[arg(long = "html-debug")]
+    html_debug: bool,
+
+    /// CSS color of uncolored text in HTML output
+    #[arg(long = "html-fg", value_name = "COLOR", default_value = "#fff")]
+    html_fg: String,
+
+    /// CSS color of the HTML page background
+    #[arg(long = "html-bg", value_name = "COLOR", default_value = "#191919")]
+    html_bg: String,
+
+    /// CSS font-family of HTML output
+    #[arg(long = "html-font", value_name = "FAMILY", default_value = "monospace")]
+    html_font: String,
+
+    /// Font size of HTML output in pixels, unless --html-fit scales it
+    #[arg(
+        long = "html-font-size",
+        value_name = "PX",
+        default_value_t = 10,
+        value_parser = clap::value_parser!(u32).range(1..),
+    )]
+    html_font_size: u32,
+
+    /// CSS file appended to the stylesheet of HTML output
+    #[arg(long = "html-css", value_name = "FILE")]
+    html_css: Option<PathBuf>,
 
-    /// Input file paths
+    /// How HTML output scales with the browser window
+    #[arg(
+        long = "html-fit",
+        value_enum,
+        default_value_t = HtmlFitOps::default(),
+    )]
+    html_fit: HtmlFitOps,
+
+    /// Make HTML and SVG output smaller: same-colored neighbours share one
+    /// element, repeated HTML colors become shared classes, colors use
+    /// their short form, coordinates are rounded and headers are compacted.
+    /// Images with few colors shrink most
+    #[arg(long = "minify")]
+    minify: bool,
+
+    /// How SVG output draws the cells
+    #[arg(
+        long = "svg-mode",
+        value_enum,
+        default_value_t = SvgModeOps::default(),
+    )]
+    svg_mode: SvgModeOps,
+
+    /// In HTML output, draw brighter cells in heavier font weights (300-900);
+    /// best with a variable-weight monospace font
+    #[arg(long = "html-weight-ramp")]
+    html_weight_ramp: bool,
+
+    /// Start each output file with a comment recording the pixt version,
+    /// source image, sizes, style, colors and arguments
+    #[arg(long = "emit-metadata", requires = "output")]
+    emit_metadata: bool,
+
+    /// Add to the end of the output file instead of replacing it
+    #[arg(long = "append", requires = "output")]
+    append: bool,
+
+    /// Written between images [default: a blank line, `<hr/>` for HTML]
+    #[arg(long = "separator")]
+    separator: Option<String>,
+
+    /// Add a table of the charset's characters and the intensities (0-255)
+    /// each stands for below the art
+    #[arg(long = "legend")]
+    legend: bool,
+
+    /// Write the legend to this file instead, formatted by its extension
+    #[arg(long = "legend-file")]
+    legend_file: Option<PathBuf>,
+
+    /// Input file paths, or `-` for an image piped to stdin
     #[arg(num_args = 1..)]
     files: Vec<PathBuf>,
 }
@@ -50,166 +625,2706 @@ enum StyleOps {
     Pixel,
     Ascii,
     Block,
+    #[value(alias = "braille")]
     Braills,
     Dots,
+    /// Colored spaces only; needs --colored
+    Solid,
+    /// `▀` colored by the top and bottom pixel; needs --colored
+    #[value(alias = "halfblock")]
+    HalfBlock,
+    /// Quadrant blocks from 2x2 pixels, two colors per character; best
+    /// with --colored
+    Quadrant,
+    /// Sextants from 2x3 pixels; needs a font with the Unicode 13 Symbols
+    /// for Legacy Computing (e.g. kitty, foot, recent VTE), best with
+    /// --colored
+    Sextant,
+    /// Braille dots from 2x4 pixels, lit by --threshold
+    BrailleDots,
     Custom,
     FromFile,
 }
 
-impl Cli {
-    pub fn run(&self) -> io::Result<()> {
-        if let Some(path) = &self.output {
-            let file = fs::File::create(path).unwrap_or_else(|err| {
-                eprintln!("{}", err);
-                std::process::exit(1);
-            });
-            let stdout = BufWriter::new(file);
-            render_app(stdout, self)?;
-        } else {
-            render_app(io::stdout(), self)?;
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+enum ColorMode {
+    /// Colors only when stdout is a color terminal and `NO_COLOR` is unset
+    #[default]
+    Auto,
+    /// Colors even when redirected or with `NO_COLOR` set
+    Always,
+    /// Never colors
+    Never,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+enum ReportFormatOps {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// JSON, for scripts
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+enum LuminanceOps {
+    /// Plain average of the RGB channels
+    Average,
+    /// ITU-R BT.601 luma weights
+    #[default]
+    Rec601,
+    /// ITU-R BT.709 luma weights
+    Rec709,
+}
+
+impl From<LuminanceOps> for Luminance {
+    fn from(value: LuminanceOps) -> Self {
+        match value {
+            LuminanceOps::Average => Self::Average,
+            LuminanceOps::Rec601 => Self::Rec601,
+            LuminanceOps::Rec709 => Self::Rec709,
         }
-        Ok(())
     }
 }
 
-fn render_app<W: io::Write>(mut stdout: W, app: &Cli) -> io::Result<()> {
-    // Extract image paths if the `--style | -s custom` option is provided in the CLI.
-    // - If the `custom` style is selected but no image path is provided, print an error and exit.
-    // - Otherwise, if `custom` is selected, skip the first argument (which may be the style
-    //   option) and collect the rest as image paths.
-    // - If a different style is selected, use all provided arguments as they are.
-    let args = if matches!(app.style, StyleOps::Custom | StyleOps::FromFile) && app.files.len() < 2
-    {
-        eprintln!("ERROR: Image Path Not Found");
-        std::process::exit(1);
-    } else if matches!(app.style, StyleOps::Custom | StyleOps::FromFile) {
-        app.files.iter().skip(1).cloned().collect()
-    } else {
-        app.files.clone()
-    };
-    for ref path in args {
-        let img = ImageReader::open(path)?.decode().unwrap_or_else(|err| {
-            eprintln!("{}", err);
-            std::process::exit(1);
-        });
-        let filter = FilterType::CatmullRom;
-        let img = match (app.width, app.height) {
-            (Some(width), Some(height)) => img.resize_exact(width, height, filter),
-            (Some(width), None) => img.resize(width, (width * img.height()) / img.width(), filter),
-            (None, Some(height)) => img.resize(
-                std::cmp::min((height * img.width()) / img.height(), {
-                    let (w, _) = crossterm::terminal::size()?;
-                    w as u32
-                }),
-                height,
-                filter,
-            ),
-            (None, None) => {
-                let (w, _) = crossterm::terminal::size()?;
-                let h = (w as u32 * img.height()) / img.width();
-                img.resize(w as u32, h, filter)
-            }
-        };
-        let output_type = match path.extension() {
-            Some(v) if v == "html" => OutputType::html(),
-            Some(v) if v == "svg" => OutputType::svg(),
-            _ => OutputType::term(),
-        };
-        match (&app.style, &app.colored) {
-            (StyleOps::Ascii, true) => {
-                let pi = PixtImg::new(ImgStyle::Ascii, output_type.color(ColorType::AvgFgOnly));
-                pi.print(&img, &mut stdout)?;
-            }
-            (StyleOps::Ascii, false) => {
-                let pi = PixtImg::new(ImgStyle::Ascii, output_type.color(ColorType::None));
-                pi.print(&img, &mut stdout)?;
-            }
-            (StyleOps::Block, true) => {
-                let pi = PixtImg::new(ImgStyle::Block, output_type.color(ColorType::AvgFgOnly));
-                pi.print(&img, &mut stdout)?;
-            }
-            (StyleOps::Block, false) => {
-                let pi = PixtImg::new(ImgStyle::Block, output_type.color(ColorType::None));
-                pi.print(&img, &mut stdout)?;
-            }
-            (StyleOps::Pixel, true) => {
-                let pi = PixtImg::new(ImgStyle::Pixel, output_type.color(ColorType::FgTopBgDown));
-                pi.print(&img, &mut stdout)?;
-            }
-            (StyleOps::Pixel, false) => {
-                let pi = PixtImg::new(ImgStyle::Pixel, output_type.color(ColorType::None));
-                pi.print(&img, &mut stdout)?;
-            }
-            (StyleOps::Braills, true) => {
-                let pi = PixtImg::new(ImgStyle::Braills, output_type.color(ColorType::AvgFgOnly));
-                pi.print(&img, &mut stdout)?;
-            }
-            (StyleOps::Braills, false) => {
-                let pi = PixtImg::new(ImgStyle::Braills, output_type.color(ColorType::None));
-                pi.print(&img, &mut stdout)?;
-            }
-            (StyleOps::Dots, true) => {
-                let pi = PixtImg::new(ImgStyle::Dots, output_type.color(ColorType::AvgFgOnly));
-                pi.print(&img, &mut stdout)?;
-            }
-            (StyleOps::Dots, false) => {
-                let pi = PixtImg::new(ImgStyle::Dots, output_type.color(ColorType::None));
-                pi.print(&img, &mut stdout)?;
-            }
-            (StyleOps::Custom, false) => {
-                let input = app.files[0]
-                    .clone()
-                    .into_os_string()
-                    .into_string()
-                    .unwrap_or_else(|err| {
-                        eprintln!("ERROR: envalid chars: '{:?}'", err);
-                        std::process::exit(1)
-                    })
-                    .chars()
-                    .collect::<Vec<char>>();
-                let pi = PixtImg::new(input, output_type.color(ColorType::None));
-                pi.print(&img, &mut stdout)?;
-            }
-            (StyleOps::Custom, true) => {
-                let input = app.files[0]
-                    .clone()
-                    .into_os_string()
-                    .into_string()
-                    .unwrap_or_else(|err| {
-                        eprintln!("ERROR: envalid chars: '{:?}'", err);
-                        std::process::exit(1)
-                    })
-                    .chars()
-                    .collect::<Vec<char>>();
-                let pi = PixtImg::new(input, output_type.color(ColorType::AvgFgOnly));
-                pi.print(&img, &mut stdout)?;
-            }
-            (StyleOps::FromFile, _) => {
-                let path = app.files[0]
-                    .clone()
-                    .into_os_string()
-                    .into_string()
-                    .unwrap_or_else(|err| {
-                        eprintln!("ERROR: envalid chars: '{:?}'", err);
-                        std::process::exit(1);
-                    });
-                let file = fs::File::open(path).unwrap_or_else(|err| {
-                    eprintln!("{}", err);
-                    std::process::exit(1)
-                });
-                let mut reader = BufReader::new(file);
-                let mut val = String::new();
-                reader.read_to_string(&mut val)?;
-                let data = val
-                    .lines()
-                    .map(|v| v.trim().chars().collect())
-                    .filter(|v: &Vec<char>| !v.is_empty())
-                    .collect::<Vec<Vec<char>>>();
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+enum PagerOps {
+    /// Page output taller than the terminal
+    #[default]
+    Auto,
+    Never,
+    /// Page all output, however short
+    Always,
+}
 
-                let pi = PixtImg::new(data, output_type.color(ColorType::AvgFgOnly));
-                pi.print(&img, &mut stdout)?;
-            }
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+enum AnimateOps {
+    /// Play multi-frame GIFs when stdout is a terminal
+    #[default]
+    Auto,
+    /// Play every input, failing for those that aren't animated GIFs
+    Always,
+    Never,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+enum PixelFormatOps {
+    /// Three bytes per pixel, as ffmpeg's `-pix_fmt rgb24`
+    #[default]
+    Rgb24,
+    /// Four bytes per pixel, as ffmpeg's `-pix_fmt rgba`
+    Rgba,
+}
+
+impl PixelFormatOps {
+    fn bytes(self) -> usize {
+        match self {
+            Self::Rgb24 => 3,
+            Self::Rgba => 4,
         }
     }
-    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+enum SamplingOps {
+    /// Resize with a CatmullRom filter, then sample each cell
+    #[default]
+    Resize,
+    /// Average every source pixel covered by a cell
+    Area,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+enum RemainderOps {
+    /// Average partial blocks over the pixels they cover
+    #[default]
+    Pad,
+    /// Drop partial blocks
+    Truncate,
+}
+
+#[derive(Debug, Clone, PartialEq, ValueEnum)]
+enum ColorTypeOps {
+    /// Average of both pixels as the foreground
+    AvgFg,
+    /// Average of both pixels as the background
+    AvgBg,
+    /// Top pixel as foreground, bottom pixel as background
+    FgTopBgDown,
+    /// Top pixel as background, bottom pixel as foreground
+    BgTopFgDown,
+    /// Top pixel as the foreground, no background
+    TopFg,
+    /// Bottom pixel as the foreground, no background
+    BottomFg,
+}
+
+impl From<ColorTypeOps> for ColorType {
+    fn from(value: ColorTypeOps) -> Self {
+        match value {
+            ColorTypeOps::AvgFg => Self::AvgFgOnly,
+            ColorTypeOps::AvgBg => Self::AvgBgOnly,
+            ColorTypeOps::FgTopBgDown => Self::FgTopBgDown,
+            ColorTypeOps::BgTopFgDown => Self::BgTopFgDown,
+            ColorTypeOps::TopFg => Self::TopFgOnly,
+            ColorTypeOps::BottomFg => Self::BottomFgOnly,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, ValueEnum)]
+enum BlendModeOps {
+    /// Draw the blended image over the source
+    #[default]
+    Normal,
+    /// Darken: multiply the colors
+    Multiply,
+    /// Lighten: multiply the inverted colors
+    Screen,
+    /// Multiply dark parts of the source, screen light ones
+    Overlay,
+}
+
+impl From<BlendModeOps> for BlendMode {
+    fn from(value: BlendModeOps) -> Self {
+        match value {
+            BlendModeOps::Normal => Self::Normal,
+            BlendModeOps::Multiply => Self::Multiply,
+            BlendModeOps::Screen => Self::Screen,
+            BlendModeOps::Overlay => Self::Overlay,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, ValueEnum)]
+enum FilterOps {
+    #[default]
+    None,
+    /// Desaturate colors
+    #[value(alias = "greyscale")]
+    Grayscale,
+    /// Warm brownish tint
+    Sepia,
+    /// Shift colors towards blue
+    Cool,
+    /// Shift colors towards red
+    Warm,
+    /// Invert colors and brightness
+    Negative,
+}
+
+impl From<FilterOps> for FilterPreset {
+    fn from(value: FilterOps) -> Self {
+        match value {
+            FilterOps::None => Self::None,
+            FilterOps::Grayscale => Self::Grayscale,
+            FilterOps::Sepia => Self::Sepia,
+            FilterOps::Cool => Self::Cool,
+            FilterOps::Warm => Self::Warm,
+            FilterOps::Negative => Self::Negative,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, ValueEnum)]
+enum ColorDepthOps {
+    /// 24-bit RGB
+    #[default]
+    #[value(alias = "24bit")]
+    Truecolor,
+    /// The xterm 256-color palette
+    #[value(name = "256", alias = "ansi256")]
+    Ansi256,
+    /// The 16 standard ANSI colors, see `--ansi-palette`
+    #[value(name = "16", alias = "ansi16")]
+    Ansi16,
+}
+
+impl From<ColorDepthOps> for ColorDepth {
+    fn from(value: ColorDepthOps) -> Self {
+        match value {
+            ColorDepthOps::Truecolor => Self::TrueColor,
+            ColorDepthOps::Ansi256 => Self::Ansi256,
+            ColorDepthOps::Ansi16 => Self::Ansi16,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, ValueEnum)]
+enum PaletteOps {
+    /// PICO-8 fantasy console (16 colors)
+    Pico8,
+    /// Commodore 64 (16 colors)
+    C64,
+}
+
+impl From<PaletteOps> for Palette {
+    fn from(value: PaletteOps) -> Self {
+        match value {
+            PaletteOps::Pico8 => Self::pico8(),
+            PaletteOps::C64 => Self::c64(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, ValueEnum)]
+enum LowlightOps {
+    #[default]
+    Grayscale,
+    Dim,
+}
+
+impl From<LowlightOps> for Lowlight {
+    fn from(value: LowlightOps) -> Self {
+        match value {
+            LowlightOps::Grayscale => Self::Grayscale,
+            LowlightOps::Dim => Self::Dim,
+        }
+    }
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum MirrorOps {
+    /// Left to right
+    H,
+    /// Top to bottom
+    V,
+    Both,
+}
+
+impl From<MirrorOps> for Mirror {
+    fn from(value: MirrorOps) -> Self {
+        match value {
+            MirrorOps::H => Self::Horizontal,
+            MirrorOps::V => Self::Vertical,
+            MirrorOps::Both => Self::Both,
+        }
+    }
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum FormatOps {
+    /// Plain text without colors
+    Text,
+    /// Text with ANSI color escapes
+    #[value(alias = "term")]
+    Ansi,
+    Html,
+    Svg,
+}
+
+impl From<FormatOps> for OutputType {
+    fn from(value: FormatOps) -> Self {
+        match value {
+            FormatOps::Text => Self::text(),
+            FormatOps::Ansi => Self::term(),
+            FormatOps::Html => Self::html(),
+            FormatOps::Svg => Self::svg(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, ValueEnum)]
+enum HtmlFitOps {
+    /// Fixed 10px font
+    #[default]
+    Fixed,
+    /// Scale the font so the art spans the page width
+    Width,
+    /// Scale the font so the art fits the page width and height
+    Screen,
+}
+
+impl From<HtmlFitOps> for HtmlFit {
+    fn from(value: HtmlFitOps) -> Self {
+        match value {
+            HtmlFitOps::Fixed => Self::Fixed,
+            HtmlFitOps::Width => Self::Width,
+            HtmlFitOps::Screen => Self::Screen,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, ValueEnum)]
+enum MatchOps {
+    #[default]
+    Intensity,
+    Structural,
+}
+
+impl From<MatchOps> for SelectionStrategy {
+    fn from(value: MatchOps) -> Self {
+        match value {
+            MatchOps::Intensity => Self::Intensity,
+            MatchOps::Structural => Self::Structural,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, ValueEnum)]
+enum SvgModeOps {
+    /// Characters as SVG text
+    #[default]
+    Text,
+    /// Colored rectangles, one or two per cell; needs no font
+    Rects,
+}
+
+impl From<SvgModeOps> for SvgMode {
+    fn from(value: SvgModeOps) -> Self {
+        match value {
+            SvgModeOps::Text => Self::Text,
+            SvgModeOps::Rects => Self::Rects,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, ValueEnum)]
+enum RevealOps {
+    /// Left to right, top to bottom
+    #[default]
+    Sequential,
+    /// A whole row at a time
+    Rows,
+    /// Dissolve in random order, see `--seed`
+    Random,
+    /// From the center outwards
+    CenterOut,
+}
+
+#[derive(Debug, Clone)]
+enum EffectOps {
+    /// Green glyphs rain down and lock into the image.
+    Matrix,
+    /// Hues rotate by a full turn every period.
+    HueCycle(Duration),
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum BgOps {
+    /// Use the average of the four corner pixels
+    Auto,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum Command {
+    /// Work with charset definition files
+    #[command(subcommand)]
+    Charset(CharsetCommand),
+    /// Print the dominant colors of an image with their coverage
+    Palette {
+        /// Image to extract the colors from
+        image: PathBuf,
+
+        /// Number of colors to extract
+        #[arg(long = "colors", default_value_t = 8, value_parser = clap::value_parser!(u16).range(1..=256))]
+        colors: u16,
+
+        /// Print colored blocks before the hex codes
+        #[arg(long = "color", value_enum, default_value_t = ColorMode::default())]
+        color: ColorMode,
+
+        /// Output format
+        #[arg(long = "format", value_enum, default_value_t = ReportFormatOps::default())]
+        format: ReportFormatOps,
+    },
+    /// Render an image in every built-in style, one labeled preview after
+    /// another (a grid of captioned previews for HTML)
+    StylesPreview {
+        /// Image to preview
+        image: PathBuf,
+
+        /// Width of every preview in characters
+        #[arg(short = 'w', long = "width", default_value_t = 40, value_parser = parse_width)]
+        width: u32,
+
+        /// Color the previews like `--colored` does
+        #[arg(short = 'c', long = "colored")]
+        colored: bool,
+
+        /// Preview every style in every color type
+        #[arg(long = "all-colors")]
+        all_colors: bool,
+
+        /// Output format [default: from the output extension, else ansi]
+        #[arg(long = "format", value_enum)]
+        format: Option<FormatOps>,
+
+        /// Output path (stdout when omitted)
+        #[arg(short = 'o', long = "output")]
+        output: Option<PathBuf>,
+    },
+    /// Print a brightness histogram of an image with min, max, mean and
+    /// percentiles
+    Histogram {
+        /// Image to measure
+        image: PathBuf,
+
+        /// How RGB channels are weighted into brightness
+        #[arg(long = "luminance", value_enum, default_value_t = LuminanceOps::default())]
+        luminance: LuminanceOps,
+
+        /// Output format
+        #[arg(long = "format", value_enum, default_value_t = ReportFormatOps::default())]
+        format: ReportFormatOps,
+    },
+    /// Print the man page (roff) to stdout, for packagers
+    #[command(hide = true)]
+    Man,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum CharsetCommand {
+    /// Save a built-in style or custom characters as a TOML charset file
+    Save {
+        /// Built-in style to save
+        #[arg(short = 's', long = "style", value_enum, default_value_t = StyleOps::default())]
+        style: StyleOps,
+
+        /// Characters ordered darkest to brightest (instead of --style)
+        #[arg(long = "chars")]
+        chars: Option<String>,
+
+        /// Convert an existing charset file (plain or TOML)
+        #[arg(long = "from", conflicts_with = "chars")]
+        from: Option<PathBuf>,
+
+        /// Name stored in the file
+        #[arg(long = "name")]
+        name: Option<String>,
+
+        /// Output path (stdout when omitted)
+        output: Option<PathBuf>,
+    },
+    /// Build a brightness ramp measured from the glyphs of a font file
+    #[cfg(feature = "font")]
+    FromFont {
+        /// TrueType/OpenType font file
+        font: PathBuf,
+
+        /// Candidate characters, or `printable-ascii`
+        #[arg(long = "chars", default_value = "printable-ascii")]
+        chars: String,
+
+        /// Number of brightness levels in the ramp
+        #[arg(long = "levels", default_value_t = 12, value_parser = clap::value_parser!(u16).range(2..))]
+        levels: u16,
+
+        /// Output path (stdout when omitted)
+        output: Option<PathBuf>,
+    },
+}
+
+impl Command {
+    fn run(&self) -> io::Result<()> {
+        match self {
+            Self::Man => crate::man::write_manpage(&mut io::stdout().lock()),
+            Self::Palette {
+                image,
+                colors,
+                color,
+                format,
+            } => {
+                let img = ImageReader::open(image)?
+                    .decode()
+                    .map_err(|err| failure(err.to_string()))?;
+                let colored = resolve_colored(true, *color, TermCaps::detect());
+                write_palette(
+                    &dominant_colors(&img, *colors as usize),
+                    *format,
+                    colored,
+                    &mut io::stdout().lock(),
+                )
+            }
+            Self::StylesPreview {
+                image,
+                width,
+                colored,
+                all_colors,
+                format,
+                output,
+            } => {
+                let img = ImageReader::open(image)?
+                    .decode()
+                    .map_err(|err| failure(err.to_string()))?;
+                let out_type = match (format, output) {
+                    (Some(format), _) => format.clone().into(),
+                    (None, Some(path)) => OutputType::from(path.as_path()),
+                    (None, None) => OutputType::term(),
+                };
+                let previews: Vec<_> = ImgStyle::ALL
+                    .into_iter()
+                    .flat_map(|style| {
+                        match (all_colors, colored) {
+                            (true, _) => ColorType::ALL.to_vec(),
+                            (false, true) => vec![default_color(style)],
+                            (false, false) => vec![ColorType::None],
+                        }
+                        .into_iter()
+                        .map(move |color| (style, color))
+                    })
+                    .collect();
+                let mut buf = Vec::new();
+                write_styles_preview(
+                    &img,
+                    *width,
+                    &out_type,
+                    &previews,
+                    *all_colors || *colored,
+                    &mut buf,
+                )?;
+                match output {
+                    Some(path) => fs::write(path, buf),
+                    None => io::stdout().write_all(&buf),
+                }
+            }
+            Self::Histogram {
+                image,
+                luminance,
+                format,
+            } => {
+                let img = ImageReader::open(image)?
+                    .decode()
+                    .map_err(|err| failure(err.to_string()))?;
+                let histogram = IntensityHistogram::of(&img, (*luminance).into());
+                write_histogram(&histogram, *format, &mut io::stdout().lock())
+            }
+            Self::Charset(CharsetCommand::Save {
+                style,
+                chars,
+                from,
+                name,
+                output,
+            }) => {
+                let mut spec = match (chars, from) {
+                    (Some(chars), _) => CharsetSpec::new("custom", vec![chars.chars().collect()]),
+                    (None, Some(path)) => load_charset(path)?,
+                    (None, None) => {
+                        let name = style
+                            .to_possible_value()
+                            .map(|v| v.get_name().to_owned())
+                            .unwrap_or_default();
+                        let style = match style {
+                            StyleOps::Pixel => ImgStyle::Pixel,
+                            StyleOps::Ascii => ImgStyle::Ascii,
+                            StyleOps::Block => ImgStyle::Block,
+                            StyleOps::Braills => ImgStyle::Braills,
+                            StyleOps::Dots => ImgStyle::Dots,
+                            StyleOps::Solid => ImgStyle::Solid,
+                            StyleOps::HalfBlock => ImgStyle::HalfBlock,
+                            StyleOps::Quadrant => ImgStyle::Quadrant,
+                            StyleOps::Sextant => ImgStyle::Sextant,
+                            StyleOps::BrailleDots => ImgStyle::BrailleDots,
+                            StyleOps::Custom | StyleOps::FromFile => {
+                                return Err(failure(
+                                    "ERROR: use --chars or --from to save a custom charset",
+                                ));
+                            }
+                        };
+                        CharsetSpec::new(name, style.into_pixt_data())
+                    }
+                };
+                if let Some(name) = name {
+                    spec.name = name.clone();
+                }
+                write_charset(&spec, output.as_deref())
+            }
+            #[cfg(feature = "font")]
+            Self::Charset(CharsetCommand::FromFont {
+                font,
+                chars,
+                levels,
+                output,
+            }) => {
+                let candidates = match chars.as_str() {
+                    "printable-ascii" => pixt::font::printable_ascii(),
+                    v => v.chars().collect(),
+                };
+                let ramp =
+                    pixt::font::ramp_from_font(&fs::read(font)?, &candidates, *levels as usize)?;
+                let name = font
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned();
+                write_charset(&CharsetSpec::new(name, vec![ramp]), output.as_deref())
+            }
+        }
+    }
+}
+
+/// Writes extracted `colors` with their coverage as `format`; text output
+/// shows each color as a block when `colored`.
+fn write_palette(
+    colors: &[([u8; 3], f64)],
+    format: ReportFormatOps,
+    colored: bool,
+    mut out: impl Write,
+) -> io::Result<()> {
+    let hex = |[r, g, b]: [u8; 3]| format!("#{:02X}{:02X}{:02X}", r, g, b);
+    match format {
+        ReportFormatOps::Text => {
+            for (color, coverage) in colors {
+                if colored {
+                    let [r, g, b] = *color;
+                    write!(out, "\x1b[48;2;{};{};{}m    \x1b[0m ", r, g, b)?;
+                }
+                writeln!(out, "{} {:5.1}%", hex(*color), coverage * 100.0)?;
+            }
+            Ok(())
+        }
+        ReportFormatOps::Json => {
+            let entries: Vec<_> = colors
+                .iter()
+                .map(|(color, coverage)| {
+                    format!(
+                        "  {{\"color\": \"{}\", \"coverage\": {:.4}}}",
+                        hex(*color),
+                        coverage
+                    )
+                })
+                .collect();
+            writeln!(out, "[\n{}\n]", entries.join(",\n"))
+        }
+    }
+}
+
+/// Color type `--colored` gives `style`.
+fn default_color(style: ImgStyle) -> ColorType {
+    match style {
+        ImgStyle::Pixel | ImgStyle::HalfBlock | ImgStyle::Quadrant | ImgStyle::Sextant => {
+            ColorType::FgTopBgDown
+        }
+        ImgStyle::BrailleDots => ColorType::TopFgOnly,
+        ImgStyle::Solid => ColorType::AvgBgOnly,
+        _ => ColorType::AvgFgOnly,
+    }
+}
+
+/// Renders `img` `width` characters wide once per `(style, color)` of
+/// `previews`, each under a label naming it (naming the color too when
+/// `colored`). HTML gets a page with a grid of captioned previews.
+fn write_styles_preview(
+    img: &DynamicImage,
+    width: u32,
+    out_type: &OutputType,
+    previews: &[(ImgStyle, ColorType)],
+    colored: bool,
+    mut out: impl Write,
+) -> io::Result<()> {
+    if let OutputType::Svg(_) = out_type {
+        return Err(failure("styles-preview writes text, ansi or html, not svg"));
+    }
+    let (w, h) = target_size(
+        (img.width(), img.height()),
+        Some(width),
+        None,
+        out_type.cell_aspect(),
+        false,
+        || Ok(width),
+    )?;
+    let img = img.resize_exact(w, h, FilterType::CatmullRom);
+    let page = matches!(out_type, OutputType::Html(_));
+    if page {
+        out.write_all(
+            b"<!DOCTYPE html>
+<html lang=\"en\">
+  <head>
+    <meta charset=\"UTF-8\">
+    <title>pixt styles</title>
+    <style>
+    * {
+        color: #fff;
+        background-color: #191919;
+        font-family: monospace;
+    }
+    body {
+        display: flex;
+        flex-wrap: wrap;
+        gap: 2em;
+    }
+    figure {
+        margin: 0;
+    }
+    pre {
+        margin: 0;
+        font-size: 10px;
+    }
+    </style>
+  </head>
+  <body>
+",
+        )?;
+    }
+    for (i, (style, color)) in previews.iter().enumerate() {
+        let label = match colored {
+            true => format!("{} ({})", style, color),
+            false => style.to_string(),
+        };
+        let out_type = out_type.clone().color(color.clone());
+        let pi = PixtImg::new(*style, out_type.clone()).with_fragment(true);
+        let grid = pi.cells(&img);
+        if page {
+            write!(
+                out,
+                "    <figure><pre style=\"line-height: {}\">",
+                html::line_height(color)
+            )?;
+            pi.write_cells(&grid, &out_type, &mut out)?;
+            writeln!(out, "</pre><figcaption>{}</figcaption></figure>", label)?;
+        } else {
+            if i > 0 {
+                writeln!(out)?;
+            }
+            writeln!(out, "{}", label)?;
+            pi.write_cells(&grid, &out_type, &mut out)?;
+        }
+    }
+    if page {
+        out.write_all(b"  </body>\n</html>\n")?;
+    }
+    Ok(())
+}
+
+/// Writes `histogram` as `format`: text draws the buckets as a bar chart
+/// above the stats.
+fn write_histogram(
+    histogram: &IntensityHistogram,
+    format: ReportFormatOps,
+    mut out: impl Write,
+) -> io::Result<()> {
+    let IntensityHistogram {
+        buckets,
+        min,
+        max,
+        mean,
+        percentiles,
+    } = histogram;
+    let percentiles: Vec<_> = PERCENTILES.iter().zip(percentiles).collect();
+    match format {
+        ReportFormatOps::Text => {
+            const HEIGHT: u64 = 8;
+            const BARS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+            let peak = buckets.iter().copied().max().unwrap_or(0).max(1);
+            for row in (0..HEIGHT).rev() {
+                let line: String = buckets
+                    .iter()
+                    .map(|count| {
+                        // Bar height in eighths of a row.
+                        let eighths = (count * HEIGHT * 8).div_ceil(peak);
+                        BARS[eighths.saturating_sub(row * 8).min(8) as usize]
+                    })
+                    .collect();
+                writeln!(out, "{}", line.trim_end())?;
+            }
+            writeln!(out, "0{:>width$}", 255, width = HISTOGRAM_BUCKETS - 1)?;
+            writeln!(out, "min {}  max {}  mean {:.1}", min, max, mean)?;
+            let percentiles: Vec<_> = percentiles
+                .iter()
+                .map(|(p, v)| format!("p{} {}", p, v))
+                .collect();
+            writeln!(out, "{}", percentiles.join("  "))
+        }
+        ReportFormatOps::Json => {
+            let buckets: Vec<_> = buckets.iter().map(u64::to_string).collect();
+            let percentiles: Vec<_> = percentiles
+                .iter()
+                .map(|(p, v)| format!("\"p{}\": {}", p, v))
+                .collect();
+            writeln!(
+                out,
+                "{{\n  \"buckets\": [{}],\n  \"min\": {},\n  \"max\": {},\n  \"mean\": {:.4},\n  \"percentiles\": {{{}}}\n}}",
+                buckets.join(", "),
+                min,
+                max,
+                mean,
+                percentiles.join(", ")
+            )
+        }
+    }
+}
+
+fn write_charset(spec: &CharsetSpec, output: Option<&Path>) -> io::Result<()> {
+    match output {
+        Some(path) => fs::write(path, spec.to_toml()),
+        None => io::stdout().write_all(spec.to_toml().as_bytes()),
+    }
+}
+
+/// Loads a charset file, treating `.toml` files (or TOML-looking content) as
+/// [`CharsetSpec`] TOML and anything else as the plain one-row-per-line format.
+fn load_charset(path: &Path) -> io::Result<CharsetSpec> {
+    let input = fs::read_to_string(path)
+        .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path.display(), err)))?;
+    let name = path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    let toml = path.extension().is_some_and(|v| v == "toml");
+    CharsetSpec::parse(name, &input, toml)
+        .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path.display(), err)))
+}
+
+impl Cli {
+    /// Parses the process arguments.
+    ///
+    /// Every `PIXT_*` environment variable only supplies a default: a flag
+    /// given on the command line wins over it, also when the two name
+    /// options that exclude each other (`PIXT_STYLE` and `--charset-file`,
+    /// `PIXT_CHARSET` and `--style`).
+    pub fn parse_args() -> Self {
+        let mut cmd = Self::command();
+        let matches = cmd.get_matches_mut();
+        let mut cli =
+            Self::from_arg_matches(&matches).unwrap_or_else(|err| err.format(&mut cmd).exit());
+        let source = |id: &str| matches.value_source(id);
+        match (source("style"), source("charset_file")) {
+            (Some(ValueSource::CommandLine), Some(ValueSource::CommandLine)) => cmd
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    "the argument '--charset-file <CHARSET_FILE>' cannot be used with '--style <STYLE>'",
+                )
+                .exit(),
+            (Some(ValueSource::EnvVariable), Some(ValueSource::EnvVariable)) => cmd
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    "PIXT_CHARSET cannot be used with PIXT_STYLE",
+                )
+                .exit(),
+            (Some(ValueSource::CommandLine), Some(ValueSource::EnvVariable)) => {
+                cli.charset_file = None;
+            }
+            (Some(ValueSource::EnvVariable), Some(ValueSource::CommandLine)) => {
+                cli.style = StyleOps::default();
+            }
+            _ => {}
+        }
+        cli
+    }
+
+    pub fn run(&self) -> io::Result<()> {
+        if let Some(command) = &self.command {
+            return command.run();
+        }
+        if self.dry_run {
+            return self.dry_run();
+        }
+        if let Some(video) = self.raw_video {
+            return play_raw_video(self, video);
+        }
+        #[cfg(all(feature = "camera", target_os = "linux"))]
+        if let Some(index) = self.camera {
+            return play_camera(self, index);
+        }
+        if self.output.is_empty() {
+            let caps = TermCaps::detect();
+            let colored = resolve_colored(self.colored, self.color, caps);
+            let output_type = self.format.clone().map(OutputType::from);
+            let sink = match self.pager_threshold(caps.tty) {
+                Some(threshold) => Sink::Pager(Pager::new(threshold)),
+                None => Sink::Stdout(io::stdout()),
+            };
+            // The pager handles Ctrl+C itself, and animations read it as a
+            // key in raw mode.
+            #[cfg(unix)]
+            if caps.tty && matches!(sink, Sink::Stdout(_)) {
+                restore_on_interrupt()?;
+            }
+            let restore = TermRestore::new(caps.tty);
+            let mut targets = [Target::new(sink, output_type, caps.tty)];
+            let result = render_app(&mut targets, self, colored);
+            let [target] = targets;
+            let finished = match target.sink {
+                Sink::Pager(pager) => pager.finish(),
+                _ => Ok(()),
+            };
+            result?;
+            let result = target.error.map_or(finished, Err);
+            if result.is_ok() {
+                restore.finish();
+            }
+            return result;
+        }
+        let colored = self.colored && self.color != ColorMode::Never;
+        let mut targets = Vec::with_capacity(self.output.len());
+        for path in &self.output {
+            if self.format.is_none() && OutputType::from_path(path).is_none() {
+                eprintln!(
+                    "WARNING: {}: unknown extension, writing plain text (see --format)",
+                    path.display()
+                );
+            }
+            let file = AtomicFileWriter::create(path, self.append)?;
+            targets.push(Target::new(
+                Sink::File(file),
+                Some(self.file_output_type(path)),
+                false,
+            ));
+        }
+        render_app(&mut targets, self, colored)?;
+        // Commit every target that was written completely; a failed one
+        // leaves its destination untouched.
+        let mut result = Ok(());
+        for (target, path) in targets.into_iter().zip(&self.output) {
+            let status = match (target.error, target.sink) {
+                (None, Sink::File(file)) => file.commit(),
+                (Some(err), _) => Err(err),
+                (None, Sink::Stdout(_) | Sink::Pager(_)) => Ok(()),
+            };
+            if let Err(err) = status {
+                eprintln!("ERROR: {}: {}", path.display(), err);
+                result = Err(failure(format!("failed to write {}", path.display())));
+            }
+        }
+        result
+    }
+
+    /// Lines terminal output may take before it goes through the pager
+    /// (`0` to always page), or `None` to never page: when stdout isn't a
+    /// terminal or an animation takes over the screen.
+    fn pager_threshold(&self, tty: bool) -> Option<usize> {
+        if !tty
+            || self.reveal.is_some()
+            || self.effect.is_some()
+            || self.animate == AnimateOps::Always
+        {
+            return None;
+        }
+        match self.pager {
+            PagerOps::Never => None,
+            PagerOps::Always => Some(0),
+            PagerOps::Auto => crossterm::terminal::size()
+                .ok()
+                .map(|(_, rows)| rows as usize),
+        }
+    }
+
+    /// Format written to the output file `path`: `--format` if given,
+    /// otherwise the one its extension names, plain text if it names none.
+    fn file_output_type(&self, path: &Path) -> OutputType {
+        match &self.format {
+            Some(format) => format.clone().into(),
+            None => OutputType::from(path),
+        }
+    }
+
+    /// Prints what [`run`](Self::run) would do: whether colors are on, and
+    /// for every image its size, the cell grid and each output with its
+    /// format and estimated size. Only image headers are read and nothing is
+    /// written.
+    fn dry_run(&self) -> io::Result<()> {
+        let caps = TermCaps::detect();
+        let (colored, tty, outputs) = if self.output.is_empty() {
+            let colored = resolve_colored(self.colored, self.color, caps);
+            let output_type = self.format.clone().map(OutputType::from);
+            (
+                colored,
+                caps.tty,
+                vec![(String::from("stdout"), output_type)],
+            )
+        } else {
+            let outputs = self
+                .output
+                .iter()
+                .map(|path| {
+                    (
+                        path.display().to_string(),
+                        Some(self.file_output_type(path)),
+                    )
+                })
+                .collect();
+            (
+                self.colored && self.color != ColorMode::Never,
+                false,
+                outputs,
+            )
+        };
+        let paths = image_paths(self)?;
+        if paths.is_empty() {
+            return Err(failure("ERROR: Image Path Not Found"));
+        }
+        let charset = app_charset(self)?;
+        let palette = app_palette(self)?;
+        let mut out = io::stdout().lock();
+        writeln!(
+            out,
+            "stdout: {}, colors: {}",
+            if caps.tty {
+                "terminal"
+            } else {
+                "not a terminal"
+            },
+            if colored { "on" } else { "off" }
+        )?;
+        for path in paths {
+            let (img_w, img_h) = match is_stdin(&path) {
+                true => ImageReader::new(Cursor::new(read_stdin()?))
+                    .with_guessed_format()?
+                    .into_dimensions(),
+                false => ImageReader::open(&path)?.into_dimensions(),
+            }
+            .map_err(|err| failure(err.to_string()))?;
+            let input_type = input_type(&path);
+            let output_type = outputs[0].1.clone().unwrap_or_else(|| input_type.clone());
+            let pi = pixt_img(
+                self,
+                charset.as_ref(),
+                palette.as_ref(),
+                output_type.clone(),
+                colored,
+            )?;
+            let (width, height) =
+                render_size(self, (img_w, img_h), &output_type, pi.cell_width(), tty)?;
+            let (cols, rows) = pi.output_size_for(width, height);
+            writeln!(
+                out,
+                "{}: {}x{} pixels, {}x{} cells",
+                path.display(),
+                img_w,
+                img_h,
+                cols,
+                rows
+            )?;
+            for (dest, output_type) in &outputs {
+                let output_type = output_type.clone().unwrap_or_else(|| input_type.clone());
+                let pi = pixt_img(
+                    self,
+                    charset.as_ref(),
+                    palette.as_ref(),
+                    output_type,
+                    colored,
+                )?;
+                writeln!(
+                    out,
+                    "  {}: {:?}, about {} bytes",
+                    dest,
+                    pi.output_type(),
+                    pi.estimated_output_size_for(width, height)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Where a render goes.
+enum Sink {
+    Stdout(io::Stdout),
+    Pager(Pager),
+    File(AtomicFileWriter),
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Stdout(v) => v.write(buf),
+            Self::Pager(v) => v.write(buf),
+            Self::File(v) => v.write(buf),
+        }
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Stdout(v) => v.flush(),
+            Self::Pager(v) => v.flush(),
+            Self::File(v) => v.flush(),
+        }
+    }
+}
+
+/// Stdout behind a pager. Output is held back until it reaches `threshold`
+/// lines, then the pager is started and everything streamed into it; output
+/// that stays shorter, or finds the pager can't be started, goes straight
+/// to stdout.
+struct Pager {
+    threshold: usize,
+    state: PagerState,
+    stdout: io::Stdout,
+}
+
+enum PagerState {
+    /// Output so far and how many lines it has.
+    Holding(Vec<u8>, usize),
+    Paging(Child, ChildStdin),
+    /// Writing to stdout directly.
+    Direct,
+    /// The pager was quit early, the rest of the output is dropped.
+    Closed(Child),
+}
+
+impl Pager {
+    fn new(threshold: usize) -> Self {
+        Self {
+            threshold,
+            state: PagerState::Holding(Vec::new(), 0),
+            stdout: io::stdout(),
+        }
+    }
+
+    /// Starts `$PAGER` (`less -R` if unset, so colors survive), falling
+    /// back to stdout, and hands it the output held back so far.
+    fn start(&mut self, held: &[u8]) -> io::Result<()> {
+        let command = env::var("PAGER").unwrap_or_else(|_| String::from("less -R"));
+        let mut args = command.split_whitespace();
+        let child = args.next().and_then(|program| {
+            process::Command::new(program)
+                .args(args)
+                .stdin(Stdio::piped())
+                .spawn()
+                .ok()
+        });
+        self.state = match child {
+            Some(mut child) => {
+                let stdin = child.stdin.take().expect("stdin is piped");
+                PagerState::Paging(child, stdin)
+            }
+            None => PagerState::Direct,
+        };
+        self.write_all(held)
+    }
+
+    /// Writes out what is still held back and waits for the pager to exit.
+    fn finish(mut self) -> io::Result<()> {
+        match std::mem::replace(&mut self.state, PagerState::Direct) {
+            PagerState::Holding(held, _) => {
+                self.stdout.write_all(&held)?;
+                self.stdout.flush()
+            }
+            PagerState::Paging(mut child, stdin) => {
+                drop(stdin);
+                child.wait().map(drop)
+            }
+            PagerState::Closed(mut child) => child.wait().map(drop),
+            PagerState::Direct => self.stdout.flush(),
+        }
+    }
+}
+
+impl Write for Pager {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.state {
+            PagerState::Holding(held, lines) => {
+                held.extend_from_slice(buf);
+                *lines += buf.iter().filter(|v| **v == b'\n').count();
+                if *lines >= self.threshold {
+                    let held = std::mem::take(held);
+                    self.start(&held)?;
+                }
+                Ok(buf.len())
+            }
+            PagerState::Paging(_, stdin) => match stdin.write(buf) {
+                Err(err) if err.kind() == io::ErrorKind::BrokenPipe => {
+                    let PagerState::Paging(child, _) =
+                        std::mem::replace(&mut self.state, PagerState::Direct)
+                    else {
+                        unreachable!("matched above")
+                    };
+                    self.state = PagerState::Closed(child);
+                    Ok(buf.len())
+                }
+                result => result,
+            },
+            PagerState::Direct => self.stdout.write(buf),
+            PagerState::Closed(_) => Ok(buf.len()),
+        }
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.state {
+            PagerState::Paging(_, stdin) => match stdin.flush() {
+                Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+                result => result,
+            },
+            PagerState::Direct => self.stdout.flush(),
+            PagerState::Holding(..) | PagerState::Closed(_) => Ok(()),
+        }
+    }
+}
+
+/// One output of a run. Every image is rendered once and then replayed to
+/// each target; a target that fails to write keeps its error and is skipped
+/// from then on, without affecting the others.
+struct Target {
+    sink: Sink,
+    /// Format to write, or `None` to derive it from each input path.
+    output_type: Option<OutputType>,
+    /// Whether the sink is an interactive terminal.
+    tty: bool,
+    error: Option<io::Error>,
+}
+
+impl Target {
+    fn new(sink: Sink, output_type: Option<OutputType>, tty: bool) -> Self {
+        Self {
+            sink,
+            output_type,
+            tty,
+            error: None,
+        }
+    }
+}
+
+/// Writes a file through a temporary sibling (`.pixt-tmp-XXXX`) that only
+/// replaces the destination on [`commit`](Self::commit), so readers never
+/// see a half-written file. Dropping the writer uncommitted removes the
+/// temporary file and leaves the destination untouched.
+struct AtomicFileWriter {
+    dest: PathBuf,
+    tmp: PathBuf,
+    file: Option<BufWriter<fs::File>>,
+}
+
+impl AtomicFileWriter {
+    /// Starts writing `dest`. With `append` the current content of `dest`
+    /// (if any) is kept and new output goes after it.
+    fn create(dest: &Path, append: bool) -> io::Result<Self> {
+        let dir = match dest.parent() {
+            Some(v) if !v.as_os_str().is_empty() => v,
+            _ => Path::new("."),
+        };
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |v| v.subsec_nanos())
+            ^ std::process::id();
+        let mut attempt = 0;
+        let (tmp, file) = loop {
+            let tmp = dir.join(format!(".pixt-tmp-{:08x}", seed.wrapping_add(attempt)));
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&tmp)
+            {
+                Ok(file) => break (tmp, file),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists && attempt < 16 => {
+                    attempt += 1;
+                }
+                Err(err) => {
+                    return Err(io::Error::new(
+                        err.kind(),
+                        format!("{}: {}", tmp.display(), err),
+                    ));
+                }
+            }
+        };
+        let mut writer = Self {
+            dest: dest.to_path_buf(),
+            tmp,
+            file: Some(BufWriter::new(file)),
+        };
+        if append {
+            match fs::File::open(dest) {
+                Ok(mut existing) => {
+                    io::copy(&mut existing, &mut writer)?;
+                }
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(writer)
+    }
+
+    /// Flushes everything and moves the temporary file over the destination.
+    fn commit(mut self) -> io::Result<()> {
+        if let Some(file) = self.file.take() {
+            let file = file.into_inner().map_err(|err| err.into_error())?;
+            file.sync_all()?;
+        }
+        fs::rename(&self.tmp, &self.dest)
+            .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", self.dest.display(), err)))
+    }
+}
+
+impl Write for AtomicFileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.file {
+            Some(file) => file.write(buf),
+            None => Err(io::Error::other("file already committed")),
+        }
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.file {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for AtomicFileWriter {
+    fn drop(&mut self) {
+        if self.file.take().is_some() {
+            let _ = fs::remove_file(&self.tmp);
+        }
+    }
+}
+
+/// Capabilities of the terminal attached to stdout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TermCaps {
+    /// Whether stdout is a terminal.
+    tty: bool,
+    /// Whether the `NO_COLOR` environment variable is set (and non-empty).
+    no_color: bool,
+    /// Whether the terminal advertises color support (`TERM` is not `dumb`).
+    color: bool,
+    /// Whether the terminal interprets ANSI/VT escape sequences. On Windows
+    /// this is only true once virtual terminal processing has been enabled.
+    vt: bool,
+}
+
+impl TermCaps {
+    fn detect() -> Self {
+        let tty = io::stdout().is_terminal();
+        let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        let color = env::var("TERM").map_or(true, |term| term != "dumb");
+        #[cfg(windows)]
+        let vt = tty && crossterm::ansi_support::supports_ansi();
+        #[cfg(not(windows))]
+        let vt = true;
+        Self {
+            tty,
+            no_color,
+            color,
+            vt,
+        }
+    }
+}
+
+/// Puts the terminal back into a usable state (colors reset, line wrap on,
+/// cursor shown) if a render into it stops early, by an error or a panic,
+/// and left colors or modes set. Does nothing once [`finish`](Self::finish)ed.
+struct TermRestore {
+    armed: bool,
+}
+
+impl TermRestore {
+    /// Guards stdout when it is a terminal.
+    fn new(tty: bool) -> Self {
+        Self { armed: tty }
+    }
+    /// The render completed and cleaned up after itself.
+    fn finish(mut self) {
+        self.armed = false;
+    }
+}
+
+impl Drop for TermRestore {
+    fn drop(&mut self) {
+        if self.armed {
+            let _ = crossterm::execute!(io::stdout(), ResetColor, EnableLineWrap, Show);
+        }
+    }
+}
+
+/// Restores the terminal like [`TermRestore`] and exits with status 130
+/// when Ctrl+C interrupts a render into it, instead of leaving the prompt
+/// in the colors of the last cell. A second Ctrl+C exits right away,
+/// should the cleanup hang.
+#[cfg(unix)]
+fn restore_on_interrupt() -> io::Result<()> {
+    use signal_hook::{consts::SIGINT, flag, iterator::Signals};
+    use std::sync::{Arc, atomic::AtomicBool};
+
+    let interrupted = Arc::new(AtomicBool::new(false));
+    // Registered first, so it only sees the flag set by an earlier signal.
+    flag::register_conditional_shutdown(SIGINT, 130, Arc::clone(&interrupted))?;
+    flag::register(SIGINT, interrupted)?;
+    let mut signals = Signals::new([SIGINT])?;
+    std::thread::spawn(move || {
+        if signals.forever().next().is_some() {
+            let _ = crossterm::execute!(
+                io::stdout(),
+                ResetColor,
+                EnableLineWrap,
+                Show,
+                crossterm::style::Print("\n")
+            );
+            process::exit(130);
+        }
+    });
+    Ok(())
+}
+
+/// Decides whether colored terminal output should be produced.
+///
+/// Precedence, highest first:
+/// 1. `-c/--colored` not given: no colors are computed at all.
+/// 2. `--color never`: no colors.
+/// 3. `--color always`: colors, even into pipes and with `NO_COLOR` set.
+/// 4. `--color auto`: colors only when `NO_COLOR` is unset and stdout is a
+///    terminal that advertises color support and interprets escapes. Colors
+///    dropped only for the last reason are reported with a warning.
+fn resolve_colored(colored: bool, mode: ColorMode, caps: TermCaps) -> bool {
+    match mode {
+        _ if !colored => false,
+        ColorMode::Never => false,
+        ColorMode::Always => true,
+        ColorMode::Auto if caps.no_color || !caps.tty || !caps.color => false,
+        ColorMode::Auto if !caps.vt => {
+            eprintln!("WARNING: terminal does not support ANSI escapes, printing without colors");
+            false
+        }
+        ColorMode::Auto => true,
+    }
+}
+
+/// Image paths given on the command line. With `-s custom` or
+/// `-s from-file` the first argument is the charset instead.
+fn image_paths(app: &Cli) -> io::Result<Vec<PathBuf>> {
+    // Extract image paths if the `--style | -s custom` option is provided in the CLI.
+    // - If the `custom` style is selected but no image path is provided, print an error and exit.
+    // - Otherwise, if `custom` is selected, skip the first argument (which may be the style
+    //   option) and collect the rest as image paths.
+    // - If a different style is selected, use all provided arguments as they are.
+    if matches!(app.style, StyleOps::Custom | StyleOps::FromFile) && app.files.len() < 2 {
+        Err(failure("ERROR: Image Path Not Found"))
+    } else if matches!(app.style, StyleOps::Custom | StyleOps::FromFile) {
+        Ok(app.files.iter().skip(1).cloned().collect())
+    } else {
+        Ok(app.files.clone())
+    }
+}
+
+/// The charset given by `--charset-file` or `-s from-file`, if any.
+fn app_charset(app: &Cli) -> io::Result<Option<CharsetSpec>> {
+    match (&app.charset_file, &app.style) {
+        (Some(path), _) => load_charset(path).map(Some),
+        (None, StyleOps::FromFile) => match app.files.first() {
+            Some(path) => load_charset(path).map(Some),
+            None => Err(failure("ERROR: Image Path Not Found")),
+        },
+        _ => Ok(None),
+    }
+}
+
+/// The palette given by `--palette-file` or `--palette`, if any.
+fn app_palette(app: &Cli) -> io::Result<Option<Palette>> {
+    match (&app.palette_file, &app.palette) {
+        (Some(path), _) => Palette::parse(&fs::read_to_string(path)?).map(Some),
+        (None, Some(name)) => Ok(Some(name.clone().into())),
+        (None, None) => Ok(None),
+    }
+}
+
+/// Format an input path implies when a target doesn't name one.
+fn input_type(path: &Path) -> OutputType {
+    match path.extension() {
+        Some(v) if v == "html" => OutputType::html(),
+        Some(v) if v == "svg" => OutputType::svg(),
+        _ => OutputType::term(),
+    }
+}
+
+fn no_blocks(bw: u32, bh: u32) -> io::Error {
+    failure(format!(
+        "ERROR: image is smaller than one {}x{} block",
+        bw, bh
+    ))
+}
+
+/// Size in pixels a `(width, height)` source is resized to (or, with
+/// `--block-size`, averaged down to) before rendering. Only the terminal
+/// width is queried, so a dry run gets the same answer as the real render.
+///
+/// Every source pixel column spans `cell_width` output columns, see
+/// [`PixtImg::cell_width`].
+fn render_size(
+    app: &Cli,
+    (img_w, img_h): (u32, u32),
+    output_type: &OutputType,
+    cell_width: u32,
+    tty: bool,
+) -> io::Result<(u32, u32)> {
+    if let Some((bw, bh)) = app.block_size {
+        let (bw, bh) = (bw.max(1), bh.max(1));
+        let (cols, rows) = match app.block_remainder {
+            RemainderOps::Truncate => (img_w / bw, img_h / bh),
+            _ => (img_w.div_ceil(bw), img_h.div_ceil(bh)),
+        };
+        if cols == 0 || rows == 0 {
+            return Err(no_blocks(bw, bh));
+        }
+        return Ok((cols, rows * 2));
+    }
+    let cell_aspect = app.cell_aspect.unwrap_or_else(|| output_type.cell_aspect());
+    let cols = app.width.map(|w| (w / cell_width).max(1));
+    // Terminal columns, if they were queried and known.
+    let mut term_cols = None;
+    let (width, height) = target_size(
+        (img_w, img_h),
+        cols,
+        app.height,
+        cell_aspect * cell_width as f32,
+        tty && !app.no_clamp,
+        || match crossterm::terminal::size() {
+            Ok((cols, _)) => Ok(*term_cols.insert(cols as u32 / cell_width)),
+            Err(err) if app.width.is_some() || app.height.is_some() => Err(err),
+            Err(_) => {
+                eprintln!(
+                    "WARNING: terminal size unknown, using width {} (set PIXT_DEFAULT_WIDTH to change it)",
+                    app.default_width
+                );
+                Ok((app.default_width / cell_width).max(1))
+            }
+        },
+    )?;
+    // Explicit columns only ever query the terminal to clamp them.
+    if let (Some(requested), Some(cols), Some(max)) = (app.width, cols, term_cols)
+        && cols > max.max(1)
+    {
+        eprintln!(
+            "WARNING: width {} exceeds the terminal, clamped to {} (use --no-clamp to keep it)",
+            requested,
+            width * cell_width
+        );
+    }
+    Ok((width, height))
+}
+
+/// Builds the renderer for the chosen style or charset with the options
+/// that don't depend on the image.
+fn pixt_img(
+    app: &Cli,
+    charset: Option<&CharsetSpec>,
+    palette: Option<&Palette>,
+    output_type: OutputType,
+    colored: bool,
+) -> io::Result<PixtImg> {
+    // Transparent pixels take the color of the page they end up on.
+    let backdrop = app
+        .alpha_bg
+        .or_else(|| match output_type {
+            OutputType::Html(_) | OutputType::Svg(_) => parse_hex(&app.html_bg),
+            OutputType::Text(_) | OutputType::Term(_) => None,
+        })
+        .unwrap_or(output_type.backdrop());
+    // `--color-mode` overrides the color type a colored style picks.
+    // Structural matches color the glyph and what's around it separately.
+    let colored_as = |color: ColorType| match (&app.color_mode, &app.selection) {
+        (Some(mode), _) => mode.clone().into(),
+        (None, MatchOps::Structural) => ColorType::FgTopBgDown,
+        (None, MatchOps::Intensity) => color,
+    };
+    let ramp = |data: PixtData| match app.sort_charset {
+        true => data.sorted_by_density(),
+        false => data,
+    };
+    let pi = if let Some(spec) = charset {
+        let color = if colored {
+            colored_as(ColorType::AvgFgOnly)
+        } else {
+            ColorType::None
+        };
+        PixtImg::new(ramp(spec.to_pixt_data()), output_type.color(color))
+    } else {
+        match (&app.style, &colored) {
+            (StyleOps::Ascii, true) => PixtImg::new(
+                ImgStyle::Ascii,
+                output_type.color(colored_as(ColorType::AvgFgOnly)),
+            ),
+            (StyleOps::Ascii, false) => {
+                PixtImg::new(ImgStyle::Ascii, output_type.color(ColorType::None))
+            }
+            (StyleOps::Block, true) => PixtImg::new(
+                ImgStyle::Block,
+                output_type.color(colored_as(ColorType::AvgFgOnly)),
+            ),
+            (StyleOps::Block, false) => {
+                PixtImg::new(ImgStyle::Block, output_type.color(ColorType::None))
+            }
+            (StyleOps::Pixel, true) => PixtImg::new(
+                ImgStyle::Pixel,
+                output_type.color(colored_as(ColorType::FgTopBgDown)),
+            ),
+            (StyleOps::Pixel, false) => {
+                PixtImg::new(ImgStyle::Pixel, output_type.color(ColorType::None))
+            }
+            (StyleOps::Braills, true) => PixtImg::new(
+                ImgStyle::Braills,
+                output_type.color(colored_as(ColorType::AvgFgOnly)),
+            ),
+            (StyleOps::Braills, false) => {
+                PixtImg::new(ImgStyle::Braills, output_type.color(ColorType::None))
+            }
+            (StyleOps::Dots, true) => PixtImg::new(
+                ImgStyle::Dots,
+                output_type.color(colored_as(ColorType::AvgFgOnly)),
+            ),
+            (StyleOps::Dots, false) => {
+                PixtImg::new(ImgStyle::Dots, output_type.color(ColorType::None))
+            }
+            (StyleOps::Solid, true) => PixtImg::new(
+                ImgStyle::Solid,
+                output_type.color(colored_as(ColorType::AvgBgOnly)),
+            ),
+            (StyleOps::Solid, false) => {
+                PixtImg::new(ImgStyle::Solid, output_type.color(ColorType::None))
+            }
+            (StyleOps::HalfBlock, true) => PixtImg::new(
+                ImgStyle::HalfBlock,
+                output_type.color(colored_as(ColorType::FgTopBgDown)),
+            ),
+            (StyleOps::HalfBlock, false) => {
+                PixtImg::new(ImgStyle::HalfBlock, output_type.color(ColorType::None))
+            }
+            (StyleOps::Quadrant, true) => PixtImg::new(
+                ImgStyle::Quadrant,
+                output_type.color(colored_as(ColorType::FgTopBgDown)),
+            ),
+            (StyleOps::Quadrant, false) => {
+                PixtImg::new(ImgStyle::Quadrant, output_type.color(ColorType::None))
+            }
+            (StyleOps::Sextant, true) => PixtImg::new(
+                ImgStyle::Sextant,
+                output_type.color(colored_as(ColorType::FgTopBgDown)),
+            ),
+            (StyleOps::Sextant, false) => {
+                PixtImg::new(ImgStyle::Sextant, output_type.color(ColorType::None))
+            }
+            (StyleOps::BrailleDots, true) => PixtImg::new(
+                ImgStyle::BrailleDots,
+                output_type.color(colored_as(ColorType::TopFgOnly)),
+            ),
+            (StyleOps::BrailleDots, false) => {
+                PixtImg::new(ImgStyle::BrailleDots, output_type.color(ColorType::None))
+            }
+            (StyleOps::Custom, false) => {
+                let input = app.files[0]
+                    .clone()
+                    .into_os_string()
+                    .into_string()
+                    .map_err(|err| failure(format!("ERROR: envalid chars: '{:?}'", err)))?;
+                PixtImg::new(
+                    ramp(Graphemes::row(&input).into()),
+                    output_type.color(ColorType::None),
+                )
+            }
+            (StyleOps::Custom, true) => {
+                let input = app.files[0]
+                    .clone()
+                    .into_os_string()
+                    .into_string()
+                    .map_err(|err| failure(format!("ERROR: envalid chars: '{:?}'", err)))?;
+                PixtImg::new(
+                    ramp(Graphemes::row(&input).into()),
+                    output_type.color(colored_as(ColorType::AvgFgOnly)),
+                )
+            }
+            (StyleOps::FromFile, _) => unreachable!("from-file charsets are loaded up front"),
+        }
+    };
+    let mut pi = pi
+        .with_fragment(true)
+        .with_filter(app.filter_preset.clone().into())
+        .with_color_depth(app.color_depth.clone().into())
+        .with_wide(app.wide || charset.is_some_and(|v| v.wide));
+    if app.trim_lines {
+        pi = pi.with_trim_lines(true);
+    }
+    if let Some(ramp) = app.attr_ramp {
+        pi = pi.with_attr_ramp(ramp);
+    }
+    #[cfg(feature = "font")]
+    if let Some(font) = &app.font {
+        let data = fs::read(font)
+            .map_err(|err| failure(format!("ERROR: --font '{}': {}", font.display(), err)))?;
+        pi = pi
+            .with_font_densities(&data, &mut pixt::font::GlyphDensity::new())
+            .map_err(|err| failure(format!("ERROR: --font '{}': {}", font.display(), err)))?;
+    }
+    if let Some(palette) = palette {
+        pi = pi.with_palette(palette.clone());
+    }
+    if let Some(path) = &app.ansi_palette {
+        let palette = Palette::parse(&fs::read_to_string(path)?)?;
+        let colors: [[u8; 3]; 16] = palette.colors().try_into().map_err(|_| {
+            failure(format!(
+                "ERROR: --ansi-palette: expected 16 colors, found {}",
+                palette.colors().len()
+            ))
+        })?;
+        pi = pi.with_ansi_palette(colors);
+    }
+    if let Some(mirror) = &app.mirror {
+        pi = pi.with_mirror(mirror.clone().into());
+    }
+    if let Some((cols, rows)) = app.tile {
+        pi = pi.with_tile(cols, rows);
+    }
+    if app.selection == MatchOps::Structural && !pi.has_shapes() {
+        return Err(failure(String::from(
+            "ERROR: --match structural: no character of the charset has a known shape (block, quadrant or braille characters)",
+        )));
+    }
+    let mut html = HtmlOptions::default()
+        .with_colors(app.html_fg.clone(), app.html_bg.clone())
+        .with_font(app.html_font.clone(), app.html_font_size)
+        .with_fit(app.html_fit.clone().into())
+        .with_debug(app.html_debug)
+        .with_minify(app.minify)
+        .with_weight_ramp(app.html_weight_ramp);
+    if let Some(path) = &app.html_css {
+        html = html.with_extra_css(fs::read_to_string(path)?);
+    }
+    if let Some(max_edge) = app.html_include_source {
+        html = html.with_source(max_edge);
+    }
+    pi = pi
+        .with_html_options(html)
+        .with_svg_mode(app.svg_mode.clone().into());
+    if let Some(threshold) = app.alpha_threshold {
+        pi = pi.with_transparent(threshold, app.alpha_fill);
+    }
+    if let Some(threshold) = app.edges {
+        pi = pi.with_edges(EdgeOptions::default().with_threshold(threshold));
+    }
+    Ok(pi
+        .with_alpha_bg(Some(backdrop))
+        .with_invert(app.invert)
+        .with_luminance(app.luminance.into())
+        .with_gamma(app.gamma)
+        .with_dither(app.dither)
+        .with_block_threshold(app.threshold)
+        .with_selection(app.selection.clone().into()))
+}
+
+fn render_app(targets: &mut [Target], app: &Cli, colored: bool) -> io::Result<()> {
+    let tty = targets.first().is_some_and(|t| t.tty);
+    let args = image_paths(app)?;
+    let charset = app_charset(app)?;
+    let palette = app_palette(app)?;
+    // HTML plays an animated GIF as a CSS animation, terminal or not.
+    let html = targets
+        .iter()
+        .any(|t| matches!(t.output_type, Some(OutputType::Html(_))));
+    for (i, path) in args.iter().enumerate() {
+        let stdin = match is_stdin(path) {
+            true => Some(read_stdin()?),
+            false => None,
+        };
+        let stdin = stdin.as_deref();
+        let mut frames = match (app.frame, app.animate, tty || html) {
+            (Some(_), _, _) => gif_frames(path, stdin)?,
+            (None, AnimateOps::Never, _) | (None, AnimateOps::Auto, false) => None,
+            (None, AnimateOps::Always, false) => {
+                return Err(failure("ERROR: --animate: stdout is not a terminal"));
+            }
+            (None, AnimateOps::Auto | AnimateOps::Always, true) => gif_frames(path, stdin)?,
+        };
+        let source = match (app.frame, frames.take()) {
+            (Some(n), frames) => {
+                let count = frames.as_ref().map_or(1, Vec::len);
+                if n as usize >= count {
+                    return Err(failure(format!(
+                        "ERROR: --frame {}: {} has {} frame{} (0-{})",
+                        n,
+                        path.display(),
+                        count,
+                        if count == 1 { "" } else { "s" },
+                        count - 1
+                    )));
+                }
+                frames.map(|mut frames| frames.swap_remove(n as usize).0)
+            }
+            (None, Some(all)) if all.len() > 1 => {
+                let first = all[0].0.clone();
+                frames = Some(all);
+                Some(first)
+            }
+            (None, _) if app.animate == AnimateOps::Always => {
+                return Err(failure(format!(
+                    "ERROR: --animate: {} is not an animated GIF",
+                    path.display()
+                )));
+            }
+            (None, _) => None,
+        };
+        let source = match source {
+            Some(frame) => frame,
+            None => decode_input(path, stdin)?,
+        };
+        let source = match app.trim {
+            Some(threshold) => trim_transparent(&source, threshold).ok_or_else(|| {
+                failure(format!(
+                    "ERROR: --trim: {} has no pixel with an alpha above {}",
+                    path.display(),
+                    threshold
+                ))
+            })?,
+            None => source,
+        };
+        let source = match &app.blend {
+            Some((other, alpha)) => {
+                let other = ImageReader::open(other)?.decode().map_err(|err| {
+                    failure(format!("ERROR: --blend {}: {}", other.display(), err))
+                })?;
+                blend(&source, &other, app.blend_mode.clone().into(), *alpha)
+            }
+            None => source,
+        };
+        let input_type = input_type(path);
+        // Sizing and cell resolution follow the first target.
+        let output_type = targets
+            .first()
+            .and_then(|t| t.output_type.clone())
+            .unwrap_or_else(|| input_type.clone());
+        let mut pi = pixt_img(
+            app,
+            charset.as_ref(),
+            palette.as_ref(),
+            output_type.clone(),
+            colored,
+        )?;
+        let (width, height) = render_size(
+            app,
+            (source.width(), source.height()),
+            &output_type,
+            pi.cell_width(),
+            tty,
+        )?;
+        // Block styles take several pixel columns, and rows, per cell.
+        let (px_cols, px_rows) = pi.cell_pixels();
+        let (width, height) = (width * px_cols, height * px_rows / 2);
+        let sample = |img: &DynamicImage| match app.block_size {
+            Some(_) if px_rows != 2 => Err(failure(format!(
+                "ERROR: --block-size averages two pixel rows per cell, this style samples {}",
+                px_rows
+            ))),
+            Some((bw, bh)) => {
+                let truncate = app.block_remainder == RemainderOps::Truncate;
+                block_average(img, (bw / px_cols).max(1), bh, truncate)
+                    .ok_or_else(|| no_blocks(bw, bh))
+            }
+            None => Ok(match app.sampling {
+                SamplingOps::Resize => img.resize_exact(width, height, FilterType::CatmullRom),
+                SamplingOps::Area => area_resize(img, width, height),
+            }),
+        };
+        let prepare = |frame: &DynamicImage| {
+            let frame = sample(frame)?;
+            Ok(match app.colors {
+                Some(n) => quantize(&frame, n as usize).0,
+                None => frame,
+            })
+        };
+        let img = sample(&source)?;
+        let img = match app.colors {
+            Some(n) => {
+                let (img, palette) = quantize(&img, n as usize);
+                if app.verbose {
+                    let palette = palette
+                        .iter()
+                        .map(|[r, g, b]| format!("#{:02X}{:02X}{:02X}", r, g, b))
+                        .collect::<Vec<_>>();
+                    eprintln!("palette ({} colors): {}", palette.len(), palette.join(" "));
+                }
+                img
+            }
+            None => img,
+        };
+        if app.emit_metadata {
+            let (cols, rows) = pi.output_size(&img);
+            let cols = cols * pi.cell_width();
+            let color = pi.output_type().color_type().to_string();
+            pi = pi.with_metadata(Metadata {
+                version: metadata::VERSION.to_string(),
+                source: path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned()),
+                source_size: [source.width(), source.height()],
+                size: [cols, rows],
+                style: match &charset {
+                    Some(spec) if !spec.name.is_empty() => spec.name.clone(),
+                    _ => app
+                        .style
+                        .to_possible_value()
+                        .map_or_else(String::new, |v| v.get_name().to_string()),
+                },
+                color,
+                args: std::env::args().skip(1).collect(),
+            });
+        }
+        if !app.highlight.is_empty() {
+            let (cols, rows) = pi.grid_size(&img);
+            for r in &app.highlight {
+                if !r.fits(cols, rows) {
+                    return Err(failure(format!(
+                        "ERROR: --highlight {},{},{}x{} does not fit the {}x{} cell grid",
+                        r.x, r.y, r.w, r.h, cols, rows
+                    )));
+                }
+            }
+            pi = pi.with_highlights(app.highlight.clone(), app.highlight_outside.clone().into());
+        }
+        if let Some(path) = &app.mask {
+            let mask = ImageReader::open(path)?
+                .decode()
+                .map_err(|err| failure(format!("ERROR: --mask {}: {}", path.display(), err)))?;
+            // Stretched over the source first, the mask then goes through
+            // the same sampling, so it lines up pixel for pixel.
+            let mask = match (mask.width(), mask.height()) == (source.width(), source.height()) {
+                true => mask,
+                false => mask.resize_exact(source.width(), source.height(), FilterType::Triangle),
+            };
+            pi = pi.with_mask(
+                Mask::new(&sample(&mask)?)
+                    .with_threshold(app.mask_threshold)
+                    .with_invert(app.mask_invert)
+                    .with_fill(app.mask_fill),
+            );
+        }
+        let bg_threshold = match app.bg {
+            Some(BgOps::Auto) => Some((None, DEFAULT_BG_DISTANCE)),
+            None => app.bg_threshold,
+        };
+        if let Some((color, distance)) = bg_threshold {
+            let background = match color {
+                Some(color) => Background::new(color, distance),
+                None => Background::from_corners(&img, distance),
+            };
+            if app.verbose {
+                let [r, g, b] = background.color;
+                eprintln!("background: #{:02X}{:02X}{:02X}", r, g, b);
+            }
+            pi = pi.with_background(background);
+        }
+        let cells = |img: &DynamicImage| match app.clahe {
+            Some(clip_limit) => pi.cells_with_luma(
+                img,
+                &clahe(&intensity_plane(img, app.luminance.into()), clip_limit),
+            ),
+            None => pi.cells(img),
+        };
+        let grid = cells(&img);
+        let html_frames = match &frames {
+            Some(frames) if html => Some(html_frames(app, frames, |frame| {
+                Ok(cells(&prepare(frame)?))
+            })?),
+            _ => None,
+        };
+        let cell_width = pi.cell_width();
+        let last = i + 1 == args.len();
+        for target in targets.iter_mut().filter(|t| t.error.is_none()) {
+            let out_type = target
+                .output_type
+                .clone()
+                .unwrap_or_else(|| input_type.clone())
+                .color(pi.output_type().color_type().clone());
+            // Anything still wider than the terminal would wrap every row,
+            // so switch line wrapping off while printing.
+            let no_wrap = target.tty
+                && matches!(out_type, OutputType::Term(_))
+                && !matches!(target.sink, Sink::Pager(_))
+                && crossterm::terminal::size().is_ok_and(|(cols, _)| {
+                    grid.first().map_or(0, |row| row.len() as u32) * cell_width > cols as u32
+                });
+            let write = |out: &mut Sink| -> io::Result<()> {
+                // All images share one document: the header goes before the
+                // first, the footer after the last, and a separator between.
+                if i == 0 {
+                    let rows = grid.len() as u32;
+                    let cols = grid.first().map_or(0, |row| row.len() as u32);
+                    pi.write_header(&out_type, (cols, rows), &mut *out)?;
+                } else {
+                    match &app.separator {
+                        Some(sep) => writeln!(out, "{}", sep)?,
+                        None => out_type.write_separator(&mut *out)?,
+                    }
+                }
+                if no_wrap {
+                    crossterm::execute!(out, DisableLineWrap)?;
+                }
+                pi.write_source_start(&source, &out_type, &mut *out)?;
+                match &html_frames {
+                    Some((grids, delays)) if matches!(out_type, OutputType::Html(_)) => {
+                        let mut buf = Vec::new();
+                        html::write_frames_start(delays, app.loops, &mut buf)?;
+                        for grid in grids {
+                            buf.extend_from_slice(b"<span>");
+                            pi.write_cells(grid, &out_type, &mut buf)?;
+                            buf.extend_from_slice(b"</span>");
+                        }
+                        html::write_frames_end(&mut buf)?;
+                        if buf.len() > MAX_HTML_FRAMES_SIZE {
+                            eprintln!(
+                                "WARNING: {}: {} frames take {} MB of HTML, which browsers may be slow to load",
+                                path.display(),
+                                grids.len(),
+                                buf.len() / (1024 * 1024)
+                            );
+                        }
+                        out.write_all(&buf)?;
+                    }
+                    _ => pi.write_cells(&grid, &out_type, &mut *out)?,
+                }
+                pi.write_source_end(&out_type, &mut *out)?;
+                if no_wrap {
+                    crossterm::execute!(out, EnableLineWrap)?;
+                }
+                if app.legend && app.legend_file.is_none() {
+                    out_type.print_line()(&mut *out)?;
+                    pi.write_legend(&out_type, &mut *out)?;
+                }
+                if last {
+                    out_type.write_footer(out)?;
+                }
+                out.flush()
+            };
+            let animate = target.tty && matches!(out_type, OutputType::Term(_));
+            if animate
+                && (app.reveal.is_some() || app.effect.is_some())
+                && let Err(err) = play_animation(app, &pi, &grid, &out_type, &mut target.sink)
+            {
+                target.error = Some(err);
+                continue;
+            }
+            if animate && let Some(frames) = &frames {
+                // The pager would only get the escapes; play on the
+                // terminal behind it.
+                let played = match &mut target.sink {
+                    Sink::Pager(_) => play_frames(app, &pi, frames, prepare, io::stdout()),
+                    sink => play_frames(app, &pi, frames, prepare, sink),
+                };
+                if let Err(err) = played {
+                    target.error = Some(err);
+                    continue;
+                }
+            }
+            if let Err(err) = write(&mut target.sink) {
+                target.error = Some(err);
+            }
+        }
+        // Every image shares the charset, so one legend covers them all.
+        if let Some(path) = app.legend_file.as_ref().filter(|_| i == 0) {
+            let out_type = OutputType::from(path).color(pi.output_type().color_type().clone());
+            let cols = grid.first().map_or(0, |row| row.len() as u32);
+            let mut buf = Vec::new();
+            pi.write_header(&out_type, (cols, grid.len() as u32), &mut buf)?;
+            pi.write_legend(&out_type, &mut buf)?;
+            out_type.write_footer(&mut buf)?;
+            fs::write(path, buf)?;
+        }
+        if app.quality_report {
+            eprintln!("{}: {}", path.display(), pi.quality(&img));
+        }
+    }
+    Ok(())
+}
+
+/// Plays the `--reveal` or `--effect` animation of `grid` on the alternate
+/// screen of `out`; the static image is printed as usual afterwards.
+fn play_animation(
+    app: &Cli,
+    pi: &PixtImg,
+    grid: &CellGrid,
+    out_type: &OutputType,
+    out: &mut Sink,
+) -> io::Result<()> {
+    let seed = app.seed.unwrap_or_else(random_seed);
+    let mut stage = Stage::enter(out)?;
+    let mut clock = FrameClock::new(Duration::from_millis(app.frame_delay));
+    if let Some(per_frame) = app.reveal {
+        let order = match app.reveal_order {
+            RevealOps::Sequential => RevealOrder::Sequential,
+            RevealOps::Rows => RevealOrder::Rows,
+            RevealOps::Random => RevealOrder::Random(seed),
+            RevealOps::CenterOut => RevealOrder::CenterOut,
+        };
+        reveal(
+            &mut stage,
+            pi,
+            grid,
+            out_type,
+            order,
+            per_frame as usize,
+            &mut clock,
+        )?;
+    }
+    match app.effect {
+        Some(EffectOps::Matrix) => {
+            matrix_rain(&mut stage, pi, grid, out_type, seed, &mut clock)?;
+        }
+        // Runs until a key is pressed, so there is nothing left to hold.
+        Some(EffectOps::HueCycle(period)) => {
+            return hue_cycle(&mut stage, pi, grid, out_type, period, &mut clock);
+        }
+        None => {}
+    }
+    if app.hold {
+        stage.wait_key()?;
+    }
+    Ok(())
+}
+
+/// Shortest GIF frame delay played as is. Shorter ones, `0` included, are
+/// played like browsers do, at [`DEFAULT_GIF_DELAY`].
+const MIN_GIF_DELAY: Duration = Duration::from_millis(20);
+const DEFAULT_GIF_DELAY: Duration = Duration::from_millis(100);
+
+/// Whether input `path` stands for stdin.
+fn is_stdin(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+/// Everything piped to stdin, for an input path of `-`.
+fn read_stdin() -> io::Result<Vec<u8>> {
+    let mut stdin = io::stdin().lock();
+    if stdin.is_terminal() {
+        return Err(failure(
+            "ERROR: -: stdin is a terminal, pipe an image to it (e.g. curl URL | pixt -)",
+        ));
+    }
+    let mut buf = Vec::new();
+    stdin.read_to_end(&mut buf)?;
+    if buf.is_empty() {
+        return Err(failure("ERROR: -: nothing was piped to stdin"));
+    }
+    Ok(buf)
+}
+
+/// Decodes input `path`, or the `stdin` bytes read for it, whose format
+/// is told by their first bytes.
+fn decode_input(path: &Path, stdin: Option<&[u8]>) -> io::Result<DynamicImage> {
+    match stdin {
+        Some(bytes) => image::load_from_memory(bytes),
+        None => ImageReader::open(path)?.decode(),
+    }
+    .map_err(|err| failure(err.to_string()))
+}
+
+/// Frames of `path`, or of the `stdin` bytes read for it, with how long
+/// each is shown, if it is a GIF.
+fn gif_frames(
+    path: &Path,
+    stdin: Option<&[u8]>,
+) -> io::Result<Option<Vec<(DynamicImage, Duration)>>> {
+    let frames = match stdin {
+        Some(bytes) if image::guess_format(bytes).ok() == Some(ImageFormat::Gif) => {
+            GifDecoder::new(Cursor::new(bytes))
+                .and_then(|decoder| decoder.into_frames().collect_frames())
+        }
+        Some(_) => return Ok(None),
+        None => {
+            let reader = ImageReader::open(path)?.with_guessed_format()?;
+            if reader.format() != Some(ImageFormat::Gif) {
+                return Ok(None);
+            }
+            GifDecoder::new(reader.into_inner())
+                .and_then(|decoder| decoder.into_frames().collect_frames())
+        }
+    }
+    .map_err(|err| failure(format!("ERROR: {}: {}", path.display(), err)))?;
+    let frames = frames
+        .into_iter()
+        .map(|frame| {
+            let (numer, denom) = frame.delay().numer_denom_ms();
+            let delay = Duration::from_millis(numer as u64 / denom.max(1) as u64);
+            let delay = match delay < MIN_GIF_DELAY {
+                true => DEFAULT_GIF_DELAY,
+                false => delay,
+            };
+            (DynamicImage::ImageRgba8(frame.into_buffer()), delay)
+        })
+        .collect();
+    Ok(Some(frames))
+}
+
+/// Frame size and rate given by `--raw-video`.
+#[derive(Debug, Clone, Copy)]
+struct RawVideo {
+    width: u32,
+    height: u32,
+    fps: Option<f32>,
+}
+
+/// The `--raw-video` frames read from `input`, until it ends or fails.
+struct RawFrames<R> {
+    input: R,
+    video: RawVideo,
+    format: PixelFormatOps,
+    /// Frames read so far.
+    count: usize,
+    /// Bytes of a last frame the input ended in the middle of.
+    partial: usize,
+    error: Option<io::Error>,
+}
+
+impl<R: io::Read> RawFrames<R> {
+    fn new(input: R, video: RawVideo, format: PixelFormatOps) -> Self {
+        Self {
+            input,
+            video,
+            format,
+            count: 0,
+            partial: 0,
+            error: None,
+        }
+    }
+}
+
+impl<R: io::Read> Iterator for RawFrames<R> {
+    type Item = DynamicImage;
+
+    fn next(&mut self) -> Option<DynamicImage> {
+        let RawVideo { width, height, .. } = self.video;
+        let mut buf = vec![0; width as usize * height as usize * self.format.bytes()];
+        let mut filled = 0;
+        while filled < buf.len() {
+            match self.input.read(&mut buf[filled..]) {
+                Ok(0) => {
+                    self.partial = filled;
+                    return None;
+                }
+                Ok(n) => filled += n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => {
+                    self.error = Some(err);
+                    return None;
+                }
+            }
+        }
+        self.count += 1;
+        match self.format {
+            PixelFormatOps::Rgb24 => {
+                RgbImage::from_raw(width, height, buf).map(DynamicImage::ImageRgb8)
+            }
+            PixelFormatOps::Rgba => {
+                RgbaImage::from_raw(width, height, buf).map(DynamicImage::ImageRgba8)
+            }
+        }
+    }
+}
+
+/// Plays live `frames` of `size` pixels on the alternate screen, sized
+/// like an image of that size and shown every `interval` at most. Any key
+/// stops playback. `flag` names the option in errors.
+fn play_live(
+    app: &Cli,
+    flag: &str,
+    size: (u32, u32),
+    frames: impl Iterator<Item = DynamicImage>,
+    interval: Duration,
+) -> io::Result<()> {
+    let caps = TermCaps::detect();
+    if !caps.tty {
+        return Err(failure(format!(
+            "ERROR: {}: stdout is not a terminal",
+            flag
+        )));
+    }
+    let colored = resolve_colored(app.colored, app.color, caps);
+    let charset = app_charset(app)?;
+    let palette = app_palette(app)?;
+    let out_type = OutputType::term();
+    let pi = pixt_img(
+        app,
+        charset.as_ref(),
+        palette.as_ref(),
+        out_type.clone(),
+        colored,
+    )?;
+    let (width, height) = render_size(app, size, &out_type, pi.cell_width(), true)?;
+    let (px_cols, px_rows) = pi.cell_pixels();
+    let (width, height) = (width * px_cols, height * px_rows / 2);
+    let played = frames.map(|frame| {
+        let frame = match app.sampling {
+            SamplingOps::Resize => frame.resize_exact(width, height, FilterType::CatmullRom),
+            SamplingOps::Area => area_resize(&frame, width, height),
+        };
+        match app.colors {
+            Some(n) => quantize(&frame, n as usize).0,
+            None => frame,
+        }
+    });
+    let mut stage = Stage::enter(io::stdout())?;
+    let mut clock = FrameClock::new(interval);
+    let diff = FrameDiff::new().with_sync(app.sync);
+    render_frames(&pi, played, diff, stage.out(), |_| clock.tick())
+}
+
+/// Plays `--raw-video` from stdin, see [`play_live`], then reports how
+/// many frames were shown.
+fn play_raw_video(app: &Cli, video: RawVideo) -> io::Result<()> {
+    if io::stdin().is_terminal() {
+        return Err(failure(
+            "ERROR: --raw-video: pipe the frames to stdin, e.g. from ffmpeg -f rawvideo -",
+        ));
+    }
+    let interval = video
+        .fps
+        .or(app.fps)
+        .map_or(Duration::ZERO, |fps| Duration::from_secs_f32(1.0 / fps));
+    let mut frames = RawFrames::new(io::stdin().lock(), video, app.pixel_format);
+    play_live(
+        app,
+        "--raw-video",
+        (video.width, video.height),
+        &mut frames,
+        interval,
+    )?;
+    if let Some(err) = frames.error {
+        return Err(failure(format!("ERROR: --raw-video: {}", err)));
+    }
+    if frames.partial > 0 {
+        eprintln!(
+            "WARNING: --raw-video: input ended {} bytes into a frame, dropped it",
+            frames.partial
+        );
+    }
+    eprintln!(
+        "{} frame{} rendered",
+        frames.count,
+        if frames.count == 1 { "" } else { "s" }
+    );
+    Ok(())
+}
+
+/// Frame rate of `--camera` when `--fps` isn't given.
+#[cfg(all(feature = "camera", target_os = "linux"))]
+const DEFAULT_CAMERA_FPS: f32 = 15.0;
+
+/// Plays `--camera` live, see [`play_live`].
+#[cfg(all(feature = "camera", target_os = "linux"))]
+fn play_camera(app: &Cli, index: usize) -> io::Result<()> {
+    let mut camera = crate::camera::Camera::open(index)
+        .map_err(|err| failure(format!("ERROR: --camera {}: {}", index, err)))?;
+    let fps = app.fps.unwrap_or(DEFAULT_CAMERA_FPS);
+    let size = camera.size();
+    play_live(
+        app,
+        "--camera",
+        size,
+        &mut camera,
+        Duration::from_secs_f32(1.0 / fps),
+    )?;
+    match camera.error {
+        Some(err) => Err(failure(format!("ERROR: --camera {}: {}", index, err))),
+        None => Ok(()),
+    }
+}
+
+/// Animated HTML past this size gets a warning; each frame is a full copy
+/// of the art.
+const MAX_HTML_FRAMES_SIZE: usize = 20 * 1024 * 1024;
+
+/// Cell grids of the `frames` of an animated GIF for HTML output, resolved
+/// by `cells` like the first frame, with how long each is shown, as the
+/// GIF or `--fps` says.
+fn html_frames(
+    app: &Cli,
+    frames: &[(DynamicImage, Duration)],
+    cells: impl Fn(&DynamicImage) -> io::Result<CellGrid>,
+) -> io::Result<(Vec<CellGrid>, Vec<Duration>)> {
+    let grids = frames
+        .iter()
+        .map(|(frame, _)| cells(frame))
+        .collect::<io::Result<Vec<_>>>()?;
+    let delays = frames
+        .iter()
+        .map(|(_, delay)| match app.fps {
+            Some(fps) => Duration::from_secs_f32(1.0 / fps),
+            None => *delay,
+        })
+        .collect();
+    Ok((grids, delays))
+}
+
+/// Plays the `frames` of an animated GIF `--loop` times on the alternate
+/// screen of `out`, each sampled by `prepare` like the first frame and
+/// shown for its own delay or as `--fps` says; the first frame is printed
+/// as usual afterwards. Any key stops playback. Frames are due at fixed
+/// times from the start, so slow ones don't make the animation drift.
+fn play_frames<W: Write>(
+    app: &Cli,
+    pi: &PixtImg,
+    frames: &[(DynamicImage, Duration)],
+    prepare: impl Fn(&DynamicImage) -> io::Result<DynamicImage>,
+    out: W,
+) -> io::Result<()> {
+    let images = frames
+        .iter()
+        .map(|(frame, _)| prepare(frame))
+        .collect::<io::Result<Vec<_>>>()?;
+    let count = match app.loops {
+        0 => usize::MAX,
+        n => n as usize * images.len(),
+    };
+    let mut stage = Stage::enter(out)?;
+    let mut clock = FrameClock::new(Duration::ZERO);
+    let mut stopped = false;
+    let diff = FrameDiff::new().with_sync(app.sync);
+    let played = images.iter().cloned().cycle().take(count);
+    render_frames(pi, played, diff, stage.out(), |i| {
+        let delay = match app.fps {
+            Some(fps) => Duration::from_secs_f32(1.0 / fps),
+            None => frames[i % frames.len()].1,
+        };
+        clock.set_interval(delay);
+        let next = clock.tick()?;
+        stopped = !next;
+        Ok(next)
+    })?;
+    if app.hold && !stopped {
+        stage.wait_key()?;
+    }
+    Ok(())
+}
+
+/// Seed for animations when `--seed` isn't given.
+fn random_seed() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |v| v.as_nanos() as u64)
+        ^ std::process::id() as u64
+}
+
+fn failure<E: Into<Box<dyn std::error::Error + Send + Sync>>>(err: E) -> io::Error {
+    io::Error::other(err)
+}
+
+fn parse_dimension(name: &str, s: &str) -> Result<u32, String> {
+    let v = s.parse::<u32>().map_err(|err| err.to_string())?;
+    validate_dimension(name, v).map_err(|err| err.to_string())
+}
+
+fn parse_width(s: &str) -> Result<u32, String> {
+    parse_dimension("width", s)
+}
+
+fn parse_height(s: &str) -> Result<u32, String> {
+    parse_dimension("height", s)
+}
+
+/// Play count of `--loop`, `0` for `infinite`.
+fn parse_loops(s: &str) -> Result<u32, String> {
+    match s {
+        "infinite" => Ok(0),
+        s => s.parse::<u32>().map_err(|err| err.to_string()),
+    }
+}
+
+fn parse_positive_f32(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(v) if v.is_finite() && v > 0.0 => Ok(v),
+        Ok(_) => Err(String::from("must be a positive number")),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+fn parse_color(s: &str) -> Result<[u8; 3], String> {
+    parse_hex(s).ok_or_else(|| format!("invalid color '{}', expected #RRGGBB", s))
+}
+
+fn parse_bg_threshold(s: &str) -> Result<(Option<[u8; 3]>, f32), String> {
+    let (color, distance) = s
+        .rsplit_once(':')
+        .ok_or_else(|| String::from("expected `<color>:<distance>`"))?;
+    let color = match color {
+        "auto" => None,
+        _ => Some(parse_hex(color).ok_or_else(|| format!("invalid color '{}'", color))?),
+    };
+    let distance = distance.parse::<f32>().map_err(|err| err.to_string())?;
+    if !distance.is_finite() || distance < 0.0 {
+        return Err(String::from("distance must not be negative"));
+    }
+    Ok((color, distance))
+}
+
+fn parse_effect(s: &str) -> Result<EffectOps, String> {
+    match s.split_once(':') {
+        None if s == "matrix" => Ok(EffectOps::Matrix),
+        None if s == "hue-cycle" => Ok(EffectOps::HueCycle(Duration::from_secs(4))),
+        Some(("hue-cycle", period)) => {
+            let period = parse_positive_f32(period)?;
+            Duration::try_from_secs_f32(period)
+                .map(EffectOps::HueCycle)
+                .map_err(|err| err.to_string())
+        }
+        _ => Err(String::from("expected `matrix` or `hue-cycle[:SECONDS]`")),
+    }
+}
+
+fn parse_dither(s: &str) -> Result<DitherMode, String> {
+    s.parse().map_err(|err: io::Error| err.to_string())
+}
+
+fn parse_block_threshold(s: &str) -> Result<BlockThreshold, String> {
+    s.parse().map_err(|err: io::Error| err.to_string())
+}
+
+fn parse_highlight(s: &str) -> Result<CellRect, String> {
+    let mut parts = s.splitn(3, ',');
+    let (Some(x), Some(y), Some(size)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(String::from("expected X,Y,WxH, e.g. 4,2,10x5"));
+    };
+    let coord = |v: &str| v.trim().parse::<u32>().map_err(|err| err.to_string());
+    let (w, h) = parse_size(size)?;
+    Ok(CellRect {
+        x: coord(x)?,
+        y: coord(y)?,
+        w,
+        h,
+    })
+}
+
+/// `IMAGE` or `IMAGE:ALPHA`; a suffix that isn't a number stays part of
+/// the path.
+fn parse_blend(s: &str) -> Result<(PathBuf, f32), String> {
+    let (path, alpha) = s
+        .rsplit_once(':')
+        .and_then(|(path, alpha)| Some((path, alpha.trim().parse::<f32>().ok()?)))
+        .unwrap_or((s, 1.0));
+    if !(0.0..=1.0).contains(&alpha) {
+        return Err(format!("alpha must be between 0 and 1, got {}", alpha));
+    }
+    if path.is_empty() {
+        return Err(String::from("expected IMAGE[:ALPHA], e.g. logo.png:0.5"));
+    }
+    Ok((PathBuf::from(path), alpha))
+}
+
+fn parse_attr_ramp(s: &str) -> Result<AttrRamp, String> {
+    let (dim, bold) = s
+        .split_once(',')
+        .ok_or_else(|| String::from("expected DIM,BOLD, e.g. 85,171"))?;
+    let parse = |v: &str| v.trim().parse::<u8>().map_err(|err| err.to_string());
+    let (dim, bold) = (parse(dim)?, parse(bold)?);
+    if dim > bold {
+        return Err(String::from("DIM must not be above BOLD"));
+    }
+    Ok(AttrRamp::new(dim, bold))
+}
+
+fn parse_raw_video(s: &str) -> Result<RawVideo, String> {
+    let (size, fps) = match s.split_once('@') {
+        Some((size, fps)) => (size, Some(parse_positive_f32(fps)?)),
+        None => (s, None),
+    };
+    let (width, height) = parse_size(size)?;
+    Ok(RawVideo { width, height, fps })
+}
+
+fn parse_size(s: &str) -> Result<(u32, u32), String> {
+    let (w, h) = s
+        .split_once(['x', 'X'])
+        .ok_or_else(|| String::from("expected WIDTHxHEIGHT, e.g. 1x2"))?;
+    let parse = |v: &str| match v.trim().parse::<u32>() {
+        Ok(0) => Err(String::from("dimensions must be at least 1")),
+        Ok(v) => Ok(v),
+        Err(err) => Err(err.to_string()),
+    };
+    Ok((parse(w)?, parse(h)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A color terminal on stdout, nothing opting out.
+    const TTY: TermCaps = TermCaps {
+        tty: true,
+        no_color: false,
+        color: true,
+        vt: true,
+    };
+
+    #[test]
+    fn colors_need_a_tty_without_no_color_unless_forced() {
+        let modes = [ColorMode::Auto, ColorMode::Always, ColorMode::Never];
+        let no_color = TermCaps {
+            no_color: true,
+            ..TTY
+        };
+        let piped = TermCaps { tty: false, ..TTY };
+        let dumb = TermCaps {
+            color: false,
+            ..TTY
+        };
+        for mode in modes {
+            // Without -c there is nothing to color.
+            for caps in [TTY, no_color, piped, dumb] {
+                assert!(!resolve_colored(false, mode, caps));
+            }
+            let forced = mode == ColorMode::Always;
+            assert_eq!(resolve_colored(true, mode, TTY), mode != ColorMode::Never);
+            assert_eq!(resolve_colored(true, mode, no_color), forced);
+            assert_eq!(resolve_colored(true, mode, piped), forced);
+            assert_eq!(resolve_colored(true, mode, dumb), forced);
+        }
+    }
+
+    #[test]
+    fn rendered_bytes_hold_escapes_only_when_colored() {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_fn(8, 4, |x, y| {
+            image::Rgb([(x * 32) as u8, (y * 64) as u8, 200])
+        }));
+        let piped = TermCaps { tty: false, ..TTY };
+        for colored in [false, true] {
+            for mode in ["auto", "always", "never"] {
+                for caps in [TTY, piped] {
+                    let mut args = vec!["pixt", "--color", mode, "img.png"];
+                    if colored {
+                        args.insert(1, "--colored");
+                    }
+                    let app = Cli::try_parse_from(&args).unwrap();
+                    let colored = resolve_colored(app.colored, app.color, caps);
+                    let pi = pixt_img(&app, None, None, OutputType::term(), colored).unwrap();
+                    let mut out = Vec::new();
+                    pi.print(&img, &mut out).unwrap();
+                    let expected =
+                        app.colored && (mode == "always" || (mode == "auto" && caps.tty));
+                    assert_eq!(out.contains(&0x1b), expected, "{:?} tty={}", args, caps.tty);
+                }
+            }
+        }
+    }
+
+    /// A fresh, empty directory for file tests.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("pixt-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn entries(dir: &Path) -> Vec<String> {
+        let mut names: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn atomic_writer_keeps_the_original_on_failure() {
+        let dir = temp_dir("atomic-fail");
+        let dest = dir.join("art.txt");
+        fs::write(&dest, "original").unwrap();
+        {
+            let mut file = AtomicFileWriter::create(&dest, false).unwrap();
+            file.write_all(b"half a ren").unwrap();
+            file.flush().unwrap();
+            assert_eq!(entries(&dir).len(), 2);
+            // A render error drops the writer without committing it.
+        }
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "original");
+        assert_eq!(entries(&dir), ["art.txt"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn atomic_writer_replaces_or_appends_on_commit() {
+        let dir = temp_dir("atomic-commit");
+        let dest = dir.join("art.txt");
+        fs::write(&dest, "original\n").unwrap();
+        let mut file = AtomicFileWriter::create(&dest, true).unwrap();
+        file.write_all(b"appended\n").unwrap();
+        file.commit().unwrap();
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "original\nappended\n");
+        let mut file = AtomicFileWriter::create(&dest, false).unwrap();
+        file.write_all(b"replaced\n").unwrap();
+        file.commit().unwrap();
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "replaced\n");
+        assert_eq!(entries(&dir), ["art.txt"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,663 @@
+use std::{fmt, io, str::FromStr};
+
+use image::{DynamicImage, GenericImageView, Pixel};
+
+use crate::img::parse_name;
+
+/// A 3×3 color matrix with a per-channel offset, applied as
+/// `out = clamp(matrix * rgb + offset)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorMatrix {
+    pub matrix: [[f32; 3]; 3],
+    pub offset: [f32; 3],
+}
+
+impl ColorMatrix {
+    pub const IDENTITY: Self = Self {
+        matrix: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+        offset: [0.0; 3],
+    };
+
+    pub const fn new(matrix: [[f32; 3]; 3]) -> Self {
+        Self {
+            matrix,
+            offset: [0.0; 3],
+        }
+    }
+
+    pub const fn with_offset(mut self, offset: [f32; 3]) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Rotates hues by `degrees` while keeping luminance, like CSS
+    /// `hue-rotate()`.
+    pub fn hue_rotation(degrees: f32) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        Self::new([
+            [
+                0.213 + cos * 0.787 - sin * 0.213,
+                0.715 - cos * 0.715 - sin * 0.715,
+                0.072 - cos * 0.072 + sin * 0.928,
+            ],
+            [
+                0.213 - cos * 0.213 + sin * 0.143,
+                0.715 + cos * 0.285 + sin * 0.140,
+                0.072 - cos * 0.072 - sin * 0.283,
+            ],
+            [
+                0.213 - cos * 0.213 - sin * 0.787,
+                0.715 - cos * 0.715 + sin * 0.715,
+                0.072 + cos * 0.928 + sin * 0.072,
+            ],
+        ])
+    }
+
+    /// Applies the matrix to a color, clamping each channel to `0..=255`.
+    pub fn apply(&self, [r, g, b]: [u8; 3]) -> [u8; 3] {
+        let (r, g, b) = (r as f32, g as f32, b as f32);
+        let mut out = [0u8; 3];
+        for (i, row) in self.matrix.iter().enumerate() {
+            let v = row[0] * r + row[1] * g + row[2] * b + self.offset[i];
+            out[i] = v.round().clamp(0.0, 255.0) as u8;
+        }
+        out
+    }
+}
+
+/// Color filter presets applied to cell colors before they are emitted.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FilterPreset {
+    #[default]
+    None,
+    Grayscale,
+    Sepia,
+    Cool,
+    Warm,
+    /// Inverts colors, and also the intensity used for character selection.
+    Negative,
+}
+
+impl FilterPreset {
+    pub const ALL: [Self; 6] = [
+        Self::None,
+        Self::Grayscale,
+        Self::Sepia,
+        Self::Cool,
+        Self::Warm,
+        Self::Negative,
+    ];
+    /// Canonical name, as used by [`Display`](fmt::Display) and
+    /// [`FromStr`].
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Grayscale => "grayscale",
+            Self::Sepia => "sepia",
+            Self::Cool => "cool",
+            Self::Warm => "warm",
+            Self::Negative => "negative",
+        }
+    }
+    pub const fn matrix(&self) -> ColorMatrix {
+        match self {
+            Self::None => ColorMatrix::IDENTITY,
+            Self::Grayscale => ColorMatrix::new([
+                [0.299, 0.587, 0.114],
+                [0.299, 0.587, 0.114],
+                [0.299, 0.587, 0.114],
+            ]),
+            Self::Sepia => ColorMatrix::new([
+                [0.393, 0.769, 0.189],
+                [0.349, 0.686, 0.168],
+                [0.272, 0.534, 0.131],
+            ]),
+            Self::Cool => ColorMatrix::new([[0.85, 0.0, 0.0], [0.0, 0.95, 0.0], [0.0, 0.0, 1.15]]),
+            Self::Warm => ColorMatrix::new([[1.15, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 0.85]]),
+            Self::Negative => {
+                ColorMatrix::new([[-1.0, 0.0, 0.0], [0.0, -1.0, 0.0], [0.0, 0.0, -1.0]])
+                    .with_offset([255.0; 3])
+            }
+        }
+    }
+
+    /// Whether this preset also inverts the intensity used to pick characters.
+    pub const fn inverts_intensity(&self) -> bool {
+        matches!(self, Self::Negative)
+    }
+
+    pub fn apply(&self, color: [u8; 3]) -> [u8; 3] {
+        match self {
+            Self::None => color,
+            _ => self.matrix().apply(color),
+        }
+    }
+}
+
+impl fmt::Display for FilterPreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Also accepts `greyscale`.
+impl FromStr for FilterPreset {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Self> {
+        parse_name(
+            "filter preset",
+            s,
+            &Self::ALL,
+            Self::name,
+            &[("greyscale", Self::Grayscale)],
+        )
+    }
+}
+
+/// Perceptual color distance using the "redmean" weighted Euclidean
+/// approximation. Cheap, integer-only, and noticeably closer to human
+/// perception than plain RGB distance.
+pub fn perceptual_distance([r1, g1, b1]: [u8; 3], [r2, g2, b2]: [u8; 3]) -> u32 {
+    let rmean = (r1 as i32 + r2 as i32) / 2;
+    let dr = r1 as i32 - r2 as i32;
+    let dg = g1 as i32 - g2 as i32;
+    let db = b1 as i32 - b2 as i32;
+    ((((512 + rmean) * dr * dr) >> 8) + 4 * dg * dg + (((767 - rmean) * db * db) >> 8)) as u32
+}
+
+/// Colors terminal output is written with.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ColorDepth {
+    /// 24-bit RGB escapes.
+    #[default]
+    TrueColor,
+    /// The xterm 256-color palette, see [`ansi256`], for terminals without
+    /// truecolor support.
+    Ansi256,
+    /// The 16 standard ANSI colors, see [`ansi16`].
+    Ansi16,
+}
+
+impl ColorDepth {
+    pub const ALL: [Self; 3] = [Self::TrueColor, Self::Ansi256, Self::Ansi16];
+    /// Canonical name, as used by [`Display`](fmt::Display) and
+    /// [`FromStr`].
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::TrueColor => "truecolor",
+            Self::Ansi256 => "256",
+            Self::Ansi16 => "16",
+        }
+    }
+}
+
+impl fmt::Display for ColorDepth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Also accepts `24bit`, `ansi256` and `ansi16`.
+impl FromStr for ColorDepth {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Self> {
+        parse_name(
+            "color depth",
+            s,
+            &Self::ALL,
+            Self::name,
+            &[
+                ("24bit", Self::TrueColor),
+                ("ansi256", Self::Ansi256),
+                ("ansi16", Self::Ansi16),
+            ],
+        )
+    }
+}
+
+/// Channel values of the 6×6×6 color cube of the xterm palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Index of the xterm 256-color palette entry closest to `color` by
+/// [`perceptual_distance`], among the color cube (16-231) and the grayscale
+/// ramp (232-255). The first 16 entries are left out, terminals theme them.
+pub fn ansi256(color: [u8; 3]) -> u8 {
+    let cube = color.map(|v| {
+        let (i, _) = CUBE_LEVELS
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, level)| level.abs_diff(v))
+            .expect("the cube has levels");
+        i as u8
+    });
+    let cube = 16 + 36 * cube[0] + 6 * cube[1] + cube[2];
+    let [r, g, b] = color.map(u32::from);
+    let gray = 232 + (((r + g + b) / 3).saturating_sub(3) / 10).min(23) as u8;
+    match perceptual_distance(color, ansi256_rgb(gray))
+        < perceptual_distance(color, ansi256_rgb(cube))
+    {
+        true => gray,
+        false => cube,
+    }
+}
+
+/// Index (`0`-`15`) of the entry of `palette` closest to `color` by
+/// [`perceptual_distance`]. Entries are in ANSI order: black, red, green,
+/// yellow, blue, magenta, cyan and white, then their bright variants.
+pub fn ansi16(color: [u8; 3], palette: &[[u8; 3]; 16]) -> u8 {
+    (0..16)
+        .min_by_key(|&i| perceptual_distance(palette[i as usize], color))
+        .expect("the palette has 16 entries")
+}
+
+/// ANSI escape parameter selecting entry `index` (`0`-`15`) of the 16
+/// color palette as the foreground, or as the background when
+/// `background`: `30`-`37` and `90`-`97`, or `40`-`47` and `100`-`107`.
+pub fn ansi16_code(index: u8, background: bool) -> u8 {
+    let base = match (index < 8, background) {
+        (true, false) => 30,
+        (false, false) => 90 - 8,
+        (true, true) => 40,
+        (false, true) => 100 - 8,
+    };
+    base + index
+}
+
+/// Color of entry `index` of the xterm 256-color palette; the first 16
+/// take their usual xterm defaults.
+pub fn ansi256_rgb(index: u8) -> [u8; 3] {
+    const SYSTEM: [[u8; 3]; 16] = [
+        [0, 0, 0],
+        [205, 0, 0],
+        [0, 205, 0],
+        [205, 205, 0],
+        [0, 0, 238],
+        [205, 0, 205],
+        [0, 205, 205],
+        [229, 229, 229],
+        [127, 127, 127],
+        [255, 0, 0],
+        [0, 255, 0],
+        [255, 255, 0],
+        [92, 92, 255],
+        [255, 0, 255],
+        [0, 255, 255],
+        [255, 255, 255],
+    ];
+    match index {
+        0..16 => SYSTEM[index as usize],
+        16..232 => {
+            let i = index - 16;
+            [i / 36, i / 6 % 6, i % 6].map(|v| CUBE_LEVELS[v as usize])
+        }
+        _ => [8 + 10 * (index - 232); 3],
+    }
+}
+
+/// How cells outside highlighted regions are drawn.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Lowlight {
+    #[default]
+    Grayscale,
+    /// Darkens colors to 40% of their brightness.
+    Dim,
+}
+
+impl Lowlight {
+    pub const fn matrix(&self) -> ColorMatrix {
+        match self {
+            Self::Grayscale => FilterPreset::Grayscale.matrix(),
+            Self::Dim => ColorMatrix::new([[0.4, 0.0, 0.0], [0.0, 0.4, 0.0], [0.0, 0.0, 0.4]]),
+        }
+    }
+
+    pub fn apply(&self, rgb: [u8; 3]) -> [u8; 3] {
+        self.matrix().apply(rgb)
+    }
+}
+
+/// Text attribute a cell is drawn with, see [`AttrRamp`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Attr {
+    #[default]
+    Normal,
+    Dim,
+    Bold,
+}
+
+/// Maps cell brightness to text attributes, so dim and bold text extend the
+/// range of intensities beyond what the glyphs alone show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AttrRamp {
+    /// Cells darker than this are drawn dim.
+    pub dim_below: u8,
+    /// Cells at least this bright are drawn bold.
+    pub bold_from: u8,
+}
+
+impl Default for AttrRamp {
+    /// Dims the darkest third and bolds the brightest third.
+    fn default() -> Self {
+        Self::new(85, 171)
+    }
+}
+
+impl AttrRamp {
+    pub const fn new(dim_below: u8, bold_from: u8) -> Self {
+        Self {
+            dim_below,
+            bold_from,
+        }
+    }
+
+    pub fn attr(&self, intensity: u8) -> Attr {
+        if intensity < self.dim_below {
+            Attr::Dim
+        } else if intensity >= self.bold_from {
+            Attr::Bold
+        } else {
+            Attr::Normal
+        }
+    }
+}
+
+/// A background color: cells whose color lies within `distance` of it are
+/// emitted as unstyled spaces so the background disappears.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Background {
+    pub color: [u8; 3],
+    /// Maximum euclidean RGB distance (`0.0..=441.7`) still counted as background.
+    pub distance: f32,
+}
+
+impl Background {
+    pub const fn new(color: [u8; 3], distance: f32) -> Self {
+        Self { color, distance }
+    }
+
+    /// Guesses the background from the average of the four corner pixels.
+    pub fn from_corners(img: &DynamicImage, distance: f32) -> Self {
+        let (w, h) = img.dimensions();
+        let mut sum = [0u32; 3];
+        if w > 0 && h > 0 {
+            for (x, y) in [(0, 0), (w - 1, 0), (0, h - 1), (w - 1, h - 1)] {
+                for (s, v) in sum.iter_mut().zip(img.get_pixel(x, y).to_rgb().0) {
+                    *s += v as u32;
+                }
+            }
+        }
+        Self::new(sum.map(|s| (s / 4) as u8), distance)
+    }
+
+    pub fn matches(&self, color: [u8; 3]) -> bool {
+        let d: f32 = color
+            .iter()
+            .zip(self.color)
+            .map(|(a, b)| (*a as f32 - b as f32).powi(2))
+            .sum();
+        d.sqrt() <= self.distance
+    }
+}
+
+/// A fixed set of colors that output colors get snapped to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    colors: Vec<[u8; 3]>,
+}
+
+/// Parses the name of a built-in palette: `pico8` (also `pico-8`) or `c64`.
+impl FromStr for Palette {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Self> {
+        let name = parse_name(
+            "palette",
+            s,
+            &["pico8", "c64"],
+            |v| *v,
+            &[("pico-8", "pico8")],
+        )?;
+        Ok(match name {
+            "pico8" => Self::pico8(),
+            _ => Self::c64(),
+        })
+    }
+}
+
+impl Palette {
+    pub const PICO8: [[u8; 3]; 16] = [
+        [0x00, 0x00, 0x00],
+        [0x1D, 0x2B, 0x53],
+        [0x7E, 0x25, 0x53],
+        [0x00, 0x87, 0x51],
+        [0xAB, 0x52, 0x36],
+        [0x5F, 0x57, 0x4F],
+        [0xC2, 0xC3, 0xC7],
+        [0xFF, 0xF1, 0xE8],
+        [0xFF, 0x00, 0x4D],
+        [0xFF, 0xA3, 0x00],
+        [0xFF, 0xEC, 0x27],
+        [0x00, 0xE4, 0x36],
+        [0x29, 0xAD, 0xFF],
+        [0x83, 0x76, 0x9C],
+        [0xFF, 0x77, 0xA8],
+        [0xFF, 0xCC, 0xAA],
+    ];
+    pub const C64: [[u8; 3]; 16] = [
+        [0x00, 0x00, 0x00],
+        [0xFF, 0xFF, 0xFF],
+        [0x68, 0x37, 0x2B],
+        [0x70, 0xA4, 0xB2],
+        [0x6F, 0x3D, 0x86],
+        [0x58, 0x8D, 0x43],
+        [0x35, 0x28, 0x79],
+        [0xB8, 0xC7, 0x6F],
+        [0x6F, 0x4F, 0x25],
+        [0x43, 0x39, 0x00],
+        [0x9A, 0x67, 0x59],
+        [0x44, 0x44, 0x44],
+        [0x6C, 0x6C, 0x6C],
+        [0x9A, 0xD2, 0x84],
+        [0x6C, 0x5E, 0xB5],
+        [0x95, 0x95, 0x95],
+    ];
+
+    /// The 16 colors of VGA text mode, in ANSI order (see [`ansi16`]).
+    pub const VGA: [[u8; 3]; 16] = [
+        [0x00, 0x00, 0x00],
+        [0xAA, 0x00, 0x00],
+        [0x00, 0xAA, 0x00],
+        [0xAA, 0x55, 0x00],
+        [0x00, 0x00, 0xAA],
+        [0xAA, 0x00, 0xAA],
+        [0x00, 0xAA, 0xAA],
+        [0xAA, 0xAA, 0xAA],
+        [0x55, 0x55, 0x55],
+        [0xFF, 0x55, 0x55],
+        [0x55, 0xFF, 0x55],
+        [0xFF, 0xFF, 0x55],
+        [0x55, 0x55, 0xFF],
+        [0xFF, 0x55, 0xFF],
+        [0x55, 0xFF, 0xFF],
+        [0xFF, 0xFF, 0xFF],
+    ];
+
+    pub fn new<T: Into<Vec<[u8; 3]>>>(colors: T) -> Self {
+        Self {
+            colors: colors.into(),
+        }
+    }
+    pub fn pico8() -> Self {
+        Self::new(Self::PICO8)
+    }
+    pub fn c64() -> Self {
+        Self::new(Self::C64)
+    }
+    pub fn colors(&self) -> &[[u8; 3]] {
+        &self.colors
+    }
+
+    /// Parses a palette from either a list of `#rrggbb` lines or a GIMP
+    /// `.gpl` palette file.
+    pub fn parse(input: &str) -> io::Result<Self> {
+        let mut lines = input.lines().enumerate().peekable();
+        let gpl = lines
+            .peek()
+            .is_some_and(|(_, l)| l.trim() == "GIMP Palette");
+        if gpl {
+            lines.next();
+        }
+        let mut colors = Vec::new();
+        for (i, line) in lines {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let color = if gpl {
+                if line.starts_with('#') || line.contains(':') {
+                    continue;
+                }
+                parse_gpl_entry(line)
+            } else {
+                parse_hex(line)
+            };
+            let color = color.ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: invalid color '{}'", i + 1, line),
+                )
+            })?;
+            colors.push(color);
+        }
+        if colors.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "palette contains no colors",
+            ));
+        }
+        Ok(Self::new(colors))
+    }
+
+    /// Returns the palette entry perceptually closest to `color`.
+    pub fn nearest(&self, color: [u8; 3]) -> [u8; 3] {
+        self.colors
+            .iter()
+            .copied()
+            .min_by_key(|c| perceptual_distance(*c, color))
+            .unwrap_or(color)
+    }
+}
+
+/// Parses `#rrggbb` (the leading `#` is optional).
+pub fn parse_hex(s: &str) -> Option<[u8; 3]> {
+    let s = s.trim();
+    let s = s.strip_prefix('#').unwrap_or(s);
+    if s.len() != 6 || !s.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&s[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+fn parse_gpl_entry(line: &str) -> Option<[u8; 3]> {
+    let mut parts = line.split_whitespace().map(|v| v.parse::<u8>().ok());
+    Some([parts.next()??, parts.next()??, parts.next()??])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_presets_map_known_colors() {
+        let cases = [
+            (FilterPreset::None, [12, 34, 56], [12, 34, 56]),
+            (FilterPreset::Grayscale, [255, 0, 0], [76, 76, 76]),
+            (FilterPreset::Grayscale, [255, 255, 255], [255, 255, 255]),
+            (FilterPreset::Sepia, [100, 100, 100], [135, 120, 94]),
+            // Clamped, not wrapped.
+            (FilterPreset::Sepia, [255, 255, 255], [255, 255, 239]),
+            (FilterPreset::Cool, [200, 200, 200], [170, 190, 230]),
+            (FilterPreset::Cool, [255, 255, 255], [217, 242, 255]),
+            (FilterPreset::Warm, [200, 200, 200], [230, 200, 170]),
+            (FilterPreset::Negative, [10, 20, 250], [245, 235, 5]),
+        ];
+        for (preset, input, output) in cases {
+            assert_eq!(preset.apply(input), output, "{} of {:?}", preset, input);
+        }
+    }
+
+    #[test]
+    fn only_negative_inverts_intensity() {
+        for preset in FilterPreset::ALL {
+            assert_eq!(preset.inverts_intensity(), preset == FilterPreset::Negative);
+        }
+    }
+
+    #[test]
+    fn ansi256_maps_its_own_colors_back() {
+        for index in 16..=255 {
+            assert_eq!(
+                ansi256(ansi256_rgb(index)),
+                index,
+                "{:?}",
+                ansi256_rgb(index)
+            );
+        }
+        assert_eq!(ansi256([255, 0, 0]), 196);
+        assert_eq!(ansi256([255, 255, 255]), 231);
+        assert_eq!(ansi256([128, 128, 128]), 244);
+        assert_eq!(ansi256([250, 5, 3]), 196);
+    }
+
+    /// Rounding each channel to the cube is not always the nearest entry,
+    /// but never much further off.
+    #[test]
+    fn ansi256_is_never_far_off() {
+        for r in (0..=255).step_by(15) {
+            for g in (0..=255).step_by(15) {
+                for b in (0..=255).step_by(15) {
+                    let color = [r as u8, g as u8, b as u8];
+                    let picked = perceptual_distance(color, ansi256_rgb(ansi256(color)));
+                    let best = (16..=255)
+                        .map(|i| perceptual_distance(color, ansi256_rgb(i)))
+                        .min()
+                        .unwrap();
+                    assert!(picked <= best * 2, "{:?}: {} for {}", color, picked, best);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ansi16_maps_palette_colors_back() {
+        for palette in [Palette::VGA, Palette::PICO8, Palette::C64] {
+            for (index, &color) in palette.iter().enumerate() {
+                assert_eq!(ansi16(color, &palette) as usize, index, "{:?}", color);
+            }
+        }
+        // Near misses still land on the entry.
+        assert_eq!(ansi16([0xA0, 0x08, 0x04], &Palette::VGA), 1);
+        assert_eq!(ansi16([0xF0, 0xF8, 0x60], &Palette::VGA), 11);
+    }
+
+    #[test]
+    fn ansi16_codes_split_normal_and_bright() {
+        let fg: Vec<u8> = (0..16).map(|i| ansi16_code(i, false)).collect();
+        let bg: Vec<u8> = (0..16).map(|i| ansi16_code(i, true)).collect();
+        assert_eq!(
+            fg,
+            [
+                30, 31, 32, 33, 34, 35, 36, 37, 90, 91, 92, 93, 94, 95, 96, 97
+            ]
+        );
+        assert_eq!(
+            bg,
+            [
+                40, 41, 42, 43, 44, 45, 46, 47, 100, 101, 102, 103, 104, 105, 106, 107
+            ]
+        );
+    }
+}
@@ -0,0 +1,446 @@
+use std::{fmt, io, str::FromStr};
+
+use crate::img::parse_name;
+
+/// How intensities are spread over the characters of a charset.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DitherMode {
+    /// Every intensity maps straight to its nearest level.
+    #[default]
+    None,
+    /// Offsets each cell by a tiled 64×64 blue-noise threshold before the
+    /// level lookup.
+    ///
+    /// Unlike ordered (Bayer) dithering it shows no cross-hatch pattern, and
+    /// as the offset only depends on the cell position, identical frames
+    /// dither identically, which keeps animated output from crawling.
+    BlueNoise,
+    /// Offsets each cell by a threshold from a `size`×`size` Bayer matrix,
+    /// tiled over the grid. `size` is one of [`BAYER_SIZES`].
+    ///
+    /// Stable across frames like [`BlueNoise`](Self::BlueNoise), with the
+    /// regular cross-hatch typical of ordered dithering.
+    Ordered { size: u8 },
+    /// Floyd–Steinberg error diffusion: how far each cell's level is off
+    /// is carried over to the cells right of and below it.
+    ///
+    /// Gives the smoothest gradients, but a change anywhere shifts the
+    /// pattern of everything after it, so animations shimmer.
+    FloydSteinberg,
+}
+
+impl DitherMode {
+    pub const ALL: [Self; 4] = [
+        Self::None,
+        Self::BlueNoise,
+        Self::Ordered {
+            size: DEFAULT_BAYER_SIZE,
+        },
+        Self::FloydSteinberg,
+    ];
+    /// Canonical name, as used by [`Display`](fmt::Display) and
+    /// [`FromStr`]. [`Ordered`](Self::Ordered) displays its size too, as
+    /// `ordered:SIZE`.
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::BlueNoise => "blue-noise",
+            Self::Ordered { .. } => "ordered",
+            Self::FloydSteinberg => "floyd-steinberg",
+        }
+    }
+}
+
+impl fmt::Display for DitherMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ordered { size } => write!(f, "{}:{}", self.name(), size),
+            _ => f.write_str(self.name()),
+        }
+    }
+}
+
+/// Also accepts `bluenoise` and `fs`, and `ordered:SIZE` for the Bayer
+/// matrix size; plain `ordered` is `ordered:4`.
+impl FromStr for DitherMode {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Self> {
+        if let Some((name, size)) = s.split_once(':')
+            && name.trim().eq_ignore_ascii_case("ordered")
+        {
+            return match size.trim().parse() {
+                Ok(size) if BAYER_SIZES.contains(&size) => Ok(Self::Ordered { size }),
+                _ => Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "invalid Bayer matrix size '{}', expected one of: {:?}",
+                        size, BAYER_SIZES
+                    ),
+                )),
+            };
+        }
+        parse_name(
+            "dither mode",
+            s,
+            &Self::ALL,
+            Self::name,
+            &[("bluenoise", Self::BlueNoise), ("fs", Self::FloydSteinberg)],
+        )
+    }
+}
+
+/// Side lengths of the Bayer matrices of [`DitherMode::Ordered`].
+pub const BAYER_SIZES: [u8; 3] = [2, 4, 8];
+
+/// Bayer matrix size of a plain `ordered` [`DitherMode`].
+pub const DEFAULT_BAYER_SIZE: u8 = 4;
+
+/// Side length of [`BLUE_NOISE`].
+pub const BLUE_NOISE_SIZE: usize = 64;
+
+/// Blue-noise threshold texture in row-major order, generated with the
+/// void-and-cluster method (Gaussian sigma 1.5, toroidal). Every value in
+/// `0..=255` occurs equally often.
+#[rustfmt::skip]
+pub const BLUE_NOISE: [u8; BLUE_NOISE_SIZE * BLUE_NOISE_SIZE] = [
+    16, 52, 94, 146, 118, 195, 14, 148, 200, 80, 29, 109, 14, 241, 138, 188,
+    113, 170, 52, 101, 185, 43, 79, 28, 135, 195, 73, 170, 146, 104, 186, 27,
+    235, 196, 56, 89, 222, 43, 161, 245, 119, 98, 37, 77, 132, 250, 64, 230,
+    141, 12, 253, 93, 219, 54, 247, 172, 205, 149, 11, 70, 93, 40, 62, 167,
+    228, 130, 181, 236, 56, 82, 176, 41, 125, 253, 146, 182, 64, 168, 43, 94,
+    255, 31, 148, 229, 12, 155, 204, 118, 243, 51, 111, 254, 22, 212, 53, 121,
+    75, 109, 136, 237, 15, 127, 206, 52, 3, 177, 218, 20, 194, 156, 2, 123,
+    195, 53, 171, 34, 156, 131, 6, 69, 128, 39, 230, 175, 252, 152, 210, 109,
+    191, 36, 75, 24, 166, 219, 96, 237, 19, 100, 52, 223, 91, 205, 18, 126,
+    198, 69, 207, 84, 126, 240, 96, 64, 152, 11, 201, 127, 63, 163, 137, 249,
+    10, 216, 36, 172, 67, 184, 83, 149, 231, 61, 144, 92, 48, 107, 180, 37,
+    100, 147, 207, 118, 67, 210, 165, 227, 84, 184, 56, 124, 25, 136, 9, 88,
+    156, 253, 144, 203, 111, 7, 133, 205, 72, 160, 197, 4, 116, 146, 231, 54,
+    160, 22, 110, 180, 57, 34, 189, 19, 214, 175, 38, 91, 232, 15, 86, 194,
+    150, 181, 91, 145, 110, 253, 27, 104, 197, 124, 248, 162, 205, 236, 70, 217,
+    245, 17, 82, 232, 24, 95, 44, 111, 20, 203, 104, 220, 79, 197, 237, 49,
+    120, 4, 100, 45, 232, 65, 151, 50, 181, 230, 125, 41, 249, 69, 173, 85,
+    225, 138, 245, 2, 220, 169, 141, 231, 78, 106, 225, 141, 180, 207, 44, 103,
+    24, 65, 240, 8, 199, 42, 225, 169, 18, 41, 79, 8, 120, 28, 140, 87,
+    163, 58, 190, 136, 178, 240, 193, 133, 250, 147, 3, 163, 38, 112, 67, 172,
+    200, 84, 221, 161, 124, 189, 245, 31, 106, 16, 84, 156, 185, 29, 107, 7,
+    186, 46, 77, 154, 117, 69, 103, 43, 129, 162, 56, 1, 76, 119, 242, 169,
+    219, 127, 205, 54, 157, 120, 72, 139, 95, 212, 176, 227, 63, 172, 202, 6,
+    126, 210, 40, 108, 1, 150, 72, 29, 174, 66, 90, 244, 186, 153, 223, 18,
+    245, 58, 181, 28, 79, 14, 90, 167, 218, 144, 240, 61, 214, 135, 238, 202,
+    121, 95, 210, 26, 195, 250, 12, 201, 242, 22, 189, 237, 150, 32, 61, 140,
+    81, 33, 106, 177, 84, 215, 5, 187, 243, 51, 110, 150, 92, 255, 51, 104,
+    237, 73, 153, 252, 85, 51, 224, 102, 232, 44, 216, 132, 58, 26, 97, 134,
+    33, 146, 110, 209, 239, 138, 204, 121, 74, 45, 178, 110, 9, 90, 51, 148,
+    35, 172, 233, 135, 48, 87, 165, 145, 60, 93, 122, 212, 101, 175, 200, 5,
+    230, 158, 251, 17, 137, 237, 57, 160, 30, 131, 195, 17, 39, 124, 155, 189,
+    32, 176, 18, 217, 125, 198, 168, 14, 144, 118, 193, 12, 109, 239, 191, 76,
+    168, 230, 10, 68, 154, 39, 57, 254, 1, 200, 131, 34, 195, 160, 218, 73,
+    246, 17, 63, 101, 179, 224, 33, 112, 219, 172, 36, 68, 16, 247, 91, 122,
+    185, 51, 74, 193, 38, 97, 198, 115, 86, 220, 72, 240, 181, 217, 20, 81,
+    228, 136, 92, 60, 158, 34, 113, 82, 207, 32, 161, 69, 175, 142, 48, 215,
+    98, 55, 132, 178, 96, 193, 110, 172, 152, 98, 231, 78, 251, 119, 14, 105,
+    163, 129, 205, 155, 8, 125, 190, 78, 10, 252, 130, 198, 162, 136, 47, 209,
+    21, 112, 149, 223, 125, 170, 20, 252, 146, 13, 163, 101, 140, 64, 109, 204,
+    53, 115, 242, 187, 9, 213, 244, 182, 61, 251, 93, 230, 208, 85, 0, 123,
+    25, 198, 248, 31, 218, 8, 234, 29, 67, 217, 18, 144, 59, 171, 41, 188,
+    227, 88, 38, 253, 74, 234, 53, 211, 158, 89, 51, 228, 82, 28, 223, 70,
+    171, 243, 88, 2, 59, 233, 79, 42, 181, 60, 204, 47, 0, 234, 168, 145,
+    7, 171, 37, 142, 103, 76, 42, 132, 3, 149, 115, 19, 40, 156, 255, 185,
+    78, 149, 113, 85, 162, 127, 80, 141, 182, 40, 116, 189, 210, 92, 240, 139,
+    54, 0, 115, 186, 144, 20, 107, 135, 29, 188, 143, 6, 115, 190, 149, 103,
+    133, 32, 187, 212, 155, 113, 202, 134, 226, 110, 246, 126, 190, 93, 34, 251,
+    88, 221, 69, 203, 231, 151, 175, 97, 225, 198, 53, 184, 126, 101, 61, 221,
+    173, 6, 207, 43, 241, 60, 195, 214, 95, 245, 162, 51, 5, 130, 27, 72,
+    216, 198, 159, 59, 97, 206, 170, 246, 63, 219, 100, 240, 166, 57, 255, 13,
+    227, 61, 140, 44, 95, 16, 163, 68, 9, 87, 31, 159, 74, 216, 120, 59,
+    194, 132, 14, 120, 52, 21, 240, 68, 31, 165, 82, 242, 214, 167, 31, 137,
+    46, 235, 69, 184, 145, 17, 107, 48, 11, 132, 77, 222, 105, 232, 155, 174,
+    98, 125, 242, 25, 230, 44, 83, 3, 115, 173, 45, 24, 209, 94, 38, 180,
+    81, 206, 108, 234, 181, 250, 38, 214, 173, 144, 197, 227, 18, 148, 180, 23,
+    161, 101, 245, 180, 85, 195, 111, 142, 207, 123, 11, 144, 68, 15, 239, 108,
+    216, 132, 98, 27, 118, 225, 172, 240, 152, 201, 28, 177, 65, 197, 46, 251,
+    13, 42, 75, 176, 108, 192, 157, 224, 145, 78, 192, 123, 69, 141, 200, 123,
+    158, 7, 167, 25, 65, 131, 91, 121, 239, 46, 117, 65, 104, 42, 238, 80,
+    218, 46, 151, 31, 221, 159, 5, 47, 229, 101, 187, 45, 96, 180, 199, 83,
+    13, 164, 195, 253, 158, 75, 35, 123, 61, 89, 255, 113, 148, 16, 86, 113,
+    186, 137, 215, 149, 10, 126, 60, 35, 202, 16, 248, 159, 226, 0, 238, 101,
+    49, 247, 126, 85, 222, 152, 197, 24, 79, 187, 5, 248, 163, 206, 134, 111,
+    2, 201, 71, 97, 134, 61, 254, 176, 73, 26, 249, 217, 157, 124, 56, 149,
+    114, 39, 59, 86, 1, 203, 99, 216, 180, 6, 160, 36, 228, 128, 217, 151,
+    59, 237, 89, 36, 204, 254, 93, 235, 133, 106, 51, 89, 35, 174, 77, 22,
+    185, 70, 214, 192, 46, 1, 231, 61, 159, 99, 217, 129, 84, 14, 62, 186,
+    252, 138, 174, 239, 16, 205, 122, 93, 201, 164, 135, 78, 2, 231, 33, 248,
+    207, 175, 230, 130, 220, 49, 164, 20, 231, 136, 206, 98, 56, 190, 73, 31,
+    200, 8, 167, 117, 71, 178, 22, 163, 69, 183, 221, 151, 203, 111, 133, 222,
+    152, 110, 34, 140, 96, 178, 107, 137, 242, 33, 174, 51, 194, 235, 167, 34,
+    90, 57, 23, 116, 186, 83, 32, 152, 10, 59, 114, 38, 204, 104, 190, 79,
+    140, 97, 22, 154, 110, 185, 140, 79, 108, 48, 71, 178, 240, 1, 159, 246,
+    102, 130, 227, 54, 219, 135, 103, 45, 210, 7, 120, 23, 63, 244, 42, 194,
+    58, 241, 9, 169, 251, 58, 211, 17, 191, 122, 75, 147, 27, 98, 143, 221,
+    124, 207, 152, 229, 52, 166, 220, 242, 103, 211, 237, 179, 139, 66, 165, 9,
+    55, 214, 75, 240, 15, 63, 250, 33, 194, 243, 124, 25, 142, 88, 118, 178,
+    45, 74, 187, 26, 158, 3, 194, 228, 143, 81, 252, 180, 140, 93, 165, 19,
+    87, 125, 208, 73, 114, 27, 163, 86, 52, 224, 13, 255, 209, 118, 67, 9,
+    177, 40, 76, 102, 4, 131, 64, 141, 43, 170, 23, 90, 220, 20, 112, 233,
+    29, 123, 182, 43, 202, 91, 211, 119, 154, 11, 212, 168, 220, 49, 233, 23,
+    213, 143, 250, 108, 86, 241, 75, 117, 29, 169, 100, 39, 224, 4, 210, 146,
+    232, 173, 50, 154, 229, 196, 130, 245, 151, 108, 182, 92, 159, 42, 187, 246,
+    106, 224, 170, 243, 190, 208, 20, 86, 192, 123, 73, 153, 46, 254, 185, 154,
+    199, 247, 100, 166, 129, 147, 4, 173, 56, 100, 77, 36, 105, 190, 69, 126,
+    91, 6, 161, 42, 209, 140, 53, 188, 235, 58, 206, 128, 74, 186, 108, 66,
+    36, 102, 191, 21, 92, 42, 70, 6, 215, 36, 135, 62, 1, 232, 83, 149,
+    60, 16, 136, 31, 90, 117, 252, 157, 227, 4, 241, 199, 99, 131, 63, 87,
+    138, 5, 67, 26, 233, 50, 220, 84, 234, 191, 159, 244, 135, 12, 152, 206,
+    172, 230, 64, 124, 180, 31, 164, 10, 89, 150, 13, 240, 160, 47, 251, 131,
+    218, 14, 238, 117, 141, 219, 180, 100, 194, 80, 237, 201, 172, 129, 212, 21,
+    123, 203, 72, 159, 50, 176, 71, 37, 104, 58, 135, 33, 174, 8, 210, 40,
+    109, 221, 178, 120, 78, 185, 109, 39, 138, 23, 116, 52, 203, 87, 253, 31,
+    48, 106, 195, 19, 226, 96, 247, 129, 200, 112, 179, 31, 121, 92, 10, 199,
+    166, 82, 150, 65, 253, 13, 155, 122, 54, 162, 21, 113, 34, 97, 50, 191,
+    163, 250, 112, 215, 236, 11, 148, 217, 180, 208, 165, 80, 218, 114, 236, 164,
+    191, 53, 145, 243, 200, 20, 150, 253, 202, 66, 218, 17, 164, 61, 179, 114,
+    142, 83, 243, 147, 73, 116, 57, 213, 41, 77, 220, 63, 196, 228, 148, 55,
+    115, 38, 212, 171, 45, 86, 203, 29, 248, 140, 219, 67, 246, 154, 225, 74,
+    95, 40, 2, 141, 99, 198, 126, 22, 87, 118, 13, 249, 62, 144, 26, 73,
+    252, 19, 90, 35, 103, 59, 169, 9, 123, 96, 175, 132, 226, 101, 7, 232,
+    209, 15, 176, 42, 216, 0, 183, 152, 23, 253, 140, 98, 169, 22, 75, 177,
+    246, 98, 1, 128, 185, 111, 234, 75, 173, 4, 88, 131, 183, 11, 122, 27,
+    234, 179, 67, 193, 45, 78, 231, 56, 244, 43, 153, 97, 183, 48, 201, 99,
+    133, 174, 212, 157, 229, 128, 221, 75, 186, 45, 246, 79, 37, 148, 193, 74,
+    161, 61, 121, 92, 162, 135, 235, 85, 106, 174, 48, 4, 244, 131, 214, 25,
+    137, 225, 197, 60, 228, 18, 136, 48, 212, 109, 192, 37, 210, 56, 174, 145,
+    209, 105, 157, 242, 19, 174, 105, 163, 133, 193, 213, 20, 130, 239, 160, 3,
+    80, 50, 122, 71, 11, 182, 92, 30, 237, 153, 0, 207, 113, 236, 49, 127,
+    29, 220, 189, 252, 28, 201, 66, 13, 197, 121, 208, 154, 60, 109, 88, 191,
+    46, 73, 151, 92, 35, 165, 193, 95, 150, 61, 243, 161, 76, 103, 252, 81,
+    47, 15, 130, 85, 205, 139, 30, 220, 0, 67, 108, 228, 81, 30, 113, 218,
+    188, 238, 28, 198, 250, 42, 146, 211, 105, 129, 64, 165, 24, 173, 85, 248,
+    98, 137, 9, 76, 110, 44, 125, 165, 241, 32, 75, 226, 181, 38, 235, 155,
+    122, 19, 176, 239, 119, 70, 249, 9, 227, 30, 121, 17, 231, 135, 7, 195,
+    117, 226, 188, 55, 114, 251, 70, 184, 91, 147, 176, 53, 167, 207, 62, 145,
+    13, 162, 109, 136, 81, 167, 116, 57, 16, 229, 194, 95, 141, 218, 5, 182,
+    204, 41, 229, 153, 181, 237, 209, 94, 55, 141, 100, 17, 134, 201, 6, 58,
+    255, 208, 106, 6, 217, 145, 44, 126, 176, 202, 90, 145, 188, 41, 214, 153,
+    62, 166, 33, 234, 9, 158, 45, 123, 233, 38, 254, 8, 139, 101, 247, 41,
+    222, 95, 212, 52, 189, 3, 234, 199, 176, 82, 28, 255, 47, 70, 110, 149,
+    59, 166, 116, 54, 90, 6, 144, 27, 188, 215, 158, 249, 82, 116, 166, 101,
+    181, 79, 140, 56, 188, 88, 210, 104, 71, 157, 50, 224, 66, 111, 85, 27,
+    237, 96, 140, 75, 180, 94, 214, 196, 21, 111, 205, 88, 192, 21, 177, 83,
+    132, 65, 17, 244, 153, 100, 71, 134, 41, 157, 118, 181, 206, 134, 239, 20,
+    82, 245, 16, 199, 224, 167, 72, 254, 113, 2, 64, 177, 30, 211, 71, 220,
+    40, 23, 162, 243, 33, 171, 14, 233, 25, 254, 114, 0, 163, 246, 176, 132,
+    190, 3, 204, 120, 221, 28, 140, 78, 150, 62, 162, 129, 70, 234, 117, 157,
+    239, 203, 171, 36, 122, 210, 30, 249, 96, 225, 61, 14, 102, 36, 170, 192,
+    122, 214, 98, 137, 33, 120, 50, 178, 86, 231, 128, 47, 238, 149, 18, 133,
+    233, 119, 213, 99, 131, 67, 155, 192, 140, 39, 178, 208, 97, 21, 217, 54,
+    77, 255, 44, 164, 59, 106, 249, 5, 177, 235, 45, 221, 32, 209, 55, 1,
+    44, 111, 144, 89, 230, 57, 180, 148, 6, 170, 125, 238, 155, 84, 223, 52,
+    29, 151, 62, 190, 78, 233, 208, 138, 39, 166, 204, 108, 86, 189, 55, 169,
+    91, 191, 69, 17, 197, 226, 114, 53, 87, 219, 74, 126, 59, 141, 38, 119,
+    171, 107, 145, 16, 236, 190, 41, 117, 202, 92, 13, 112, 171, 143, 99, 191,
+    80, 25, 183, 69, 196, 21, 115, 84, 207, 48, 216, 74, 196, 2, 132, 105,
+    236, 176, 10, 250, 156, 21, 96, 12, 195, 70, 23, 145, 8, 227, 111, 251,
+    0, 49, 150, 248, 43, 92, 4, 246, 133, 166, 14, 241, 186, 159, 233, 201,
+    13, 224, 88, 206, 129, 83, 169, 226, 70, 131, 247, 186, 83, 21, 248, 164,
+    131, 217, 252, 5, 128, 161, 241, 66, 189, 104, 21, 142, 43, 251, 165, 70,
+    199, 87, 127, 47, 107, 184, 126, 246, 151, 93, 242, 216, 172, 38, 137, 76,
+    198, 127, 179, 106, 166, 139, 209, 182, 24, 107, 203, 44, 86, 5, 99, 72,
+    154, 55, 181, 34, 63, 150, 11, 52, 146, 31, 156, 58, 230, 124, 66, 205,
+    98, 56, 150, 105, 211, 48, 221, 10, 136, 248, 167, 114, 183, 94, 213, 18,
+    145, 35, 228, 202, 68, 225, 36, 60, 213, 117, 49, 125, 66, 100, 208, 160,
+    28, 223, 79, 12, 236, 66, 34, 77, 226, 57, 151, 118, 222, 196, 133, 249,
+    25, 125, 243, 102, 217, 186, 242, 111, 212, 191, 102, 4, 200, 42, 155, 9,
+    240, 36, 171, 81, 30, 142, 94, 177, 37, 87, 59, 222, 11, 64, 125, 50,
+    245, 112, 168, 2, 137, 164, 86, 174, 5, 189, 28, 155, 184, 239, 12, 60,
+    243, 95, 39, 211, 120, 193, 157, 127, 172, 94, 252, 30, 167, 65, 48, 177,
+    208, 80, 161, 3, 123, 29, 91, 167, 18, 77, 219, 170, 139, 89, 226, 179,
+    143, 119, 222, 192, 238, 60, 201, 114, 153, 228, 27, 200, 148, 235, 161, 193,
+    80, 184, 57, 100, 212, 23, 237, 143, 104, 252, 81, 224, 18, 87, 148, 109,
+    170, 134, 187, 152, 54, 19, 90, 241, 41, 199, 10, 141, 81, 239, 19, 110,
+    145, 42, 229, 196, 74, 234, 138, 58, 247, 127, 35, 68, 254, 115, 27, 79,
+    198, 13, 69, 22, 109, 162, 13, 245, 68, 194, 105, 133, 81, 35, 101, 14,
+    211, 26, 147, 255, 73, 122, 49, 198, 64, 128, 168, 52, 115, 191, 219, 45,
+    204, 5, 70, 255, 99, 223, 203, 2, 113, 71, 177, 216, 105, 190, 155, 222,
+    93, 25, 115, 51, 147, 179, 42, 200, 88, 155, 232, 106, 15, 185, 214, 50,
+    101, 242, 159, 134, 185, 83, 215, 46, 130, 7, 179, 47, 253, 174, 225, 143,
+    122, 94, 223, 37, 159, 184, 95, 16, 223, 37, 208, 141, 243, 71, 27, 127,
+    83, 228, 114, 23, 175, 139, 68, 169, 146, 232, 125, 55, 37, 130, 1, 69,
+    182, 245, 166, 215, 94, 15, 109, 220, 5, 188, 51, 206, 158, 60, 129, 164,
+    38, 85, 206, 49, 255, 29, 145, 99, 169, 236, 72, 211, 112, 0, 74, 45,
+    241, 66, 175, 130, 12, 208, 242, 150, 178, 92, 21, 100, 7, 175, 152, 249,
+    184, 41, 147, 199, 47, 121, 29, 250, 44, 96, 22, 227, 198, 89, 254, 203,
+    46, 126, 7, 67, 189, 253, 158, 64, 136, 116, 22, 143, 90, 244, 1, 224,
+    184, 142, 7, 103, 124, 67, 189, 227, 32, 89, 151, 26, 165, 136, 202, 183,
+    155, 8, 197, 55, 111, 80, 39, 119, 71, 238, 156, 196, 225, 120, 55, 102,
+    19, 163, 89, 241, 73, 226, 189, 87, 210, 154, 183, 73, 138, 169, 32, 109,
+    159, 85, 225, 141, 39, 124, 26, 231, 173, 76, 197, 230, 35, 177, 77, 117,
+    248, 60, 228, 181, 218, 156, 2, 116, 59, 205, 108, 244, 58, 218, 93, 20,
+    108, 215, 86, 247, 169, 224, 141, 1, 205, 46, 131, 61, 83, 35, 200, 232,
+    68, 211, 128, 4, 172, 103, 15, 135, 64, 7, 246, 106, 15, 232, 62, 143,
+    236, 27, 175, 107, 211, 83, 199, 98, 40, 250, 102, 62, 124, 218, 149, 23,
+    107, 160, 82, 43, 23, 93, 197, 249, 172, 142, 9, 191, 82, 36, 128, 249,
+    52, 134, 32, 147, 17, 63, 187, 251, 107, 173, 24, 183, 254, 160, 129, 11,
+    173, 112, 56, 219, 145, 40, 164, 234, 196, 124, 167, 51, 214, 120, 183, 11,
+    75, 202, 49, 240, 12, 171, 54, 147, 215, 18, 139, 184, 10, 97, 55, 197,
+    212, 15, 130, 171, 236, 139, 47, 81, 26, 220, 52, 124, 158, 234, 176, 68,
+    164, 227, 190, 102, 203, 128, 89, 52, 147, 78, 221, 113, 4, 96, 223, 76,
+    143, 247, 24, 186, 84, 249, 115, 53, 97, 33, 82, 192, 148, 40, 86, 221,
+    165, 128, 94, 152, 65, 132, 242, 0, 120, 165, 49, 211, 153, 239, 171, 37,
+    72, 231, 100, 203, 63, 113, 216, 162, 133, 99, 177, 228, 14, 107, 25, 206,
+    117, 3, 78, 49, 238, 35, 168, 213, 19, 234, 40, 202, 142, 52, 179, 28,
+    197, 43, 101, 137, 64, 202, 26, 222, 139, 206, 237, 18, 99, 251, 200, 112,
+    34, 247, 16, 194, 225, 105, 187, 78, 196, 91, 244, 72, 32, 84, 118, 138,
+    186, 51, 153, 11, 246, 31, 185, 10, 68, 246, 32, 88, 66, 193, 147, 90,
+    41, 244, 157, 177, 116, 222, 6, 105, 160, 130, 92, 165, 71, 215, 116, 234,
+    87, 164, 214, 235, 11, 173, 149, 72, 0, 156, 57, 129, 168, 66, 3, 151,
+    58, 184, 74, 120, 40, 23, 158, 45, 229, 25, 111, 132, 192, 220, 3, 253,
+    22, 90, 193, 121, 79, 150, 97, 230, 118, 197, 151, 212, 135, 253, 53, 224,
+    182, 135, 97, 15, 65, 141, 185, 72, 247, 55, 188, 30, 246, 20, 156, 46,
+    130, 2, 69, 110, 49, 126, 93, 186, 255, 110, 182, 221, 32, 121, 175, 232,
+    103, 142, 229, 163, 216, 86, 250, 138, 65, 175, 207, 12, 160, 61, 179, 107,
+    167, 235, 36, 223, 178, 55, 203, 43, 170, 56, 3, 112, 40, 168, 7, 122,
+    71, 29, 212, 194, 254, 91, 43, 204, 116, 10, 211, 104, 134, 82, 206, 103,
+    253, 189, 146, 178, 209, 244, 31, 213, 46, 88, 13, 77, 196, 241, 80, 27,
+    207, 44, 5, 98, 61, 182, 123, 8, 219, 95, 149, 48, 237, 96, 38, 148,
+    207, 125, 69, 142, 0, 112, 254, 23, 143, 93, 239, 189, 82, 219, 98, 195,
+    155, 241, 53, 119, 25, 152, 227, 28, 173, 146, 66, 230, 174, 7, 183, 62,
+    22, 217, 39, 81, 15, 159, 67, 120, 169, 135, 233, 154, 105, 46, 136, 191,
+    88, 127, 255, 196, 153, 19, 205, 102, 162, 30, 248, 80, 122, 199, 226, 76,
+    54, 20, 244, 98, 215, 160, 85, 188, 121, 226, 71, 157, 24, 128, 57, 234,
+    15, 87, 139, 167, 80, 180, 104, 132, 77, 238, 34, 154, 50, 116, 225, 142,
+    168, 95, 121, 239, 139, 101, 194, 8, 240, 30, 204, 60, 21, 213, 161, 9,
+    226, 173, 70, 30, 116, 53, 231, 74, 194, 57, 137, 185, 26, 144, 8, 113,
+    137, 187, 156, 47, 193, 30, 64, 217, 14, 179, 35, 136, 247, 200, 173, 36,
+    114, 182, 228, 2, 219, 47, 245, 12, 187, 97, 125, 202, 89, 194, 33, 77,
+    233, 54, 204, 26, 182, 55, 220, 90, 146, 74, 112, 184, 128, 250, 73, 118,
+    56, 151, 105, 214, 238, 142, 171, 39, 119, 223, 9, 106, 235, 68, 177, 251,
+    213, 103, 12, 75, 119, 229, 134, 154, 50, 108, 208, 64, 11, 106, 78, 147,
+    209, 62, 41, 108, 200, 67, 144, 209, 57, 219, 0, 245, 24, 134, 240, 105,
+    6, 128, 157, 75, 112, 248, 40, 171, 200, 49, 217, 4, 170, 96, 30, 182,
+    242, 39, 12, 183, 77, 94, 2, 252, 152, 89, 169, 209, 49, 158, 94, 41,
+    79, 164, 201, 238, 176, 92, 5, 249, 81, 168, 237, 92, 164, 218, 26, 241,
+    132, 97, 251, 157, 129, 16, 99, 160, 37, 114, 163, 78, 182, 54, 159, 210,
+    178, 37, 224, 189, 2, 151, 129, 17, 104, 252, 138, 84, 228, 51, 202, 143,
+    83, 199, 133, 158, 44, 195, 129, 208, 26, 67, 243, 32, 127, 193, 224, 24,
+    241, 55, 128, 37, 147, 56, 206, 114, 193, 22, 142, 46, 192, 119, 53, 169,
+    6, 187, 21, 76, 175, 238, 195, 77, 251, 179, 64, 139, 216, 94, 17, 70,
+    144, 243, 93, 59, 215, 85, 233, 67, 190, 24, 164, 35, 121, 156, 235, 24,
+    113, 222, 65, 243, 24, 229, 108, 51, 183, 137, 102, 201, 84, 1, 115, 149,
+    97, 6, 209, 111, 18, 243, 161, 32, 66, 230, 122, 1, 254, 149, 72, 226,
+    85, 204, 118, 224, 34, 53, 119, 25, 130, 16, 232, 39, 110, 255, 124, 199,
+    47, 114, 17, 138, 172, 33, 205, 158, 123, 90, 222, 68, 188, 13, 102, 60,
+    170, 4, 95, 117, 175, 68, 157, 83, 238, 10, 165, 54, 154, 248, 63, 188,
+    140, 174, 233, 63, 190, 78, 131, 183, 96, 153, 199, 79, 102, 33, 183, 127,
+    38, 160, 57, 144, 95, 210, 154, 227, 186, 84, 148, 192, 7, 168, 34, 233,
+    85, 162, 192, 252, 99, 53, 109, 12, 244, 43, 202, 143, 247, 82, 213, 138,
+    253, 193, 50, 207, 142, 8, 216, 36, 196, 123, 229, 25, 213, 134, 40, 222,
+    76, 42, 89, 153, 222, 103, 44, 236, 12, 216, 50, 167, 232, 206, 17, 245,
+    106, 215, 25, 249, 188, 3, 65, 100, 45, 216, 104, 57, 211, 76, 137, 183,
+    4, 221, 71, 28, 127, 229, 187, 136, 63, 174, 104, 1, 54, 117, 179, 38,
+    76, 123, 161, 32, 247, 88, 180, 111, 148, 63, 91, 178, 72, 103, 170, 23,
+    254, 196, 120, 28, 170, 6, 201, 146, 62, 127, 27, 115, 63, 137, 87, 153,
+    66, 179, 134, 71, 113, 162, 244, 138, 171, 10, 248, 125, 156, 229, 50, 99,
+    121, 143, 50, 207, 156, 5, 76, 162, 218, 22, 131, 229, 158, 205, 19, 227,
+    153, 15, 233, 105, 59, 131, 228, 15, 255, 41, 204, 118, 235, 14, 209, 124,
+    100, 10, 236, 139, 56, 245, 117, 84, 173, 248, 188, 222, 9, 177, 45, 198,
+    11, 239, 93, 20, 205, 42, 86, 30, 197, 76, 166, 29, 90, 19, 194, 249,
+    24, 175, 235, 108, 87, 196, 241, 34, 95, 198, 80, 184, 33, 94, 135, 58,
+    101, 210, 83, 172, 199, 27, 161, 73, 99, 168, 8, 150, 48, 190, 60, 159,
+    46, 184, 68, 214, 91, 187, 22, 210, 37, 104, 73, 155, 94, 250, 117, 225,
+    103, 39, 166, 230, 146, 181, 215, 127, 238, 110, 49, 204, 181, 114, 152, 62,
+    203, 77, 35, 166, 19, 133, 54, 115, 150, 254, 48, 115, 239, 70, 168, 246,
+    187, 44, 133, 2, 223, 118, 47, 192, 213, 126, 225, 80, 247, 139, 88, 227,
+    134, 156, 109, 35, 165, 130, 72, 157, 236, 4, 136, 48, 192, 31, 161, 58,
+    146, 195, 122, 81, 58, 7, 106, 62, 20, 151, 231, 129, 65, 242, 8, 132,
+    96, 218, 119, 250, 65, 212, 174, 223, 14, 65, 166, 8, 147, 213, 16, 119,
+    29, 160, 236, 62, 148, 82, 235, 143, 18, 59, 185, 33, 107, 3, 178, 28,
+    80, 221, 201, 1, 250, 47, 226, 107, 58, 170, 208, 231, 126, 81, 213, 0,
+    75, 236, 18, 211, 133, 251, 163, 223, 179, 91, 3, 215, 37, 84, 223, 168,
+    44, 154, 0, 185, 144, 102, 26, 79, 191, 138, 232, 201, 102, 43, 176, 89,
+    208, 74, 112, 191, 21, 181, 107, 34, 91, 242, 116, 161, 217, 198, 121, 244,
+];
+
+impl DitherMode {
+    /// Threshold in `0.0..1.0` for the cell at `(x, y)`, or `None` when
+    /// not dithering by threshold.
+    pub fn threshold(&self, x: u32, y: u32) -> Option<f32> {
+        match self {
+            Self::None | Self::FloydSteinberg => None,
+            Self::BlueNoise => {
+                let (x, y) = (x as usize % BLUE_NOISE_SIZE, y as usize % BLUE_NOISE_SIZE);
+                Some((BLUE_NOISE[y * BLUE_NOISE_SIZE + x] as f32 + 0.5) / 256.0)
+            }
+            Self::Ordered { size } => {
+                let bits = size.max(&1).ilog2();
+                Some((bayer(x, y, bits) as f32 + 0.5) / (1u32 << (2 * bits)) as f32)
+            }
+        }
+    }
+}
+
+/// Entry `(x, y)` of the tiled `2^bits`-sided Bayer matrix, in
+/// `0..4^bits`: the bits of `x ^ y` and `y` interleaved, lowest first.
+fn bayer(x: u32, y: u32, bits: u32) -> u32 {
+    (0..bits).fold(0, |v, bit| {
+        let v = (v << 1) | ((x ^ y) >> bit & 1);
+        (v << 1) | (y >> bit & 1)
+    })
+}
+
+/// Shifts `intensity` by up to half a level of an `n`-level axis either way,
+/// following `threshold` (see [`DitherMode::threshold`]).
+pub fn dither(intensity: u8, n: usize, threshold: Option<f32>) -> u8 {
+    match threshold {
+        Some(t) => {
+            let step = 256.0 / n.max(1) as f32;
+            (intensity as f32 + (t - 0.5) * step)
+                .round()
+                .clamp(0.0, 255.0) as u8
+        }
+        None => intensity,
+    }
+}
+
+/// Quantization errors on their way to later cells with
+/// [`DitherMode::FloydSteinberg`], for the current and the next row of
+/// cells. Each column holds the error of both charset axes, `[column, row]`.
+#[derive(Debug, Clone)]
+pub(crate) struct Diffusion {
+    current: Vec<[f32; 2]>,
+    next: Vec<[f32; 2]>,
+}
+
+impl Diffusion {
+    /// Errors for rows `width` cells wide.
+    pub(crate) fn new(width: usize) -> Self {
+        Self {
+            current: vec![[0.0; 2]; width],
+            next: vec![[0.0; 2]; width],
+        }
+    }
+    /// Error carried into column `i` of the current row.
+    pub(crate) fn carried(&self, i: usize) -> [f32; 2] {
+        self.current[i]
+    }
+    /// Spreads the error of column `i` over the cells not visited yet:
+    /// 7/16 to the right, 3/16, 5/16 and 1/16 to the row below.
+    pub(crate) fn spread(&mut self, i: usize, err: [f32; 2]) {
+        let add = |row: &mut [[f32; 2]], at: Option<usize>, weight: f32| {
+            if let Some(cell) = at.and_then(|at| row.get_mut(at)) {
+                cell[0] += err[0] * weight;
+                cell[1] += err[1] * weight;
+            }
+        };
+        add(&mut self.current, Some(i + 1), 7.0 / 16.0);
+        add(&mut self.next, i.checked_sub(1), 3.0 / 16.0);
+        add(&mut self.next, Some(i), 5.0 / 16.0);
+        add(&mut self.next, Some(i + 1), 1.0 / 16.0);
+    }
+    /// Moves on to the next row.
+    pub(crate) fn next_row(&mut self) {
+        std::mem::swap(&mut self.current, &mut self.next);
+        self.next.fill([0.0; 2]);
+    }
+}
@@ -0,0 +1,123 @@
+//! Directional glyphs along the edges of the image.
+
+use image::GrayImage;
+
+/// Gradient magnitude from which cells count as edges, unless set otherwise
+/// with [`EdgeOptions::with_threshold`].
+pub const DEFAULT_EDGE_THRESHOLD: u8 = 96;
+
+/// Draws cells on strong edges with a glyph following the edge, instead of
+/// the one the charset picks by intensity; see
+/// [`PixtImg::with_edges`](crate::img::PixtImg::with_edges).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EdgeOptions {
+    /// Sobel gradient magnitude (`0`-`255`, a hard black-to-white step
+    /// being `255`) from which a cell counts as an edge.
+    pub threshold: u8,
+    /// Glyphs for vertical edges, rising diagonals, horizontal edges through
+    /// the top pixel of a cell, falling diagonals and horizontal edges
+    /// through the bottom pixel.
+    pub chars: [char; 5],
+}
+
+impl Default for EdgeOptions {
+    fn default() -> Self {
+        Self {
+            threshold: DEFAULT_EDGE_THRESHOLD,
+            chars: ['|', '/', '-', '\\', '_'],
+        }
+    }
+}
+
+impl EdgeOptions {
+    /// Sets the gradient magnitude from which cells count as edges.
+    pub fn with_threshold(mut self, threshold: u8) -> Self {
+        self.threshold = threshold;
+        self
+    }
+    /// Sets the glyphs, in the order of [`chars`](Self::chars).
+    pub fn with_chars(mut self, chars: [char; 5]) -> Self {
+        self.chars = chars;
+        self
+    }
+}
+
+/// Sobel gradients of an intensity plane, one per pixel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EdgeMap {
+    width: u32,
+    height: u32,
+    gradients: Vec<(i32, i32)>,
+}
+
+impl EdgeMap {
+    /// Runs the 3×3 Sobel operator over `plane`, repeating the border
+    /// pixels past its edges.
+    pub fn new(plane: &GrayImage) -> Self {
+        let (width, height) = plane.dimensions();
+        let at = |x: i64, y: i64| {
+            let x = x.clamp(0, width as i64 - 1) as u32;
+            let y = y.clamp(0, height as i64 - 1) as u32;
+            plane.get_pixel(x, y).0[0] as i32
+        };
+        let gradients = (0..height as i64)
+            .flat_map(|y| (0..width as i64).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let gx = at(x + 1, y - 1) + 2 * at(x + 1, y) + at(x + 1, y + 1)
+                    - at(x - 1, y - 1)
+                    - 2 * at(x - 1, y)
+                    - at(x - 1, y + 1);
+                let gy = at(x - 1, y + 1) + 2 * at(x, y + 1) + at(x + 1, y + 1)
+                    - at(x - 1, y - 1)
+                    - 2 * at(x, y - 1)
+                    - at(x + 1, y - 1);
+                (gx, gy)
+            })
+            .collect();
+        Self {
+            width,
+            height,
+            gradients,
+        }
+    }
+    /// Gradient magnitude at `(x, y)`, scaled to `0`-`255`; `None` outside
+    /// the plane.
+    pub fn magnitude(&self, x: u32, y: u32) -> Option<u8> {
+        let (gx, gy) = self.gradient(x, y)?;
+        // A full step gives 4 × 255 along one axis.
+        Some(((gx as f32).hypot(gy as f32) / 4.0).min(255.0) as u8)
+    }
+    fn gradient(&self, x: u32, y: u32) -> Option<(i32, i32)> {
+        (x < self.width && y < self.height).then(|| self.gradients[(y * self.width + x) as usize])
+    }
+    /// Edge glyph for the cell drawn from the pixels at `top` and `bottom`
+    /// (`(x, y)` each), following the stronger of the two gradients; `None`
+    /// when neither reaches the threshold of `options`.
+    pub fn glyph(
+        &self,
+        options: &EdgeOptions,
+        top: (u32, u32),
+        bottom: (u32, u32),
+    ) -> Option<char> {
+        let strength = |(x, y): (u32, u32)| self.magnitude(x, y).unwrap_or(0);
+        let (at_bottom, (x, y)) = match strength(bottom) > strength(top) {
+            true => (true, bottom),
+            false => (false, top),
+        };
+        if strength((x, y)) < options.threshold.max(1) {
+            return None;
+        }
+        let (gx, gy) = self.gradient(x, y)?;
+        // The edge runs across the gradient; with y pointing down, a
+        // gradient towards the bottom right lies across a rising edge.
+        let angle = (gy as f32).atan2(gx as f32).to_degrees().rem_euclid(180.0);
+        let [vertical, rising, top_line, falling, bottom_line] = options.chars;
+        Some(match angle {
+            a if !(22.5..157.5).contains(&a) => vertical,
+            a if a < 67.5 => rising,
+            a if a < 112.5 && at_bottom => bottom_line,
+            a if a < 112.5 => top_line,
+            _ => falling,
+        })
+    }
+}
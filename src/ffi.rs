@@ -0,0 +1,265 @@
+//! C API for embedding pixt in non-Rust applications.
+//!
+//! Build with `--features ffi` to get `libpixt.{so,a}`; the matching header
+//! is `include/pixt.h`. Every function reports failure through a
+//! [`PixtStatus`]; the matching message is available from
+//! [`pixt_last_error_message`] on the same thread. Panics never cross the
+//! boundary, they are reported as [`PixtStatus::Panic`].
+
+use std::{
+    cell::RefCell,
+    ffi::{CStr, CString, c_char},
+    panic::{self, AssertUnwindSafe},
+    ptr, slice,
+};
+
+use image::imageops::FilterType;
+
+use crate::{
+    img::{ColorType, Graphemes, OutputType, PixtImg},
+    size::{target_size, validate_dimension},
+    style::ImgStyle,
+};
+
+/// Width used when neither `width` nor `height` is given.
+const DEFAULT_WIDTH: u32 = 80;
+
+/// Result of a pixt C API call.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixtStatus {
+    Ok = 0,
+    /// A required pointer argument was null.
+    NullArgument = 1,
+    /// An option had an unknown or out-of-range value.
+    InvalidOption = 2,
+    /// The image bytes could not be decoded.
+    Decode = 3,
+    /// Rendering failed.
+    Render = 4,
+    /// pixt panicked; this is a bug.
+    Panic = 5,
+}
+
+/// Colors emitted for every cell.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixtColor {
+    None = 0,
+    /// Average of both pixels as the foreground color.
+    AvgFg = 1,
+    /// Average of both pixels as the background color.
+    AvgBg = 2,
+    /// Top pixel as foreground, bottom pixel as background.
+    FgTopBgDown = 3,
+    /// Top pixel as background, bottom pixel as foreground.
+    BgTopFgDown = 4,
+    /// Top pixel as the foreground color.
+    TopFg = 5,
+    /// Bottom pixel as the foreground color.
+    BottomFg = 6,
+}
+
+/// Output format.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixtFormat {
+    /// Plain text, colors are ignored.
+    Text = 0,
+    /// Text with ANSI escape sequences.
+    Ansi = 1,
+    /// A standalone HTML page.
+    Html = 2,
+}
+
+/// Options for [`pixt_render`]. Zeroed options render 80 columns of plain
+/// ASCII text.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct PixtOptions {
+    /// Style name (`ascii`, `block`, `pixel`, `braills`, `dots`, `solid`,
+    /// `half-block`, `quadrant`, `sextant` or `braille-dots`), or null for
+    /// `ascii`.
+    pub style: *const c_char,
+    /// Custom characters ordered from darkest to brightest (UTF-8). Takes
+    /// precedence over `style` when not null.
+    pub charset: *const c_char,
+    /// Output width in characters, `0` to derive it from `height`.
+    pub width: u32,
+    /// Output height in characters, `0` to derive it from `width`.
+    pub height: u32,
+    /// One of [`PixtColor`].
+    pub color: u32,
+    /// One of [`PixtFormat`].
+    pub format: u32,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(msg: impl Into<String>) {
+    let msg = CString::new(msg.into().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|v| *v.borrow_mut() = Some(msg));
+}
+
+/// Renders an encoded image (PNG, JPEG, ...) to text.
+///
+/// On success `*out` receives a NUL-terminated buffer owned by pixt, which
+/// must be released with [`pixt_free`], and `*out_len` its length without the
+/// terminator. `opts` may be null for the defaults.
+///
+/// # Safety
+///
+/// `image_bytes` must point to `len` readable bytes, `opts` must be null or
+/// point to a valid [`PixtOptions`] whose strings are NUL-terminated, and
+/// `out`/`out_len` must be valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pixt_render(
+    image_bytes: *const u8,
+    len: usize,
+    opts: *const PixtOptions,
+    out: *mut *mut c_char,
+    out_len: *mut usize,
+) -> PixtStatus {
+    if image_bytes.is_null() || out.is_null() || out_len.is_null() {
+        set_last_error("image_bytes, out and out_len must not be null");
+        return PixtStatus::NullArgument;
+    }
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        // SAFETY: the caller guarantees both pointers are valid.
+        let bytes = unsafe { slice::from_raw_parts(image_bytes, len) };
+        let opts = unsafe { opts.as_ref() };
+        render(bytes, opts)
+    }));
+    match result {
+        Ok(Ok(text)) => {
+            let text = CString::new(text).unwrap_or_default();
+            // SAFETY: the caller guarantees `out` and `out_len` are writable.
+            unsafe {
+                *out_len = text.as_bytes().len();
+                *out = text.into_raw();
+            }
+            PixtStatus::Ok
+        }
+        Ok(Err((status, msg))) => {
+            set_last_error(msg);
+            status
+        }
+        Err(payload) => {
+            let msg = payload
+                .downcast_ref::<&str>()
+                .map(|v| v.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| String::from("unknown panic"));
+            set_last_error(format!("panic: {}", msg));
+            PixtStatus::Panic
+        }
+    }
+}
+
+/// Releases a buffer returned by [`pixt_render`]. Null is ignored.
+///
+/// # Safety
+///
+/// `out` must be null or a pointer obtained from [`pixt_render`] that has
+/// not been freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pixt_free(out: *mut c_char) {
+    if !out.is_null() {
+        // SAFETY: `out` came from `CString::into_raw` in `pixt_render`.
+        drop(unsafe { CString::from_raw(out) });
+    }
+}
+
+/// Message describing the last error on the calling thread, or null if no
+/// call failed yet. The pointer stays valid until the next failing call on
+/// the same thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn pixt_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|v| v.borrow().as_ref().map_or(ptr::null(), |v| v.as_ptr()))
+}
+
+fn render(bytes: &[u8], opts: Option<&PixtOptions>) -> Result<String, (PixtStatus, String)> {
+    let invalid = |msg: String| (PixtStatus::InvalidOption, msg);
+    let string = |p: *const c_char, name: &str| -> Result<Option<String>, (PixtStatus, String)> {
+        if p.is_null() {
+            return Ok(None);
+        }
+        // SAFETY: the caller guarantees option strings are NUL-terminated.
+        let s = unsafe { CStr::from_ptr(p) };
+        s.to_str()
+            .map(|v| Some(v.to_string()))
+            .map_err(|_| invalid(format!("{} is not valid UTF-8", name)))
+    };
+    let (style, charset, width, height, color, format) = match opts {
+        Some(o) => (
+            string(o.style, "style")?,
+            string(o.charset, "charset")?,
+            o.width,
+            o.height,
+            o.color,
+            o.format,
+        ),
+        None => (None, None, 0, 0, 0, 0),
+    };
+    let color = match color {
+        v if v == PixtColor::None as u32 => ColorType::None,
+        v if v == PixtColor::AvgFg as u32 => ColorType::AvgFgOnly,
+        v if v == PixtColor::AvgBg as u32 => ColorType::AvgBgOnly,
+        v if v == PixtColor::FgTopBgDown as u32 => ColorType::FgTopBgDown,
+        v if v == PixtColor::BgTopFgDown as u32 => ColorType::BgTopFgDown,
+        v if v == PixtColor::TopFg as u32 => ColorType::TopFgOnly,
+        v if v == PixtColor::BottomFg as u32 => ColorType::BottomFgOnly,
+        v => return Err(invalid(format!("unknown color mode {}", v))),
+    };
+    let output_type = match format {
+        v if v == PixtFormat::Text as u32 => OutputType::text(),
+        v if v == PixtFormat::Ansi as u32 => OutputType::term(),
+        v if v == PixtFormat::Html as u32 => OutputType::html(),
+        v => return Err(invalid(format!("unknown output format {}", v))),
+    }
+    .color(color);
+
+    for (name, value) in [("width", width), ("height", height)] {
+        if value > 0 {
+            validate_dimension(name, value).map_err(|err| invalid(err.to_string()))?;
+        }
+    }
+
+    let img =
+        image::load_from_memory(bytes).map_err(|err| (PixtStatus::Decode, err.to_string()))?;
+    let (w, h) = target_size(
+        (img.width(), img.height()),
+        (width > 0).then_some(width),
+        (height > 0).then_some(height),
+        output_type.cell_aspect(),
+        false,
+        || Ok(DEFAULT_WIDTH),
+    )
+    .map_err(|err| (PixtStatus::Render, err.to_string()))?;
+
+    let pi = match (charset, style.as_deref()) {
+        (Some(chars), _) if chars.is_empty() => {
+            return Err(invalid(String::from("charset is empty")));
+        }
+        (Some(chars), _) => PixtImg::new(Graphemes::row(&chars), output_type),
+        (None, None | Some("ascii")) => PixtImg::new(ImgStyle::Ascii, output_type),
+        (None, Some("block")) => PixtImg::new(ImgStyle::Block, output_type),
+        (None, Some("pixel")) => PixtImg::new(ImgStyle::Pixel, output_type),
+        (None, Some("braills")) => PixtImg::new(ImgStyle::Braills, output_type),
+        (None, Some("dots")) => PixtImg::new(ImgStyle::Dots, output_type),
+        (None, Some("solid")) => PixtImg::new(ImgStyle::Solid, output_type),
+        (None, Some("half-block")) => PixtImg::new(ImgStyle::HalfBlock, output_type),
+        (None, Some("quadrant")) => PixtImg::new(ImgStyle::Quadrant, output_type),
+        (None, Some("sextant")) => PixtImg::new(ImgStyle::Sextant, output_type),
+        (None, Some("braille-dots")) => PixtImg::new(ImgStyle::BrailleDots, output_type),
+        (None, Some(other)) => return Err(invalid(format!("unknown style '{}'", other))),
+    };
+    let (px_cols, px_rows) = pi.cell_pixels();
+    let img = img.resize_exact(w * px_cols, h * px_rows / 2, FilterType::CatmullRom);
+    let mut buf = Vec::with_capacity(pi.estimated_output_size(&img));
+    pi.print(&img, &mut buf)
+        .map_err(|err| (PixtStatus::Render, err.to_string()))?;
+    String::from_utf8(buf).map_err(|err| (PixtStatus::Render, err.to_string()))
+}
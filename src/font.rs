@@ -0,0 +1,180 @@
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    io,
+};
+
+use ab_glyph::{Font, FontRef, PxScale, ScaleFont, point};
+
+/// Pixel size glyphs are rasterized at when measuring coverage.
+pub const RASTER_PX: f32 = 48.0;
+
+/// Printable ASCII characters (`' '..='~'`).
+pub fn printable_ascii() -> Vec<char> {
+    (' '..='~').collect()
+}
+
+/// Loads a TrueType/OpenType font from raw file bytes.
+pub fn load_font(data: &[u8]) -> io::Result<FontRef<'_>> {
+    FontRef::try_from_slice(data).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Fraction (`0.0..=1.0`) of a character cell covered with ink when `ch` is
+/// drawn in `font`, or `None` if the font has no glyph for it.
+///
+/// The cell is the glyph's advance width by the font's line height, so the
+/// result is comparable across glyphs of a monospace font.
+pub fn glyph_coverage<F: Font>(font: &F, ch: char, px: f32) -> Option<f32> {
+    let id = font.glyph_id(ch);
+    if id.0 == 0 {
+        return None;
+    }
+    let scaled = font.as_scaled(PxScale::from(px));
+    let cell = scaled.h_advance(id) * (scaled.ascent() - scaled.descent());
+    if cell <= 0.0 {
+        return None;
+    }
+    let glyph = id.with_scale_and_position(px, point(0.0, scaled.ascent()));
+    let mut ink = 0.0;
+    if let Some(outlined) = font.outline_glyph(glyph) {
+        outlined.draw(|_, _, c| ink += c);
+    }
+    Some((ink / cell).clamp(0.0, 1.0))
+}
+
+/// Measures every character of `chars` that exists in `font`, sorted from
+/// least to most coverage.
+pub fn measure<F: Font>(font: &F, chars: &[char], px: f32) -> Vec<(char, f32)> {
+    let mut measured: Vec<(char, f32)> = chars
+        .iter()
+        .filter_map(|&ch| Some((ch, glyph_coverage(font, ch, px)?)))
+        .collect();
+    measured.sort_by(|a, b| a.1.total_cmp(&b.1));
+    measured
+}
+
+/// Picks up to `levels` characters from `measured` (sorted by coverage) whose
+/// coverage is as evenly spaced as possible between the lightest and the
+/// heaviest glyph. Each character is used at most once.
+pub fn select_levels(measured: &[(char, f32)], levels: usize) -> Vec<char> {
+    let (Some(first), Some(last)) = (measured.first(), measured.last()) else {
+        return Vec::new();
+    };
+    if levels <= 1 {
+        return vec![first.0];
+    }
+    let (min, max) = (first.1, last.1);
+    let mut used = vec![false; measured.len()];
+    let mut ramp = Vec::with_capacity(levels);
+    for i in 0..levels {
+        let target = min + (max - min) * i as f32 / (levels - 1) as f32;
+        let best = measured
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| !used[*j])
+            .min_by(|(_, a), (_, b)| (a.1 - target).abs().total_cmp(&(b.1 - target).abs()));
+        if let Some((j, (ch, _))) = best {
+            used[j] = true;
+            ramp.push(*ch);
+        }
+    }
+    // Keep the ramp ordered by coverage even if neighbouring picks crossed.
+    ramp.sort_by_key(|ch| measured.iter().position(|(c, _)| c == ch));
+    ramp
+}
+
+/// Builds a brightness ramp of `levels` characters from `chars`, tuned to
+/// the glyph shapes of the font in `font_data`.
+pub fn ramp_from_font(font_data: &[u8], chars: &[char], levels: usize) -> io::Result<Vec<char>> {
+    let font = load_font(font_data)?;
+    let measured = measure(&font, chars, RASTER_PX);
+    if measured.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "font has none of the requested characters",
+        ));
+    }
+    Ok(select_levels(&measured, levels))
+}
+
+/// Coverage of characters measured by [`glyph_coverage`] at [`RASTER_PX`],
+/// kept per font and character so each glyph is rasterized only once.
+#[derive(Debug, Default, Clone)]
+pub struct GlyphDensity {
+    cache: HashMap<(u64, char), Option<f32>>,
+}
+
+impl GlyphDensity {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Coverage of every character of `chars` in the font in `font_data`,
+    /// `None` for those the font has no glyph for.
+    pub fn densities(&mut self, font_data: &[u8], chars: &[char]) -> io::Result<Vec<Option<f32>>> {
+        let mut hasher = DefaultHasher::new();
+        font_data.hash(&mut hasher);
+        let key = hasher.finish();
+        let mut font = None;
+        chars
+            .iter()
+            .map(|&ch| {
+                if let Some(density) = self.cache.get(&(key, ch)) {
+                    return Ok(*density);
+                }
+                let font = match &font {
+                    Some(font) => font,
+                    None => font.insert(load_font(font_data)?),
+                };
+                let density = glyph_coverage(font, ch, RASTER_PX);
+                self.cache.insert((key, ch), density);
+                Ok(density)
+            })
+            .collect()
+    }
+}
+
+/// Intensity boundaries (see
+/// [`PixtData::set_thresholds`](crate::img::PixtData::set_thresholds)) that
+/// give each character of a ramp the intensities nearest its coverage, the
+/// lightest glyph standing for `0` and the heaviest for `255`.
+///
+/// Characters missing from the font (`None`) take the coverage between their
+/// measured neighbours, or that of the nearest one at the ends of the ramp,
+/// where they are never picked. Characters lighter than one before them are
+/// never picked either, so the ramp should be ordered. `None` when fewer
+/// than two characters differ in coverage.
+pub fn density_thresholds(densities: &[Option<f32>]) -> Option<Vec<u8>> {
+    let known: Vec<(usize, f32)> = densities
+        .iter()
+        .enumerate()
+        .filter_map(|(i, d)| Some((i, (*d)?)))
+        .collect();
+    let (min, max) = known.iter().fold((f32::MAX, f32::MIN), |(lo, hi), (_, d)| {
+        (lo.min(*d), hi.max(*d))
+    });
+    if known.len() < 2 || max <= min {
+        return None;
+    }
+    let filled = (0..densities.len()).map(|i| {
+        let next = known.partition_point(|(j, _)| *j < i);
+        match (next.checked_sub(1).map(|p| known[p]), known.get(next)) {
+            (_, Some(&(j, d))) if j == i => d,
+            (Some((a, da)), Some(&(b, db))) => da + (db - da) * (i - a) as f32 / (b - a) as f32,
+            (Some((_, d)), None) | (None, Some(&(_, d))) => d,
+            (None, None) => min,
+        }
+    });
+    let mut level = 0.0f32;
+    let levels: Vec<f32> = filled
+        .map(|d| {
+            level = level.max((d - min) / (max - min) * u8::MAX as f32);
+            level
+        })
+        .collect();
+    Some(
+        levels
+            .windows(2)
+            .map(|w| ((w[0] + w[1]) / 2.0).round() as u8)
+            .collect(),
+    )
+}
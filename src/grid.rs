@@ -0,0 +1,140 @@
+use crate::img::{Glyph, Pixel};
+
+/// One resolved output cell: its glyph and the final colors of the two
+/// source pixels it stands for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cell {
+    pub ch: Glyph,
+    /// Charset indices `(row, column)` the glyph was picked from; the row is
+    /// `0` for 1-D charsets. Kept even when the glyph is replaced, e.g. by a
+    /// blank background cell or a mirrored counterpart.
+    pub level: (u16, u16),
+    pub top: Pixel,
+    pub bottom: Pixel,
+    /// Whether the top and bottom half matched the background color, see
+    /// [`PixtImg::with_background`](crate::img::PixtImg::with_background).
+    pub background: (bool, bool),
+}
+
+/// Rows of cells, top to bottom.
+pub type CellGrid = Vec<Vec<Cell>>;
+
+/// Axis to mirror a render across.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mirror {
+    /// Left and right swap.
+    Horizontal,
+    /// Top and bottom swap.
+    Vertical,
+    Both,
+}
+
+/// Glyphs that turn into each other when mirrored left to right.
+const HORIZONTAL_PAIRS: &[(char, char)] = &[
+    ('/', '\\'),
+    ('(', ')'),
+    ('[', ']'),
+    ('{', '}'),
+    ('<', '>'),
+    ('d', 'b'),
+    ('p', 'q'),
+    ('▌', '▐'),
+    ('▖', '▗'),
+    ('▘', '▝'),
+    ('▙', '▟'),
+    ('▛', '▜'),
+    ('▚', '▞'),
+];
+
+/// Glyphs that turn into each other when mirrored top to bottom.
+const VERTICAL_PAIRS: &[(char, char)] = &[
+    ('/', '\\'),
+    ('^', 'v'),
+    ('\'', ','),
+    ('`', '.'),
+    ('b', 'p'),
+    ('d', 'q'),
+    ('M', 'W'),
+    ('▀', '▄'),
+    ('▖', '▘'),
+    ('▗', '▝'),
+    ('▙', '▛'),
+    ('▟', '▜'),
+    ('▚', '▞'),
+];
+
+fn swap(pairs: &[(char, char)], ch: char) -> char {
+    pairs
+        .iter()
+        .find_map(|&(a, b)| match ch {
+            _ if ch == a => Some(b),
+            _ if ch == b => Some(a),
+            _ => None,
+        })
+        .unwrap_or(ch)
+}
+
+/// Exchanges two sets of braille dot bits.
+fn swap_dots(bits: u32, pairs: &[(u32, u32)]) -> u32 {
+    pairs.iter().fold(bits, |out, &(a, b)| {
+        let (has_a, has_b) = (bits & a != 0, bits & b != 0);
+        let out = out & !(a | b);
+        out | if has_a { b } else { 0 } | if has_b { a } else { 0 }
+    })
+}
+
+impl Mirror {
+    /// The glyph that looks like `ch` mirrored along this axis. Glyphs with
+    /// no counterpart (symmetric ones included) are returned unchanged.
+    pub fn glyph(&self, ch: char) -> char {
+        if let Some(bits) = (ch as u32).checked_sub(0x2800).filter(|v| *v <= 0xFF) {
+            // Braille dots 1-2-3-7 form the left column, 4-5-6-8 the right.
+            let horizontal = [(0x01, 0x08), (0x02, 0x10), (0x04, 0x20), (0x40, 0x80)];
+            let vertical = [(0x01, 0x40), (0x02, 0x04), (0x08, 0x80), (0x10, 0x20)];
+            let bits = match self {
+                Self::Horizontal => swap_dots(bits, &horizontal),
+                Self::Vertical => swap_dots(bits, &vertical),
+                Self::Both => swap_dots(swap_dots(bits, &horizontal), &vertical),
+            };
+            return char::from_u32(0x2800 + bits).unwrap_or(ch);
+        }
+        match self {
+            Self::Horizontal => swap(HORIZONTAL_PAIRS, ch),
+            Self::Vertical => swap(VERTICAL_PAIRS, ch),
+            Self::Both => swap(VERTICAL_PAIRS, swap(HORIZONTAL_PAIRS, ch)),
+        }
+    }
+
+    /// Mirrors `grid` in place, swapping direction-sensitive glyphs. Cell
+    /// colors stay attached to their glyph, so half-block art flips along.
+    pub fn apply(&self, grid: &mut CellGrid) {
+        if matches!(self, Self::Vertical | Self::Both) {
+            grid.reverse();
+        }
+        for row in grid.iter_mut() {
+            if matches!(self, Self::Horizontal | Self::Both) {
+                row.reverse();
+            }
+            for cell in row.iter_mut() {
+                if let Glyph::Char(ch) = cell.ch {
+                    cell.ch = Glyph::Char(self.glyph(ch));
+                }
+            }
+        }
+    }
+}
+
+/// Repeats `grid` `cols` times across and `rows` times down.
+pub fn tile(grid: &CellGrid, cols: u32, rows: u32) -> CellGrid {
+    let mut out = Vec::with_capacity(grid.len() * rows as usize);
+    for _ in 0..rows {
+        for row in grid {
+            out.push(
+                (0..cols)
+                    .flat_map(|_| row.iter().cloned())
+                    .collect::<Vec<_>>(),
+            );
+        }
+    }
+    out
+}
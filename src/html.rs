@@ -0,0 +1,514 @@
+//! Options and helpers that only concern HTML output.
+
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    io::{self, Cursor, Write},
+    time::Duration,
+};
+
+use image::{DynamicImage, ImageFormat, imageops::FilterType};
+
+use crate::img::ColorType;
+
+/// Longest edge the source image is shrunk to by default before it gets
+/// embedded, see [`HtmlOptions::source_max_edge`].
+pub const DEFAULT_SOURCE_MAX_EDGE: u32 = 512;
+
+/// Default font size of [`HtmlFit::Fixed`] pages, in pixels.
+pub const DEFAULT_FONT_SIZE: u32 = 10;
+
+/// Advance of one monospace character in `em`; most monospace fonts are
+/// close to 0.6.
+const CHAR_ADVANCE: f64 = 0.6;
+
+/// Lightest `font-weight` of [`HtmlOptions::weight_ramp`]; the others go up
+/// in steps of 100.
+const MIN_WEIGHT: u32 = 300;
+
+/// Number of distinct weights [`HtmlOptions::weight_ramp`] uses, 300 to 900.
+const WEIGHTS: u32 = 7;
+
+/// `font-weight` [`HtmlOptions::weight_ramp`] gives a cell of `intensity`:
+/// brighter cells are drawn heavier.
+pub fn weight(intensity: u8) -> u32 {
+    MIN_WEIGHT + 100 * (intensity as u32 * WEIGHTS / 256)
+}
+
+/// Name of the CSS class that sets `font-weight: weight`.
+pub fn weight_class(weight: u32) -> String {
+    format!("w{}", weight / 100)
+}
+
+/// ` class="…"` attribute naming [`weight_class`], ready to go into a tag
+/// without formatting it per cell.
+pub fn weight_class_attr(weight: u32) -> &'static str {
+    const ATTRS: [&str; WEIGHTS as usize] = [
+        " class=\"w3\"",
+        " class=\"w4\"",
+        " class=\"w5\"",
+        " class=\"w6\"",
+        " class=\"w7\"",
+        " class=\"w8\"",
+        " class=\"w9\"",
+    ];
+    ATTRS[((weight.saturating_sub(MIN_WEIGHT) / 100) as usize).min(ATTRS.len() - 1)]
+}
+
+/// How HTML output is sized, see [`HtmlOptions::fit`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HtmlFit {
+    /// A fixed [`HtmlOptions::font_size`], whatever the viewport.
+    #[default]
+    Fixed,
+    /// Scale the font so the art spans the viewport width.
+    Width,
+    /// Like `Width`, but also shrink it so the art fits the viewport height.
+    Screen,
+}
+
+/// CSS `font-size` that sizes a `cols`×`rows` character grid as `fit` asks,
+/// with lines `line_height` em apart; `Fixed` uses `fixed_px`.
+///
+/// `Width` makes `cols` characters span `100vw`, `Screen` takes the smaller
+/// of that and the size at which `rows` lines span `100vh`.
+pub fn fit_font_size(
+    fit: HtmlFit,
+    fixed_px: u32,
+    cols: u32,
+    rows: u32,
+    line_height: f64,
+) -> String {
+    let vw = 100.0 / (cols.max(1) as f64 * CHAR_ADVANCE);
+    let vh = 100.0 / (rows.max(1) as f64 * line_height);
+    match fit {
+        HtmlFit::Fixed => format!("{}px", fixed_px),
+        HtmlFit::Width => format!("{:.4}vw", vw),
+        HtmlFit::Screen => format!("min({:.4}vw, {:.4}vh)", vw, vh),
+    }
+}
+
+/// Options for HTML output, see
+/// [`PixtImg::with_html_options`](crate::img::PixtImg::with_html_options).
+///
+/// The defaults produce the classic page: light monospace text on a dark
+/// background at a fixed 10px.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HtmlOptions {
+    /// CSS color of uncolored text.
+    pub foreground: String,
+    /// CSS color of the page background.
+    pub background: String,
+    /// CSS `font-family` of the page.
+    pub font_family: String,
+    /// Font size in pixels for [`HtmlFit::Fixed`].
+    pub font_size: u32,
+    /// Extra CSS added to the end of the page's stylesheet.
+    pub extra_css: String,
+    /// Shows the source image next to the render, embedded as a data URI so
+    /// the page stays self-contained.
+    pub include_source: bool,
+    /// Longest edge in pixels of the embedded source; larger images are
+    /// downscaled to it to keep the page small.
+    pub source_max_edge: u32,
+    /// Gives every cell a `title` tooltip naming its position, colors and
+    /// charset level, for debugging how cells were picked. Roughly triples
+    /// the output size.
+    pub debug: bool,
+    /// How the page scales the art to the viewport.
+    pub fit: HtmlFit,
+    /// Makes the output smaller: neighbouring characters of the same color
+    /// share one element, colors used more than once become classes (see
+    /// [`ColorClasses`]), colors use the short `#RGB` form where possible
+    /// and the header loses its indentation. Also applies to SVG output, as
+    /// described in [`svg`](crate::svg).
+    pub minify: bool,
+    /// Draws brighter cells in heavier `font-weight`s, through a few shared
+    /// classes. Best with a variable-weight monospace font.
+    pub weight_ramp: bool,
+}
+
+impl Default for HtmlOptions {
+    fn default() -> Self {
+        Self {
+            foreground: String::from("#fff"),
+            background: String::from("#191919"),
+            font_family: String::from("monospace"),
+            font_size: DEFAULT_FONT_SIZE,
+            extra_css: String::new(),
+            include_source: false,
+            source_max_edge: DEFAULT_SOURCE_MAX_EDGE,
+            debug: false,
+            fit: HtmlFit::Fixed,
+            minify: false,
+            weight_ramp: false,
+        }
+    }
+}
+
+impl HtmlOptions {
+    /// Sets the CSS colors of uncolored text and of the page background.
+    pub fn with_colors(
+        mut self,
+        foreground: impl Into<String>,
+        background: impl Into<String>,
+    ) -> Self {
+        self.foreground = foreground.into();
+        self.background = background.into();
+        self
+    }
+    /// Sets the CSS `font-family` and the font size in pixels used by
+    /// [`HtmlFit::Fixed`].
+    pub fn with_font(mut self, family: impl Into<String>, size: u32) -> Self {
+        self.font_family = family.into();
+        self.font_size = size;
+        self
+    }
+    /// Appends `css` to the page's stylesheet.
+    pub fn with_extra_css(mut self, css: impl Into<String>) -> Self {
+        self.extra_css = css.into();
+        self
+    }
+    /// Embeds the source image next to the art, downscaled to at most
+    /// `max_edge` pixels.
+    pub fn with_source(mut self, max_edge: u32) -> Self {
+        self.include_source = true;
+        self.source_max_edge = max_edge;
+        self
+    }
+    /// Sets how the page scales the art to the viewport.
+    pub fn with_fit(mut self, fit: HtmlFit) -> Self {
+        self.fit = fit;
+        self
+    }
+    /// Turns per-cell debug tooltips on or off.
+    pub fn with_debug(mut self, debug: bool) -> Self {
+        self.debug = debug;
+        self
+    }
+    /// Turns minified output on or off.
+    pub fn with_minify(mut self, minify: bool) -> Self {
+        self.minify = minify;
+        self
+    }
+    /// Turns brightness-driven font weights on or off.
+    pub fn with_weight_ramp(mut self, weight_ramp: bool) -> Self {
+        self.weight_ramp = weight_ramp;
+        self
+    }
+}
+
+/// CSS `line-height` of rows colored as `color`. Colored rows stack two
+/// pixels per cell, so they sit half as far apart. Both are relative to the
+/// font size, so rows stay aligned however [`HtmlFit`] scales it.
+pub fn line_height(color: &ColorType) -> f64 {
+    match color {
+        ColorType::None => 1.2,
+        _ => 0.6,
+    }
+}
+
+/// Writes everything of an HTML page up to the opening `<pre>` the rows go
+/// into, for a `cols`×`rows` character grid colored as `color`.
+pub fn write_head(
+    options: &HtmlOptions,
+    color: &ColorType,
+    cols: u32,
+    rows: u32,
+    mut out: impl io::Write,
+) -> io::Result<()> {
+    let HtmlOptions {
+        foreground,
+        background,
+        font_family,
+        fit,
+        ..
+    } = options;
+    let margin = 0;
+    let padding = 0;
+    let line_height = line_height(color);
+    let font_size = fit_font_size(*fit, options.font_size, cols, rows, line_height);
+    // Pin the block to whole characters so a scaled font can't wrap rows
+    // early.
+    let width = match fit {
+        HtmlFit::Fixed => String::new(),
+        _ => format!("\n        width: {}ch;\n        white-space: pre;", cols),
+    };
+    let weights: String = match options.weight_ramp {
+        true => (0..WEIGHTS)
+            .map(|i| MIN_WEIGHT + 100 * i)
+            .map(|w| format!("    .{} {{ font-weight: {}; }}\n", weight_class(w), w))
+            .collect(),
+        false => String::new(),
+    };
+    let extra_css = match options.extra_css.trim() {
+        "" => String::new(),
+        css => format!("    {}\n", css),
+    };
+    let buf = format!(
+        "<!DOCTYPE html>
+<html lang=\"en\">
+  <head>
+    <meta charset=\"UTF-8\">
+    <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">
+    <style>
+    * {{
+        color: {foreground};
+        background-color: {background};
+        font-family: {font_family};
+    }}
+    pre {{
+        line-height: {line_height};
+        margin: {margin};
+        padding: {padding};
+        font-size: {font_size};{width}
+    }}
+{weights}{extra_css}    </style>
+  </head>
+  <body>
+    <pre>"
+    );
+    if options.minify {
+        let buf: String = buf.lines().map(str::trim).collect();
+        return out.write_all(buf.as_bytes());
+    }
+    out.write_all(buf.as_bytes())
+}
+
+/// Entity `ch` is written as in text or a quoted attribute value, if it
+/// needs one.
+fn entity(ch: char) -> Option<&'static str> {
+    match ch {
+        '&' => Some("&amp;"),
+        '<' => Some("&lt;"),
+        '>' => Some("&gt;"),
+        '"' => Some("&quot;"),
+        '\'' => Some("&#39;"),
+        _ => None,
+    }
+}
+
+/// Escapes `text` for use inside a double- or single-quoted attribute value.
+pub fn escape_attr(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match entity(ch) {
+            Some(entity) => out.push_str(entity),
+            None => out.push(ch),
+        }
+    }
+    out
+}
+
+/// Like [`escape_attr`], writing straight to `out` instead of allocating.
+pub fn write_escaped(mut out: impl io::Write, text: &str) -> io::Result<()> {
+    let mut start = 0;
+    for (i, ch) in text.char_indices() {
+        if let Some(entity) = entity(ch) {
+            out.write_all(&text.as_bytes()[start..i])?;
+            out.write_all(entity.as_bytes())?;
+            // Every escaped character is ASCII.
+            start = i + 1;
+        }
+    }
+    out.write_all(&text.as_bytes()[start..])
+}
+
+/// Formats `#RRGGBB` without allocating.
+#[inline(always)]
+pub fn css_hex(color: [u8; 3]) -> [u8; 7] {
+    const DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+    let mut out = [b'#'; 7];
+    for (i, v) in color.into_iter().enumerate() {
+        out[1 + i * 2] = DIGITS[(v >> 4) as usize];
+        out[2 + i * 2] = DIGITS[(v & 0xF) as usize];
+    }
+    out
+}
+
+/// Writes the `<style>` and opening tag of a block holding the frames of an
+/// animation, each shown for its entry of `delays` in turn. The frames go
+/// in one `<span>` each, stacked on top of one another; close the block
+/// with [`write_frames_end`] after the last. `loops` counts the passes,
+/// `0` repeating forever; a finished animation stays on its last frame.
+pub fn write_frames_start(
+    delays: &[Duration],
+    loops: u32,
+    mut out: impl io::Write,
+) -> io::Result<()> {
+    let total: f64 = delays.iter().map(Duration::as_secs_f64).sum();
+    let total = total.max(f64::EPSILON);
+    let count = match loops {
+        0 => "infinite".to_string(),
+        n => n.to_string(),
+    };
+    write!(
+        out,
+        "<style>.pixt-frames {{ display: inline-grid; }} \
+         .pixt-frames > span {{ grid-area: 1 / 1; visibility: hidden; \
+         animation: {total:.3}s step-end {count} both; }}"
+    )?;
+    let mut start = 0.0;
+    for (i, delay) in delays.iter().enumerate() {
+        let end = start + delay.as_secs_f64();
+        let (from, to) = (100.0 * start / total, 100.0 * end / total);
+        write!(
+            out,
+            " .pixt-frames > span:nth-child({}) {{ animation-name: pixt-frame-{}; }} @keyframes pixt-frame-{} {{ ",
+            i + 1,
+            i,
+            i
+        )?;
+        if i > 0 {
+            out.write_all(b"0% { visibility: hidden; } ")?;
+        }
+        write!(out, "{:.3}% {{ visibility: visible; }} ", from)?;
+        match i + 1 == delays.len() {
+            true => out.write_all(b"100% { visibility: visible; } }")?,
+            false => write!(out, "{:.3}% {{ visibility: hidden; }} }}", to)?,
+        }
+        start = end;
+    }
+    out.write_all(b"</style><span class=\"pixt-frames\">")
+}
+
+/// Closes what [`write_frames_start`] opened.
+pub fn write_frames_end(mut out: impl io::Write) -> io::Result<()> {
+    out.write_all(b"</span>")
+}
+
+/// Writes `color` as `#RRGGBB`.
+pub fn write_hex(mut out: impl io::Write, color: [u8; 3]) -> io::Result<()> {
+    out.write_all(&css_hex(color))
+}
+
+/// Writes `color` as `#RGB` when every channel repeats its hex digit,
+/// `#RRGGBB` otherwise.
+pub fn write_short_hex(mut out: impl io::Write, color: [u8; 3]) -> io::Result<()> {
+    match css_hex(color) {
+        [b'#', r1, r2, g1, g2, b1, b2] if r1 == r2 && g1 == g2 && b1 == b2 => {
+            out.write_all(&[b'#', r1, g1, b1])
+        }
+        hex => out.write_all(&hex),
+    }
+}
+
+/// CSS property a [`ColorClasses`] class sets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColorProperty {
+    Color,
+    BackgroundColor,
+}
+
+impl ColorProperty {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Color => "color",
+            Self::BackgroundColor => "background-color",
+        }
+    }
+    /// First letter of the classes setting this property.
+    fn prefix(self) -> char {
+        match self {
+            Self::Color => 'f',
+            Self::BackgroundColor => 'b',
+        }
+    }
+}
+
+/// Classes standing in for colors minified HTML repeats. A color goes
+/// inline the first time; from the second on it is a class named after it,
+/// whose rule [`write_pending`](Self::write_pending) adds to the page. CSS
+/// applies a stylesheet wherever it sits, so the rule may follow the first
+/// use, and as names follow the color, rules written for separate renders
+/// on one page agree.
+#[derive(Debug, Default)]
+pub struct ColorClasses {
+    /// Colors seen so far, with whether their rule was written.
+    seen: HashMap<(ColorProperty, [u8; 3]), bool>,
+    /// Rules not written yet.
+    pending: Vec<u8>,
+}
+
+impl ColorClasses {
+    /// Class setting `property` to `color` if the color was seen before,
+    /// `None` to write it inline.
+    pub fn class(&mut self, property: ColorProperty, color: [u8; 3]) -> io::Result<Option<String>> {
+        let defined = match self.seen.entry((property, color)) {
+            Entry::Vacant(entry) => {
+                entry.insert(false);
+                return Ok(None);
+            }
+            Entry::Occupied(mut entry) => entry.insert(true),
+        };
+        let name = color_class(property, color);
+        if !defined {
+            write!(self.pending, ".{}{{{}:", name, property.name())?;
+            write_short_hex(&mut self.pending, color)?;
+            self.pending.push(b'}');
+        }
+        Ok(Some(name))
+    }
+    /// Writes a `<style>` with the rules of the classes handed out since
+    /// the last call, if there are any.
+    pub fn write_pending(&mut self, mut out: impl io::Write) -> io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        out.write_all(b"<style>")?;
+        out.write_all(&self.pending)?;
+        self.pending.clear();
+        out.write_all(b"</style>")
+    }
+}
+
+/// Name of the class setting `property` to `color`: a letter for the
+/// property, then the color in four base64url digits.
+fn color_class(property: ColorProperty, [r, g, b]: [u8; 3]) -> String {
+    const DIGITS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+    let v = u32::from_be_bytes([0, r, g, b]);
+    let mut name = String::with_capacity(5);
+    name.push(property.prefix());
+    for shift in [18, 12, 6, 0] {
+        name.push(DIGITS[(v >> shift & 63) as usize] as char);
+    }
+    name
+}
+
+/// Encodes `bytes` as standard base64 with padding.
+pub fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = match *chunk {
+            [a, b, c] => u32::from_be_bytes([0, a, b, c]),
+            [a, b] => u32::from_be_bytes([0, a, b, 0]),
+            [a] => u32::from_be_bytes([0, a, 0, 0]),
+            _ => unreachable!("chunks(3) yields 1 to 3 bytes"),
+        };
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// `data:` URI of `img` as a PNG, downscaled first if an edge is longer
+/// than `max_edge`.
+pub fn png_data_uri(img: &DynamicImage, max_edge: u32) -> io::Result<String> {
+    let max_edge = max_edge.max(1);
+    let small;
+    let img = if img.width().max(img.height()) > max_edge {
+        small = img.resize(max_edge, max_edge, FilterType::Triangle);
+        &small
+    } else {
+        img
+    };
+    let mut png = Vec::new();
+    img.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(io::Error::other)?;
+    Ok(format!("data:image/png;base64,{}", base64(&png)))
+}
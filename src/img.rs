@@ -1,6 +1,6 @@
 #![allow(unused)]
 
-use std::{io, path::Path};
+use std::{borrow::Cow, collections::HashMap, fmt, io, path::Path, str::FromStr, sync::Arc};
 
 #[cfg(not(target_arch = "wasm32"))]
 use crossterm::{
@@ -8,123 +8,2017 @@ use crossterm::{
     style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
 };
 
-use image::{DynamicImage, GenericImageView, Pixel as _, Rgb};
+use image::{
+    DynamicImage, GenericImageView, GrayAlphaImage, GrayImage, Pixel as _, Rgb, RgbImage, Rgba,
+};
+
+#[cfg(feature = "font")]
+use crate::font::{GlyphDensity, density_thresholds};
+use crate::{
+    blocks::{
+        BITMAP_SIZE, BlockThreshold, SelectionStrategy, Subpixels, glyph_bitmap, lit_pixels, otsu,
+    },
+    charset::ink_density,
+    color::{
+        Attr, AttrRamp, Background, ColorDepth, FilterPreset, Lowlight, Palette, ansi16,
+        ansi16_code, ansi256,
+    },
+    dither::{Diffusion, DitherMode, dither},
+    edges::{EdgeMap, EdgeOptions},
+    grid::{Cell, CellGrid, Mirror, tile},
+    html::{
+        self, ColorClasses, ColorProperty, HtmlOptions, css_hex, escape_attr, png_data_uri,
+        write_escaped, write_hex,
+    },
+    mask::Mask,
+    metadata::Metadata,
+    metrics::Quality,
+    preprocess::{Luminance, flatten, flatten_pixel, intensity_plane, luma_row},
+    size::DEFAULT_CELL_ASPECT,
+    svg::{self, SvgMode},
+};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
 pub struct PixtImg {
     data: PixtData,
     out_type: OutputType,
+    filter: FilterPreset,
+    palette: Option<Palette>,
+    wide: bool,
+    trim_lines: Option<bool>,
+    fragment: bool,
+    background: Option<Background>,
+    highlights: Vec<CellRect>,
+    lowlight: Lowlight,
+    mirror: Option<Mirror>,
+    tile: Option<(u32, u32)>,
+    html: HtmlOptions,
+    metadata: Option<Metadata>,
+    attr_ramp: Option<AttrRamp>,
+    mask: Option<Mask>,
+    /// Alpha below which a pixel counts as transparent, and the character
+    /// of fully transparent cells.
+    transparent: Option<(u8, char)>,
+    svg_mode: SvgMode,
+    edges: Option<EdgeOptions>,
+    color_depth: ColorDepth,
+    ansi_palette: [[u8; 3]; 16],
+}
+
+/// A rectangle of output cells, `x`/`y` being its top-left cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellRect {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+impl CellRect {
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        (self.x..self.x.saturating_add(self.w)).contains(&x)
+            && (self.y..self.y.saturating_add(self.h)).contains(&y)
+    }
+    /// Whether the rectangle lies entirely within a `cols`×`rows` grid.
+    pub fn fits(&self, cols: u32, rows: u32) -> bool {
+        self.x.checked_add(self.w).is_some_and(|v| v <= cols)
+            && self.y.checked_add(self.h).is_some_and(|v| v <= rows)
+    }
 }
 
 impl PixtImg {
-    pub fn new<T: IntoPixtData>(data: T, out_type: OutputType) -> Self {
+    pub fn new<T: Into<PixtData>>(data: T, out_type: OutputType) -> Self {
+        let mut data = data.into();
+        data.set_alpha_bg(Some(out_type.backdrop()));
         Self {
-            data: data.into(),
+            data,
             out_type,
+            filter: FilterPreset::default(),
+            palette: None,
+            wide: false,
+            trim_lines: None,
+            fragment: false,
+            background: None,
+            highlights: Vec::new(),
+            lowlight: Lowlight::default(),
+            mirror: None,
+            tile: None,
+            html: HtmlOptions::default(),
+            metadata: None,
+            attr_ramp: None,
+            mask: None,
+            transparent: None,
+            svg_mode: SvgMode::Text,
+            edges: None,
+            color_depth: ColorDepth::default(),
+            ansi_palette: Palette::VGA,
+        }
+    }
+    /// Applies a color filter preset to every cell before it is emitted.
+    pub fn with_filter(mut self, filter: FilterPreset) -> Self {
+        if filter.inverts_intensity() != self.filter.inverts_intensity() {
+            self.data.set_invert(!self.data.invert);
+        }
+        self.filter = filter;
+        self
+    }
+    /// Reverses the brightness ramp, so bright areas pick the sparse end of
+    /// the charset and white renders blank; for light terminal backgrounds.
+    /// By default bright areas pick the dense end, which suits dark ones. Combines with the
+    /// inversion of [`FilterPreset::Negative`], which it cancels out.
+    pub fn with_invert(mut self, invert: bool) -> Self {
+        self.data
+            .set_invert(invert != self.filter.inverts_intensity());
+        self
+    }
+    /// Snaps every emitted color to the nearest entry of `palette`.
+    pub fn with_palette(mut self, palette: Palette) -> Self {
+        self.palette = Some(palette);
+        self
+    }
+    /// Emits every cell twice so each rendered pixel is roughly square.
+    ///
+    /// The image should be resized to half the target column count.
+    pub fn with_wide(mut self, wide: bool) -> Self {
+        self.wide = wide;
+        self
+    }
+    /// Drops trailing spaces on every row and trailing blank rows.
+    ///
+    /// Defaults to on for [`OutputType::Text`] and off otherwise. It has no
+    /// effect when the color type paints cell backgrounds.
+    pub fn with_trim_lines(mut self, trim: bool) -> Self {
+        self.trim_lines = Some(trim);
+        self
+    }
+    /// Leaves out the document header and footer (e.g. the HTML page around
+    /// the art), so several renders can share one document.
+    pub fn with_fragment(mut self, fragment: bool) -> Self {
+        self.fragment = fragment;
+        self
+    }
+    /// Emits cells matching `background` as unstyled spaces.
+    ///
+    /// Cells are compared by their average color, except when the two pixels
+    /// carry separate foreground and background colors: there a matching
+    /// background-half only drops the background escape.
+    pub fn with_background(mut self, background: Background) -> Self {
+        self.background = Some(background);
+        self
+    }
+    /// Draws dark cells dim and bright cells bold, as `ramp` decides from
+    /// their brightness. Terminal output uses the text attributes, HTML
+    /// `opacity` and `font-weight`; other outputs ignore it.
+    pub fn with_attr_ramp(mut self, ramp: AttrRamp) -> Self {
+        self.attr_ramp = Some(ramp);
+        self
+    }
+    /// Keeps full color only inside `regions`; every other cell is drawn
+    /// through `lowlight`. An empty list leaves all cells untouched.
+    pub fn with_highlights(mut self, regions: Vec<CellRect>, lowlight: Lowlight) -> Self {
+        self.highlights = regions;
+        self.lowlight = lowlight;
+        self
+    }
+    /// Draws the cells `mask` hides as its fill character, uncolored.
+    ///
+    /// The mask covers the image the cells are made from pixel for pixel,
+    /// so it has to be resized (or block-averaged) to the same size first.
+    pub fn with_mask(mut self, mask: Mask) -> Self {
+        self.mask = Some(mask);
+        self
+    }
+    /// Draws cells on strong edges with directional glyphs instead of the
+    /// charset, see [`EdgeOptions`]. Edges are found on the same intensities
+    /// the charset is picked from.
+    pub fn with_edges(mut self, edges: EdgeOptions) -> Self {
+        self.edges = Some(edges);
+        self
+    }
+    /// Leaves pixels with an alpha below `threshold` out: cells where both
+    /// pixels are transparent are drawn as an uncolored `fill`, so whatever
+    /// is behind the art shows through. When only one is, the other picks
+    /// the character and colors alone; color types drawing the halves
+    /// separately leave the transparent half uncolored.
+    pub fn with_transparent(mut self, threshold: u8, fill: char) -> Self {
+        self.transparent = Some((threshold, fill));
+        self
+    }
+    /// Writes terminal colors as `depth`, see [`ColorDepth`]. Other output
+    /// types keep full RGB.
+    pub fn with_color_depth(mut self, depth: ColorDepth) -> Self {
+        self.color_depth = depth;
+        self
+    }
+    /// Colors [`ColorDepth::Ansi16`] matches against, in ANSI order (see
+    /// [`ansi16`]); [`Palette::VGA`] by default. Set them to the colors
+    /// the terminal actually shows for the best match.
+    pub fn with_ansi_palette(mut self, palette: [[u8; 3]; 16]) -> Self {
+        self.ansi_palette = palette;
+        self
+    }
+    pub fn output_type(&self) -> &OutputType {
+        &self.out_type
+    }
+    pub fn color_depth(&self) -> ColorDepth {
+        self.color_depth
+    }
+    pub fn ansi_palette(&self) -> [[u8; 3]; 16] {
+        self.ansi_palette
+    }
+    /// Size of the output grid `(columns, rows)` in cells for `img`.
+    pub fn grid_size(&self, img: &DynamicImage) -> (u32, u32) {
+        self.data.grid_size(img)
+    }
+    /// Source pixels `(columns, rows)` every cell covers, to size images
+    /// for a given grid.
+    pub fn cell_pixels(&self) -> (u32, u32) {
+        self.data.cell_pixels()
+    }
+    /// Like [`grid_size`](Self::grid_size), after mirroring and tiling.
+    pub fn output_size(&self, img: &DynamicImage) -> (u32, u32) {
+        self.output_size_for(img.width(), img.height())
+    }
+    /// Like [`output_size`](Self::output_size), for an image of
+    /// `width`×`height` pixels.
+    pub fn output_size_for(&self, width: u32, height: u32) -> (u32, u32) {
+        let (cols, rows) = self.data.grid_dims(width, height);
+        let (c, r) = self.tile.unwrap_or((1, 1));
+        (cols * c, rows * r)
+    }
+    /// Approximate size in bytes of what [`print`](Self::print) writes for
+    /// `img`, to preallocate buffers or as a `Content-Length` hint.
+    ///
+    /// Markup sizes are exact for HTML; the length of ANSI escapes follows
+    /// the average number of digits of the source channels. Trimmed spaces
+    /// and blank background cells make the real output somewhat smaller.
+    pub fn estimated_output_size(&self, img: &DynamicImage) -> usize {
+        self.estimate_size(img.width(), img.height(), || channel_digits(img))
+    }
+    /// Like [`estimated_output_size`](Self::estimated_output_size), for an
+    /// image of `width`×`height` pixels that hasn't been decoded yet. Every
+    /// color channel is assumed to take three digits, so colored estimates
+    /// err on the large side.
+    pub fn estimated_output_size_for(&self, width: u32, height: u32) -> usize {
+        self.estimate_size(width, height, || 3.0)
+    }
+    fn estimate_size(&self, width: u32, height: u32, digits: impl FnOnce() -> f64) -> usize {
+        let (cols, rows) = self.output_size_for(width, height);
+        let cells = cols as usize * if self.wide { 2 } else { 1 };
+        let (cell_markup, line_break) = self.out_type.markup_size();
+        let escapes = match self.out_type {
+            OutputType::Term(
+                ColorType::AvgFgOnly
+                | ColorType::AvgBgOnly
+                | ColorType::TopFgOnly
+                | ColorType::BottomFgOnly,
+            ) => 1.0,
+            OutputType::Term(ColorType::FgTopBgDown | ColorType::BgTopFgDown) => 2.0,
+            _ => 0.0,
+        };
+        // `\x1b[38;2;R;G;Bm`: ten fixed bytes and three numbers, or
+        // `\x1b[38;5;Nm` with one number of up to three digits.
+        let escape = match (escapes > 0.0, self.color_depth) {
+            (false, _) => 0.0,
+            (true, ColorDepth::TrueColor) => 10.0 + 3.0 * digits(),
+            (true, ColorDepth::Ansi256) => 10.0,
+            (true, ColorDepth::Ansi16) => 5.0,
+        };
+        let cell = cell_markup as f64 + escapes * escape + self.data.glyph_size();
+        let line = (cells as f64 * cell).ceil() as usize + line_break;
+        let document = match (&self.out_type, self.fragment) {
+            (_, true) => 0,
+            (out_type, false) => {
+                let mut buf = Vec::new();
+                let _ = out_type.write_header(width, height, &mut buf);
+                let _ = out_type.write_footer(&mut buf);
+                buf.len()
+            }
+        };
+        rows as usize * line + document
+    }
+    /// Mirrors the rendered grid, swapping direction-sensitive glyphs.
+    pub fn with_mirror(mut self, mirror: Mirror) -> Self {
+        self.mirror = Some(mirror);
+        self
+    }
+    /// Repeats the rendered grid `cols` times across and `rows` times down.
+    pub fn with_tile(mut self, cols: u32, rows: u32) -> Self {
+        self.tile = Some((cols, rows));
+        self
+    }
+    /// Records how the output was made in a comment before the header, see
+    /// [`Metadata::write_comment`].
+    pub fn with_metadata(mut self, metadata: Metadata) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+    /// Sets the options that only apply to HTML output.
+    pub fn with_html_options(mut self, options: HtmlOptions) -> Self {
+        self.html = options;
+        self
+    }
+    /// Sets how SVG output draws the cells.
+    pub fn with_svg_mode(mut self, mode: SvgMode) -> Self {
+        self.svg_mode = mode;
+        self
+    }
+    /// Composites transparent pixels over `color`, see
+    /// [`PixtData::set_alpha_bg`]. Defaults to the backdrop of the output
+    /// type, see [`OutputType::backdrop`].
+    pub fn with_alpha_bg(mut self, color: Option<[u8; 3]>) -> Self {
+        self.data.set_alpha_bg(color);
+        self
+    }
+    /// Applies `gamma` to intensities before the character lookup, see
+    /// [`PixtData::set_gamma`].
+    pub fn with_gamma(mut self, gamma: f32) -> Self {
+        self.data.set_gamma(gamma);
+        self
+    }
+    /// Sets how pixel colors are weighted into the intensity characters
+    /// are picked by, see [`Luminance`].
+    pub fn with_luminance(mut self, luminance: Luminance) -> Self {
+        self.data.set_luminance(luminance);
+        self
+    }
+    /// Picks how characters are chosen for cells, see [`SelectionStrategy`].
+    pub fn with_selection(mut self, selection: SelectionStrategy) -> Self {
+        self.data.set_selection(selection);
+        self
+    }
+    /// Whether any charset character has an inked shape
+    /// [`SelectionStrategy::Structural`] can match.
+    pub fn has_shapes(&self) -> bool {
+        self.data.has_shapes()
+    }
+    /// Picks the lit pixels of sextant and braille dot cells, see
+    /// [`BlockThreshold`].
+    pub fn with_block_threshold(mut self, threshold: BlockThreshold) -> Self {
+        self.data.set_block_threshold(threshold);
+        self
+    }
+    /// Spaces the intensities picking each character by its coverage in a
+    /// font, see [`PixtData::set_font_densities`].
+    #[cfg(feature = "font")]
+    pub fn with_font_densities(
+        mut self,
+        font_data: &[u8],
+        cache: &mut GlyphDensity,
+    ) -> io::Result<Self> {
+        self.data.set_font_densities(font_data, cache)?;
+        Ok(self)
+    }
+    /// Dithers the character selection, see [`DitherMode`].
+    pub fn with_dither(mut self, dither: DitherMode) -> Self {
+        self.data.set_dither(dither);
+        self
+    }
+    /// Measures how well the charset approximates `img` (MSE and SSIM of
+    /// the reconstructed cell intensities against the source).
+    pub fn quality(&self, img: &DynamicImage) -> Quality {
+        let (source, render, width, height) = self.data.reconstruct(img);
+        Quality::compare(&source, &render, width, height)
+    }
+    pub fn print(&self, img: &DynamicImage, out: impl io::Write) -> io::Result<()> {
+        self.render(img, None, out)
+    }
+    /// Like [`print`](Self::print), but picks characters from the intensity
+    /// plane `luma` (the same size as `img`) instead of deriving them from the
+    /// pixel colors, which stay untouched. Used by intensity-only
+    /// preprocessing such as CLAHE.
+    pub fn print_with_luma(
+        &self,
+        img: &DynamicImage,
+        luma: &GrayImage,
+        out: impl io::Write,
+    ) -> io::Result<()> {
+        self.render(img, Some(luma), out)
+    }
+    /// Resolves every output cell of `img` (colors mapped, transforms
+    /// applied) without printing anything.
+    pub fn cells(&self, img: &DynamicImage) -> CellGrid {
+        self.resolve(img, None)
+    }
+    /// Like [`cells`](Self::cells), picking characters from the intensity
+    /// plane `luma` as [`print_with_luma`](Self::print_with_luma) does.
+    pub fn cells_with_luma(&self, img: &DynamicImage, luma: &GrayImage) -> CellGrid {
+        self.resolve(img, Some(luma))
+    }
+    /// Writes the rows of an already resolved `grid` as `out_type`, without
+    /// document header or footer. Lets one render be replayed into several
+    /// output formats; `out_type` should use the color type the cells were
+    /// resolved with.
+    pub fn write_cells(
+        &self,
+        grid: &CellGrid,
+        out_type: &OutputType,
+        mut out: impl io::Write,
+    ) -> io::Result<()> {
+        let mut rows = RowWriter::with_output_type(self, out_type);
+        for cells in grid.iter() {
+            rows.write(cells, &mut out)?;
+        }
+        Ok(())
+    }
+    /// With [`HtmlOptions::include_source`] and HTML output, opens a flex
+    /// row holding `source` as an embedded image, followed by the art. Every
+    /// other case writes nothing. Close it with
+    /// [`write_source_end`](Self::write_source_end) after the rows.
+    ///
+    /// [`print`](Self::print) does this by itself, with the rendered image
+    /// as the source.
+    pub fn write_source_start(
+        &self,
+        source: &DynamicImage,
+        out_type: &OutputType,
+        mut out: impl io::Write,
+    ) -> io::Result<()> {
+        if !self.html.include_source || !matches!(out_type, OutputType::Html(_)) {
+            return Ok(());
+        }
+        write!(
+            out,
+            "<span style=\"display:flex;gap:2ch;align-items:flex-start\"><img src=\"{}\" alt=\"source image\"><span>",
+            png_data_uri(source, self.html.source_max_edge)?
+        )
+    }
+    /// Closes what [`write_source_start`](Self::write_source_start) opened.
+    pub fn write_source_end(
+        &self,
+        out_type: &OutputType,
+        mut out: impl io::Write,
+    ) -> io::Result<()> {
+        if !self.html.include_source || !matches!(out_type, OutputType::Html(_)) {
+            return Ok(());
         }
+        out.write_all(b"</span></span>")
     }
-    pub fn print(&self, img: &DynamicImage, mut out: impl io::Write) -> io::Result<()> {
-        self.out_type
-            .write_header(img.width(), img.height(), &mut out)?;
-        for line in self.data.chars(img) {
-            for p in line {
-                let print = self.out_type.print_pixel();
-                print(&mut out, p)?;
+    /// Writes the document header of `out_type` for a grid of `cols`×`rows`
+    /// cells, sized as [`HtmlOptions::fit`] asks; see
+    /// [`OutputType::write_header_with`]. Preceded by the
+    /// [metadata](Self::with_metadata) comment if there is one.
+    pub fn write_header(
+        &self,
+        out_type: &OutputType,
+        (cols, rows): (u32, u32),
+        mut out: impl io::Write,
+    ) -> io::Result<()> {
+        if let Some(metadata) = &self.metadata {
+            metadata.write_comment(out_type, &mut out)?;
+        }
+        out_type.write_header_with(cols * self.cell_width(), rows, &self.html, out)
+    }
+    /// Writes one line per entry of the charset's
+    /// [`legend`](PixtData::legend): the character, then the intensities
+    /// (`0` dark to `255` bright) that pick it. Colored outputs add a swatch
+    /// of the gray in the middle of the range and draw the character in it.
+    ///
+    /// SVG output has no room for the labels and is rejected.
+    pub fn write_legend(&self, out_type: &OutputType, mut out: impl io::Write) -> io::Result<()> {
+        if let OutputType::Svg(_) = out_type {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "legends can't be written as SVG",
+            ));
+        }
+        let colored =
+            !matches!(out_type, OutputType::Text(_)) && *out_type.color_type() != ColorType::None;
+        let swatch_type = out_type.clone().color(ColorType::AvgBgOnly);
+        let gray = |(lo, hi): (u8, u8)| ((lo as u16 + hi as u16) / 2) as u8;
+        let pixel = |y: u32, v: u8| Pixel {
+            x: 0,
+            y,
+            color: (v, v, v),
+            alpha: u8::MAX,
+        };
+        for entry in self.data.legend() {
+            let (top, bottom) = (
+                gray(entry.range),
+                entry.row_range.map_or(gray(entry.range), gray),
+            );
+            let cell = Cell {
+                ch: entry.ch,
+                level: entry.level,
+                top: pixel(0, top),
+                bottom: pixel(1, bottom),
+                background: (false, false),
+            };
+            let swatch = Cell {
+                ch: Glyph::Char(' '),
+                ..cell.clone()
+            };
+            self.write_run(&[cell], out_type, &mut out)?;
+            out.write_all(b"  ")?;
+            if colored {
+                self.write_run(&[swatch.clone(), swatch], &swatch_type, &mut out)?;
+                out.write_all(b"  ")?;
             }
-            let println = self.out_type.print_line();
+            let (lo, hi) = entry.range;
+            match entry.row_range {
+                None => write!(out, "{:>3}-{}", lo, hi)?,
+                Some((row_lo, row_hi)) => write!(
+                    out,
+                    "top {:>3}-{:<3} bottom {:>3}-{}",
+                    lo, hi, row_lo, row_hi
+                )?,
+            }
+            let println = out_type.print_line();
             println(&mut out)?;
         }
         Ok(())
     }
+    /// Writes `cells` as `out_type` without trimming or a line break, ending
+    /// with the colors reset. For drawing parts of a grid at the cursor, e.g.
+    /// in animations.
+    pub fn write_run(
+        &self,
+        cells: &[Cell],
+        out_type: &OutputType,
+        mut out: impl io::Write,
+    ) -> io::Result<()> {
+        RowWriter::with_output_type(self, out_type).write_run(cells, &mut out)?;
+        #[cfg(not(target_arch = "wasm32"))]
+        if matches!(out_type, OutputType::Term(color) if *color != ColorType::None) {
+            execute!(out, ResetColor)?;
+        }
+        Ok(())
+    }
+    /// Terminal columns one cell takes up: the charset's
+    /// [`glyph_width`](PixtData::glyph_width), doubled with
+    /// [`with_wide`](Self::with_wide).
+    pub fn cell_width(&self) -> u32 {
+        self.data.glyph_width() * if self.wide { 2 } else { 1 }
+    }
+    /// Resolves only the cells inside `region` of the output grid, as
+    /// [`cells`](Self::cells) would for the full grid.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if `region` doesn't fit the
+    /// grid (see [`output_size`](Self::output_size)).
+    pub fn cells_region(&self, img: &DynamicImage, region: CellRect) -> io::Result<CellGrid> {
+        let (cols, rows) = self.output_size(img);
+        if !region.fits(cols, rows) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "region {},{},{}x{} does not fit the {}x{} cell grid",
+                    region.x, region.y, region.w, region.h, cols, rows
+                ),
+            ));
+        }
+        if self.mirror.is_none() && self.tile.is_none() {
+            return Ok(self.resolve_in(img, None, region));
+        }
+        // Transforms move cells around, so cut the region out of the result.
+        let (x, w) = (region.x as usize, region.w as usize);
+        Ok(self
+            .resolve(img, None)
+            .into_iter()
+            .skip(region.y as usize)
+            .take(region.h as usize)
+            .map(|row| row[x..x + w].to_vec())
+            .collect())
+    }
+    /// Like [`print`](Self::print), but only the cells inside `region` of the
+    /// output grid; see [`cells_region`](Self::cells_region).
+    pub fn print_region(
+        &self,
+        img: &DynamicImage,
+        region: CellRect,
+        mut out: impl io::Write,
+    ) -> io::Result<()> {
+        let grid = self.cells_region(img, region)?;
+        if !self.fragment {
+            self.write_header(&self.out_type, (region.w, region.h), &mut out)?;
+        }
+        let mut rows = RowWriter::new(self);
+        for cells in grid.iter() {
+            rows.write(cells, &mut out)?;
+        }
+        if !self.fragment {
+            self.out_type.write_footer(&mut out)?;
+        }
+        Ok(())
+    }
+    fn resolve(&self, img: &DynamicImage, luma: Option<&GrayImage>) -> CellGrid {
+        let (w, h) = self.data.grid_size(img);
+        self.resolve_in(img, luma, CellRect { x: 0, y: 0, w, h })
+    }
+    /// Resolves the cells of `region` (in untransformed grid coordinates),
+    /// then applies mirroring and tiling.
+    fn resolve_in(
+        &self,
+        img: &DynamicImage,
+        luma: Option<&GrayImage>,
+        region: CellRect,
+    ) -> CellGrid {
+        let mut grid = Vec::new();
+        self.resolve_into(img, luma, region, &mut grid);
+        grid
+    }
+    /// Like [`resolve_in`](Self::resolve_in), overwriting `grid` and reusing
+    /// its rows.
+    fn resolve_into(
+        &self,
+        img: &DynamicImage,
+        luma: Option<&GrayImage>,
+        region: CellRect,
+        grid: &mut CellGrid,
+    ) {
+        let mut nearest: HashMap<[u8; 3], [u8; 3]> = HashMap::new();
+        let mut map_color = |color: (u8, u8, u8)| -> (u8, u8, u8) {
+            let color = self.filter.apply(color.into());
+            match &self.palette {
+                Some(palette) => (*nearest
+                    .entry(color)
+                    .or_insert_with(|| palette.nearest(color)))
+                .into(),
+                None => color.into(),
+            }
+        };
+        let split = matches!(
+            self.out_type.color_type(),
+            ColorType::FgTopBgDown | ColorType::BgTopFgDown
+        );
+        // Which halves of a cell (top, bottom) are background.
+        let is_background = |p1: Pixel, p2: Pixel| -> (bool, bool) {
+            let Some(bg) = &self.background else {
+                return (false, false);
+            };
+            // A cell colored from one pixel is background when that pixel is.
+            let one = match self.out_type.color_type() {
+                ColorType::TopFgOnly => Some(p1),
+                ColorType::BottomFgOnly => Some(p2),
+                _ => None,
+            };
+            if split {
+                (bg.matches(p1.into()), bg.matches(p2.into()))
+            } else if let Some(p) = one {
+                let one = bg.matches(p.into());
+                (one, one)
+            } else {
+                let avg = bg.matches(avg_color(p1.into(), p2.into()));
+                (avg, avg)
+            }
+        };
+        let is_clear = |p: Pixel| self.transparent.is_some_and(|(t, _)| p.alpha < t);
+        let edges = self.edges.map(|options| {
+            let map = match luma {
+                Some(luma) => EdgeMap::new(luma),
+                None => EdgeMap::new(&self.data.intensity_plane(img)),
+            };
+            (options, map)
+        });
+        let mut len = 0;
+        let lines = self.data.levels_in(img, luma, region);
+        for (row, line) in (region.y..).zip(lines) {
+            let cells = (region.x..)
+                .zip(line)
+                .map(|(col, (mut level, mut p1, mut p2))| {
+                    let clear = (is_clear(p1), is_clear(p2));
+                    // The opaque pixel stands in for its transparent neighbour;
+                    // block glyphs already separate the two.
+                    let opaque = match clear {
+                        _ if self.data.by_shape() => None,
+                        (true, false) => Some(p2),
+                        (false, true) => Some(p1),
+                        _ => None,
+                    };
+                    if let Some(p) = opaque {
+                        let (row, col) = self.data.levels(Rgb(p.into()), Rgb(p.into()), None);
+                        level = (row as u16, col as u16);
+                        if !split {
+                            (p1.color, p2.color) = (p.color, p.color);
+                        }
+                    }
+                    let edge = edges
+                        .as_ref()
+                        .and_then(|(options, map)| map.glyph(options, (p1.x, p1.y), (p2.x, p2.y)));
+                    let masked = self
+                        .mask
+                        .as_ref()
+                        .filter(|mask| mask.hides((p1.x, p1.y), (p2.x, p2.y)));
+                    let bg = match (masked, clear) {
+                        (Some(_), _) | (None, (true, true)) => (true, true),
+                        (None, (clear1, clear2)) if split => {
+                            let (bg1, bg2) = is_background(p1, p2);
+                            (bg1 || clear1, bg2 || clear2)
+                        }
+                        (None, _) => is_background(p1, p2),
+                    };
+                    p1.color = map_color(p1.color);
+                    p2.color = map_color(p2.color);
+                    if !self.highlights.is_empty()
+                        && !self.highlights.iter().any(|r| r.contains(col, row))
+                    {
+                        p1.color = self.lowlight.apply(p1.into()).into();
+                        p2.color = self.lowlight.apply(p2.into()).into();
+                    }
+                    Cell {
+                        ch: match (masked, bg, edge) {
+                            (Some(mask), _, _) => Glyph::Char(mask.fill),
+                            (None, _, _) if clear == (true, true) => {
+                                Glyph::Char(self.transparent.map_or(' ', |(_, fill)| fill))
+                            }
+                            (None, (true, true), _) => Glyph::Char(' '),
+                            (None, _, Some(edge)) => Glyph::Char(edge),
+                            (None, _, None) => {
+                                self.data.data[level.0 as usize][level.1 as usize].clone()
+                            }
+                        },
+                        level,
+                        top: p1,
+                        bottom: p2,
+                        background: bg,
+                    }
+                });
+            match grid.get_mut(len) {
+                Some(v) => {
+                    v.clear();
+                    v.extend(cells);
+                }
+                None => grid.push(cells.collect()),
+            }
+            len += 1;
+        }
+        grid.truncate(len);
+        if let Some(mirror) = &self.mirror {
+            mirror.apply(grid);
+        }
+        if let Some((cols, rows)) = self.tile {
+            *grid = tile(grid, cols, rows);
+        }
+    }
+    fn render(
+        &self,
+        img: &DynamicImage,
+        luma: Option<&GrayImage>,
+        mut out: impl io::Write,
+    ) -> io::Result<()> {
+        if !self.fragment {
+            self.write_header(&self.out_type, self.output_size(img), &mut out)?;
+        }
+        self.write_source_start(img, &self.out_type, &mut out)?;
+        let mut rows = RowWriter::new(self);
+        for cells in &self.resolve(img, luma) {
+            // Rows reset their colors at the end only; don't leave a
+            // terminal stained by a row cut short.
+            rows.write(cells, &mut out).inspect_err(|_| {
+                #[cfg(not(target_arch = "wasm32"))]
+                if matches!(&self.out_type, OutputType::Term(color) if *color != ColorType::None) {
+                    let _ = execute!(out, ResetColor);
+                }
+            })?;
+        }
+        self.write_source_end(&self.out_type, &mut out)?;
+        if !self.fragment {
+            self.out_type.write_footer(&mut out)?;
+        }
+        Ok(())
+    }
+    /// Like [`print`](Self::print), reusing the buffers in `scratch`.
+    ///
+    /// The cell grid, the output bytes and the formatted color codes are
+    /// kept between calls and only grow when a frame needs more, so
+    /// repeated renders of same-sized frames (animations, live previews)
+    /// don't reallocate them. `out` receives the whole output in one write;
+    /// it also stays available from [`RenderScratch::output`].
+    pub fn render_into(
+        &self,
+        img: &DynamicImage,
+        scratch: &mut RenderScratch,
+        mut out: impl io::Write,
+    ) -> io::Result<()> {
+        let RenderScratch {
+            grid,
+            codes,
+            out: buf,
+        } = scratch;
+        buf.clear();
+        buf.reserve(self.estimated_output_size(img));
+        let (w, h) = self.data.grid_size(img);
+        self.resolve_into(img, None, CellRect { x: 0, y: 0, w, h }, grid);
+        if !self.fragment {
+            self.write_header(&self.out_type, self.output_size(img), &mut *buf)?;
+        }
+        self.write_source_start(img, &self.out_type, &mut *buf)?;
+        let mut rows = RowWriter::new(self);
+        rows.codes = std::mem::take(codes).with_depth(self.color_depth, self.ansi_palette);
+        for cells in grid.iter() {
+            rows.write(cells, &mut *buf)?;
+        }
+        *codes = rows.codes;
+        self.write_source_end(&self.out_type, &mut *buf)?;
+        if !self.fragment {
+            self.out_type.write_footer(buf)?;
+        }
+        out.write_all(buf)
+    }
+    /// Like [`print`](Self::print), but writes to an async writer. Each row
+    /// is awaited on its own and the task yields to the executor in between,
+    /// so large renders don't starve other tasks.
+    #[cfg(feature = "tokio")]
+    pub async fn print_async(
+        &self,
+        img: &DynamicImage,
+        mut out: impl tokio::io::AsyncWrite + Unpin,
+    ) -> io::Result<()> {
+        use tokio::io::AsyncWriteExt;
+
+        let mut buf = Vec::new();
+        if !self.fragment {
+            self.write_header(&self.out_type, self.output_size(img), &mut buf)?;
+        }
+        self.write_source_start(img, &self.out_type, &mut buf)?;
+        let mut rows = RowWriter::new(self);
+        for cells in &self.resolve(img, None) {
+            rows.write(cells, &mut buf)?;
+            out.write_all(&buf).await?;
+            buf.clear();
+            tokio::task::yield_now().await;
+        }
+        self.write_source_end(&self.out_type, &mut buf)?;
+        if !self.fragment {
+            self.out_type.write_footer(&mut buf)?;
+        }
+        out.write_all(&buf).await?;
+        out.flush().await
+    }
+}
+
+/// Buffers kept between [`PixtImg::render_into`] calls.
+#[derive(Debug, Default)]
+pub struct RenderScratch {
+    grid: CellGrid,
+    codes: ColorCodes,
+    out: Vec<u8>,
+}
+
+impl RenderScratch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Output of the last [`PixtImg::render_into`] call.
+    pub fn output(&self) -> &[u8] {
+        &self.out
+    }
+}
+
+/// Writes resolved rows one at a time, keeping the state (trailing blank
+/// rows held back for trimming) that spans rows.
+struct RowWriter<'a> {
+    pixt_img: &'a PixtImg,
+    out_type: &'a OutputType,
+    trim: bool,
+    blank_rows: usize,
+    codes: ColorCodes,
+    /// Index of the next row, blank rows held back included.
+    row: u32,
+    /// Whether cells get [`HtmlOptions::debug`] tooltips.
+    debug: bool,
+    /// Whether runs of same-colored cells share a span, see
+    /// [`HtmlOptions::minify`].
+    minify: bool,
+    /// Whether a merged span of minified HTML is still open.
+    span_open: bool,
+    /// Style of the open merged span.
+    open_style: SpanStyle,
+    /// Classes repeated colors of minified HTML share.
+    classes: ColorClasses,
+    /// Whether cells get [`HtmlOptions::weight_ramp`] classes.
+    weight_ramp: bool,
+    /// Picks the text attribute of every cell, if any.
+    attr_ramp: Option<AttrRamp>,
+    /// Text attribute the terminal currently draws with.
+    attr: Attr,
+}
+
+impl<'a> RowWriter<'a> {
+    fn new(pixt_img: &'a PixtImg) -> Self {
+        Self::with_output_type(pixt_img, &pixt_img.out_type)
+    }
+    fn with_output_type(pixt_img: &'a PixtImg, out_type: &'a OutputType) -> Self {
+        Self {
+            pixt_img,
+            out_type,
+            trim: pixt_img
+                .trim_lines
+                .unwrap_or(matches!(out_type, OutputType::Text(_)))
+                && !out_type.paints_background(),
+            blank_rows: 0,
+            codes: ColorCodes::default().with_depth(pixt_img.color_depth, pixt_img.ansi_palette),
+            row: 0,
+            debug: pixt_img.html.debug && matches!(out_type, OutputType::Html(_)),
+            minify: pixt_img.html.minify
+                && !pixt_img.html.debug
+                && matches!(out_type, OutputType::Html(_)),
+            span_open: false,
+            open_style: SpanStyle::default(),
+            classes: ColorClasses::default(),
+            weight_ramp: pixt_img.html.weight_ramp && matches!(out_type, OutputType::Html(_)),
+            attr_ramp: pixt_img
+                .attr_ramp
+                .filter(|_| matches!(out_type, OutputType::Term(_) | OutputType::Html(_))),
+            attr: Attr::Normal,
+        }
+    }
+    fn write(&mut self, mut cells: &[Cell], mut out: impl io::Write) -> io::Result<()> {
+        let out_type = self.out_type;
+        if self.trim {
+            let end = cells.iter().rposition(|cell| !cell.ch.is_blank());
+            cells = &cells[..end.map_or(0, |i| i + 1)];
+            // Hold back blank rows until we know they aren't trailing.
+            if cells.is_empty() {
+                self.blank_rows += 1;
+                self.row += 1;
+                return Ok(());
+            }
+            for _ in 0..std::mem::take(&mut self.blank_rows) {
+                let println = out_type.print_line();
+                println(&mut out)?;
+            }
+        }
+        self.write_run(cells, &mut out)?;
+        self.row += 1;
+        let println = out_type.print_line();
+        println(&mut out)
+    }
+    /// Writes `cells` without trimming or a line break.
+    fn write_run(&mut self, cells: &[Cell], mut out: impl io::Write) -> io::Result<()> {
+        let out_type = self.out_type;
+        let data = &self.pixt_img.data;
+        let glyph_width = data.glyph_width();
+        let repeat = if self.pixt_img.wide { 2 } else { 1 };
+        if let (OutputType::Svg(color), SvgMode::Rects) = (out_type, self.pixt_img.svg_mode) {
+            let mut cols = Vec::with_capacity(cells.len() * self.pixt_img.cell_width() as usize);
+            for cell in cells {
+                let halves = svg_rect_colors(cell, color);
+                for _ in 0..self.pixt_img.cell_width() {
+                    cols.push(halves);
+                }
+            }
+            return svg::write_rects(self.row, color, &cols, self.pixt_img.html.minify, out);
+        }
+        if let OutputType::Svg(color) = out_type {
+            let mut cols = Vec::with_capacity(cells.len() * self.pixt_img.cell_width() as usize);
+            for cell in cells {
+                let (ch, fg, bg) = svg_cell(cell, color);
+                for _ in 0..repeat {
+                    if *ch == Glyph::Char(' ') {
+                        // Blank cells still fill every column of the cell.
+                        for _ in 0..glyph_width {
+                            cols.push((Some(ch), fg, bg));
+                        }
+                        continue;
+                    }
+                    // The glyph covers the whole cell; its other columns
+                    // only carry the colors.
+                    cols.push((Some(ch), fg, bg));
+                    for _ in 1..glyph_width {
+                        cols.push((None, fg, bg));
+                    }
+                }
+            }
+            return svg::write_row(self.row, color, &mut cols, self.pixt_img.html.minify, out);
+        }
+        for (x, cell) in (0..).zip(cells) {
+            let &Cell {
+                ref ch,
+                level,
+                top: p1,
+                bottom: p2,
+                background: bg,
+            } = cell;
+            if self.debug {
+                let level = match self.pixt_img.data.data.len() {
+                    1 => level.1.to_string(),
+                    _ => format!("{},{}", level.0, level.1),
+                };
+                let title = format!(
+                    "(x={},y={}) top={} bottom={} level={}",
+                    x,
+                    self.row,
+                    rgb_to_css_hex(p1),
+                    rgb_to_css_hex(p2),
+                    level
+                );
+                write!(out, "<span title=\"{}\">", escape_attr(&title))?;
+            }
+            let attr = self.attr_ramp.map_or(Attr::Normal, |ramp| {
+                ramp.attr(
+                    self.pixt_img
+                        .data
+                        .luminance
+                        .luma(avg_color(p1.into(), p2.into())),
+                )
+            });
+            for _ in 0..repeat {
+                match (bg, out_type.color_type()) {
+                    ((true, true), _) => {
+                        self.close_span(&mut out)?;
+                        // Masked cells may carry a fill character.
+                        out_type.print_plain(&mut out, ch.encode_utf8(&mut [0; 4]))?;
+                        for _ in 1..glyph_width {
+                            out_type.print_blank(&mut out)?
+                        }
+                        // Colored terminal blanks reset all attributes.
+                        if matches!(out_type, OutputType::Term(color) if *color != ColorType::None)
+                        {
+                            self.attr = Attr::Normal;
+                        }
+                    }
+                    ((true, false), ColorType::BgTopFgDown) => {
+                        self.close_span(&mut out)?;
+                        self.print_fg_only(&mut out, ch, p2, attr)?
+                    }
+                    ((false, true), ColorType::FgTopBgDown) => {
+                        self.close_span(&mut out)?;
+                        self.print_fg_only(&mut out, ch, p1, attr)?
+                    }
+                    _ => self.print_pixel(out_type, &mut out, ch, p1, p2, attr)?,
+                }
+            }
+            if self.debug {
+                out.write_all(b"</span>")?;
+            }
+        }
+        self.close_span(&mut out)?;
+        if self.minify {
+            self.classes.write_pending(&mut out)?;
+        }
+        self.set_attr(&mut out, Attr::Normal)
+    }
+    /// Switches the terminal to `attr` unless it already draws with it.
+    fn set_attr(&mut self, mut out: impl io::Write, attr: Attr) -> io::Result<()> {
+        if attr == self.attr || !matches!(self.out_type, OutputType::Term(_)) {
+            return Ok(());
+        }
+        // Bold and dim share one reset, and some terminals show both if
+        // one is set over the other.
+        if self.attr != Attr::Normal {
+            out.write_all(b"\x1b[22m")?;
+        }
+        match attr {
+            Attr::Normal => {}
+            Attr::Dim => out.write_all(b"\x1b[2m")?,
+            Attr::Bold => out.write_all(b"\x1b[1m")?,
+        }
+        self.attr = attr;
+        Ok(())
+    }
+    /// Closes the span merged cells share in minified HTML, if one is open.
+    fn close_span(&mut self, mut out: impl io::Write) -> io::Result<()> {
+        if std::mem::take(&mut self.span_open) {
+            out.write_all(b"</span>")?;
+        }
+        Ok(())
+    }
+    /// Writes one styled HTML cell, continuing the open span if it has the
+    /// same attributes.
+    fn print_minified(
+        &mut self,
+        mut out: impl io::Write,
+        glyph: &[u8],
+        style: SpanStyle,
+    ) -> io::Result<()> {
+        if !self.span_open || self.open_style != style {
+            self.close_span(&mut out)?;
+            self.open_span(&mut out, style)?;
+        }
+        out.write_all(glyph)
+    }
+    /// Opens a merged span of minified HTML, with its colors as classes
+    /// where they repeat.
+    fn open_span(&mut self, mut out: impl io::Write, style: SpanStyle) -> io::Result<()> {
+        let fg = match style.fg {
+            Some(fg) => Some((fg, self.classes.class(ColorProperty::Color, fg)?)),
+            None => None,
+        };
+        let bg = match style.bg {
+            Some(bg) => Some((bg, self.classes.class(ColorProperty::BackgroundColor, bg)?)),
+            None => None,
+        };
+        out.write_all(b"<span")?;
+        let mut sep = " class=\"";
+        if let Some(weight) = style.weight {
+            write!(out, "{}{}", sep, html::weight_class(weight))?;
+            sep = " ";
+        }
+        for class in [&fg, &bg]
+            .into_iter()
+            .flatten()
+            .filter_map(|v| v.1.as_ref())
+        {
+            write!(out, "{}{}", sep, class)?;
+            sep = " ";
+        }
+        if sep == " " {
+            out.write_all(b"\"")?;
+        }
+        let mut sep = " style=\"";
+        for (property, color) in [
+            (ColorProperty::Color, fg),
+            (ColorProperty::BackgroundColor, bg),
+        ] {
+            if let Some((color, None)) = color {
+                write!(out, "{}{}:", sep, property.name())?;
+                html::write_short_hex(&mut out, color)?;
+                sep = ";";
+            }
+        }
+        if !style.attr_css.is_empty() {
+            write!(out, "{}{}", sep, style.attr_css.trim_end_matches(';'))?;
+            sep = ";";
+        }
+        if sep == ";" {
+            out.write_all(b"\"")?;
+        }
+        out.write_all(b">")?;
+        self.open_style = style;
+        self.span_open = true;
+        Ok(())
+    }
+    /// Writes one cell like [`OutputType::print_fg_only`].
+    fn print_fg_only(
+        &mut self,
+        mut out: impl io::Write,
+        ch: &Glyph,
+        fg: Pixel,
+        attr: Attr,
+    ) -> io::Result<()> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let OutputType::Term(_) = self.out_type {
+            execute!(out, ResetColor)?;
+            self.attr = Attr::Normal;
+        }
+        let fg_only = self.out_type.clone().color(ColorType::AvgFgOnly);
+        self.print_pixel(&fg_only, out, ch, fg, fg, attr)
+    }
+    /// Writes one cell as `out_type` exactly like
+    /// [`OutputType::print_pixel`], but with the color codes taken from the
+    /// per-render cache and drawn with `attr`.
+    fn print_pixel(
+        &mut self,
+        out_type: &OutputType,
+        mut out: impl io::Write,
+        ch: &Glyph,
+        p1: Pixel,
+        p2: Pixel,
+        attr: Attr,
+    ) -> io::Result<()> {
+        let (c1, c2): ([u8; 3], [u8; 3]) = (p1.into(), p2.into());
+        let mut buf = [0; 4];
+        let text = ch.encode_utf8(&mut buf);
+        let mut escaped = Vec::new();
+        let glyph = match (out_type, text) {
+            // Keeps background-only cells from collapsing outside `<pre>`.
+            (OutputType::Html(ColorType::AvgBgOnly), " ") => b"&nbsp;",
+            (OutputType::Html(_), glyph) if glyph.contains(['&', '<', '>', '"', '\'']) => {
+                html::write_escaped(&mut escaped, glyph)?;
+                escaped.as_slice()
+            }
+            (_, glyph) => glyph.as_bytes(),
+        };
+        self.set_attr(&mut out, attr)?;
+        let attr_css = match attr {
+            Attr::Normal => "",
+            Attr::Dim => "opacity:0.5;",
+            Attr::Bold => "font-weight:bold;",
+        };
+        let weight = self
+            .weight_ramp
+            .then(|| html::weight(self.pixt_img.data.luminance.luma(avg_color(c1, c2))));
+        let class = weight.map_or("", html::weight_class_attr);
+        if self.minify {
+            let (fg, bg) = out_type.color_type().colors(c1, c2);
+            // A space shows no foreground or attribute, so it can join any
+            // span.
+            let blank = ch.is_blank();
+            let style = SpanStyle {
+                weight: weight.filter(|_| !blank),
+                fg: fg.filter(|_| !blank),
+                bg,
+                attr_css: if blank { "" } else { attr_css },
+            };
+            if style != SpanStyle::default() {
+                return self.print_minified(out, glyph, style);
+            }
+            if self.span_open {
+                return out.write_all(glyph);
+            }
+        }
+        let codes = &mut self.codes;
+        match out_type {
+            #[cfg(not(target_arch = "wasm32"))]
+            OutputType::Term(ColorType::AvgFgOnly) => {
+                out.write_all(codes.get(Code::Fg, avg_color(c1, c2)))?;
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            OutputType::Term(ColorType::TopFgOnly) => {
+                out.write_all(codes.get(Code::Fg, c1))?;
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            OutputType::Term(ColorType::BottomFgOnly) => {
+                out.write_all(codes.get(Code::Fg, c2))?;
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            OutputType::Term(ColorType::AvgBgOnly) => {
+                out.write_all(codes.get(Code::Bg, avg_color(c1, c2)))?;
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            OutputType::Term(ColorType::FgTopBgDown) => {
+                out.write_all(codes.get(Code::Bg, c2))?;
+                out.write_all(codes.get(Code::Fg, c1))?;
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            OutputType::Term(ColorType::BgTopFgDown) => {
+                out.write_all(codes.get(Code::Bg, c1))?;
+                out.write_all(codes.get(Code::Fg, c2))?;
+            }
+            OutputType::Html(
+                color @ (ColorType::AvgFgOnly | ColorType::TopFgOnly | ColorType::BottomFgOnly),
+            ) => {
+                let (Some(fg), _) = color.colors(c1, c2) else {
+                    unreachable!("foreground-only colors set a foreground")
+                };
+                write!(out, "<span{} style=\"color: ", class)?;
+                out.write_all(codes.get(Code::Hex, fg))?;
+                write!(out, ";{}\">", attr_css)?;
+            }
+            OutputType::Html(ColorType::AvgBgOnly) => {
+                write!(out, "<span{} style=\"background-color:", class)?;
+                out.write_all(codes.get(Code::Hex, avg_color(c1, c2)))?;
+                write!(out, ";{}\">", attr_css)?;
+            }
+            OutputType::Html(ColorType::FgTopBgDown | ColorType::BgTopFgDown) => {
+                let (fg, bg) = match out_type.color_type() {
+                    ColorType::FgTopBgDown => (c1, c2),
+                    _ => (c2, c1),
+                };
+                write!(out, "<span{} style=\"color:", class)?;
+                out.write_all(codes.get(Code::Hex, fg))?;
+                out.write_all(b";background-color:")?;
+                out.write_all(codes.get(Code::Hex, bg))?;
+                write!(out, ";{}\">", attr_css)?;
+            }
+            OutputType::Html(ColorType::None) if !attr_css.is_empty() => {
+                write!(out, "<span{} style=\"{}\">", class, attr_css)?;
+            }
+            OutputType::Html(ColorType::None) if !class.is_empty() => {
+                write!(out, "<span{}>", class)?;
+            }
+            OutputType::Text(_)
+            | OutputType::Term(ColorType::None)
+            | OutputType::Html(ColorType::None) => {}
+            _ => {
+                let print = out_type.print_pixel();
+                return print(out, (text, p1, p2));
+            }
+        }
+        out.write_all(glyph)?;
+        let span = match out_type {
+            OutputType::Html(ColorType::None) => !attr_css.is_empty() || !class.is_empty(),
+            OutputType::Html(_) => true,
+            _ => false,
+        };
+        if span {
+            out.write_all(b"</span>")?;
+        }
+        Ok(())
+    }
+}
+
+/// What a merged span of minified HTML sets.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct SpanStyle {
+    /// `font-weight` of [`HtmlOptions::weight_ramp`].
+    weight: Option<u32>,
+    fg: Option<[u8; 3]>,
+    bg: Option<[u8; 3]>,
+    /// Declarations of the text attribute.
+    attr_css: &'static str,
+}
+
+/// Kind of a cached color code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Code {
+    /// ANSI escape setting the foreground color.
+    Fg,
+    /// ANSI escape setting the background color.
+    Bg,
+    /// CSS hex color, `#RRGGBB`.
+    Hex,
+}
+
+/// Color codes formatted during one render. Photographs, and quantized
+/// images even more so, repeat colors a lot, so every code is formatted
+/// once instead of per cell.
+#[derive(Debug, Default)]
+struct ColorCodes {
+    codes: HashMap<(Code, [u8; 3]), Box<[u8]>>,
+    depth: ColorDepth,
+    ansi_palette: [[u8; 3]; 16],
+}
+
+impl ColorCodes {
+    /// Entries kept before the cache starts over, bounding its memory.
+    const CAPACITY: usize = 4096;
+
+    /// Formats ANSI escapes for `depth`, matching [`ColorDepth::Ansi16`]
+    /// against `ansi_palette`, and drops codes cached for others.
+    fn with_depth(mut self, depth: ColorDepth, ansi_palette: [[u8; 3]; 16]) -> Self {
+        if (depth, ansi_palette) != (self.depth, self.ansi_palette) {
+            self.codes.clear();
+            self.depth = depth;
+            self.ansi_palette = ansi_palette;
+        }
+        self
+    }
+
+    fn get(&mut self, code: Code, color: [u8; 3]) -> &[u8] {
+        if self.codes.len() >= Self::CAPACITY && !self.codes.contains_key(&(code, color)) {
+            self.codes.clear();
+        }
+        let (depth, ansi_palette) = (self.depth, &self.ansi_palette);
+        self.codes.entry((code, color)).or_insert_with(|| {
+            let [r, g, b] = color;
+            match (code, depth) {
+                (Code::Fg, ColorDepth::TrueColor) => {
+                    format!("\x1b[38;2;{};{};{}m", r, g, b).into_bytes().into()
+                }
+                (Code::Bg, ColorDepth::TrueColor) => {
+                    format!("\x1b[48;2;{};{};{}m", r, g, b).into_bytes().into()
+                }
+                (Code::Fg, ColorDepth::Ansi256) => {
+                    format!("\x1b[38;5;{}m", ansi256(color)).into_bytes().into()
+                }
+                (Code::Bg, ColorDepth::Ansi256) => {
+                    format!("\x1b[48;5;{}m", ansi256(color)).into_bytes().into()
+                }
+                (Code::Fg | Code::Bg, ColorDepth::Ansi16) => {
+                    let index = ansi16(color, ansi_palette);
+                    format!("\x1b[{}m", ansi16_code(index, code == Code::Bg))
+                        .into_bytes()
+                        .into()
+                }
+                (Code::Hex, _) => css_hex(color).into(),
+            }
+        })
+    }
 }
 
 pub struct PixtData {
-    data: Vec<Vec<char>>,
+    data: Vec<Vec<Glyph>>,
+    invert: bool,
+    thresholds: Option<Vec<u8>>,
+    dither: DitherMode,
+    luminance: Luminance,
+    gamma: f32,
+    alpha_bg: Option<[u8; 3]>,
+    /// Cells pick block glyphs by pattern instead of by intensity, see
+    /// [`IntoPixtData::subpixels`].
+    subpixels: Option<Subpixels>,
+    block_threshold: BlockThreshold,
+    selection: SelectionStrategy,
+    /// Level and [`glyph_bitmap`] of every charset character that has one,
+    /// for [`SelectionStrategy::Structural`].
+    shapes: Vec<((u16, u16), u32)>,
+    /// Column (1-D: character) index for every raw intensity, with inversion
+    /// and thresholds already applied.
+    col_lut: [u16; 256],
+    /// Row index for every raw intensity; all zero for 1-D charsets.
+    row_lut: [u16; 256],
 }
 
 impl PixtData {
     pub fn new<T: IntoPixtData>(data: T) -> Self {
-        Self {
-            data: data.into_pixt_data(),
+        let subpixels = data.subpixels();
+        let mut v = Self {
+            data: glyphs(data.into_pixt_data()),
+            invert: false,
+            thresholds: None,
+            dither: DitherMode::None,
+            luminance: Luminance::default(),
+            gamma: 1.0,
+            alpha_bg: None,
+            subpixels,
+            block_threshold: BlockThreshold::default(),
+            selection: SelectionStrategy::default(),
+            shapes: Vec::new(),
+            col_lut: [0; 256],
+            row_lut: [0; 256],
+        };
+        v.rebuild_luts();
+        v
+    }
+    /// Uses explicit, ascending intensity boundaries instead of evenly spaced
+    /// levels: intensity below `thresholds[i]` selects character `i`.
+    ///
+    /// Only applies along charset axes with exactly `thresholds.len() + 1`
+    /// characters; other axes keep uniform spacing.
+    pub fn set_thresholds(&mut self, thresholds: Vec<u8>) -> &mut Self {
+        self.thresholds = Some(thresholds);
+        self.rebuild_luts();
+        self
+    }
+    /// Maps intensity `v` like `255 - v`, so bright pixels pick the dark end
+    /// of the charset.
+    pub fn set_invert(&mut self, invert: bool) -> &mut Self {
+        self.invert = invert;
+        self.rebuild_luts();
+        self
+    }
+    /// Dithers the character selection, see [`DitherMode`].
+    pub fn set_dither(&mut self, dither: DitherMode) -> &mut Self {
+        self.dither = dither;
+        self
+    }
+    /// Picks how characters are chosen for cells, see [`SelectionStrategy`].
+    pub fn set_selection(&mut self, selection: SelectionStrategy) -> &mut Self {
+        self.selection = selection;
+        self
+    }
+    /// Whether any charset character has an inked shape
+    /// [`SelectionStrategy::Structural`] can match.
+    pub fn has_shapes(&self) -> bool {
+        self.shapes.iter().any(|(_, shape)| *shape != 0)
+    }
+    /// Whether cells are picked from blocks of pixels by their pattern
+    /// instead of by intensity.
+    fn by_shape(&self) -> bool {
+        self.subpixels.is_some() || self.selection == SelectionStrategy::Structural
+    }
+    /// Picks the lit pixels of [`Subpixels`] cells that threshold them, see
+    /// [`BlockThreshold`].
+    pub fn set_block_threshold(&mut self, threshold: BlockThreshold) -> &mut Self {
+        self.block_threshold = threshold;
+        self
+    }
+    /// Raises intensities to `1 / gamma` before the character lookup, so
+    /// values above 1 brighten the midtones of sRGB images. Values that
+    /// aren't positive and finite reset it to 1, which changes nothing.
+    pub fn set_gamma(&mut self, gamma: f32) -> &mut Self {
+        self.gamma = match gamma {
+            g if g.is_finite() && g > 0.0 => g,
+            _ => 1.0,
+        };
+        self.rebuild_luts();
+        self
+    }
+    /// Composites transparent pixels over `color` before characters and
+    /// colors are picked, instead of using the RGB they store. `None` keeps
+    /// the stored RGB. Either way the alpha is handed on in
+    /// [`Pixel::alpha`].
+    pub fn set_alpha_bg(&mut self, color: Option<[u8; 3]>) -> &mut Self {
+        self.alpha_bg = color;
+        self
+    }
+    /// Sets how pixel colors are weighted into the intensity characters
+    /// are picked by.
+    pub fn set_luminance(&mut self, luminance: Luminance) -> &mut Self {
+        self.luminance = luminance;
+        self
+    }
+    /// Reorders every row of the charset from the least to the most inked
+    /// character by [`ink_density`], keeping the order of equally dense
+    /// ones, so hand-written ramps need not be sorted. Grapheme clusters
+    /// count as their densest character. Meant for 1-D charsets: rows of
+    /// 2-D ones are sorted on their own.
+    pub fn sorted_by_density(mut self) -> Self {
+        let density = |glyph: &Glyph| {
+            glyph
+                .encode_utf8(&mut [0; 4])
+                .chars()
+                .map(ink_density)
+                .fold(0.0, f32::max)
+        };
+        for row in self.data.iter_mut() {
+            row.sort_by(|a, b| density(a).total_cmp(&density(b)));
         }
+        self.rebuild_luts();
+        self
+    }
+    /// Spaces the intensities picking each character by its ink coverage
+    /// in the font in `font_data` instead of evenly, measured through
+    /// `cache`; see [`density_thresholds`]. Replaces any
+    /// [`set_thresholds`](Self::set_thresholds). Grapheme clusters count as
+    /// their densest character. Meant for 1-D charsets: the first row is
+    /// measured. Nothing changes when the font has fewer than two of the
+    /// characters.
+    #[cfg(feature = "font")]
+    pub fn set_font_densities(
+        &mut self,
+        font_data: &[u8],
+        cache: &mut GlyphDensity,
+    ) -> io::Result<&mut Self> {
+        let glyphs: Vec<String> = self.data.first().map_or(Vec::new(), |row| {
+            row.iter()
+                .map(|glyph| glyph.encode_utf8(&mut [0; 4]).to_owned())
+                .collect()
+        });
+        let densities = glyphs
+            .iter()
+            .map(|glyph| {
+                let chars: Vec<char> = glyph.chars().collect();
+                let densities = cache.densities(font_data, &chars)?;
+                Ok(densities.into_iter().flatten().reduce(f32::max))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        if let Some(thresholds) = density_thresholds(&densities) {
+            self.set_thresholds(thresholds);
+        }
+        Ok(self)
     }
     pub fn set_pixel_data<T: IntoPixtData>(&mut self, data: T) -> &mut Self {
-        self.data = data.into_pixt_data();
+        self.subpixels = data.subpixels();
+        self.data = glyphs(data.into_pixt_data());
+        self.rebuild_luts();
         self
     }
-    /// Returns a character representing the brightness levels of two pixels.
-    fn get_char(&self, Rgb([tr, tg, tb]): Rgb<u8>, Rgb([br, bg, bb]): Rgb<u8>) -> char {
+    /// Like [`set_pixel_data`](Self::set_pixel_data), with a charset of
+    /// grapheme clusters.
+    pub fn set_graphemes(&mut self, data: Graphemes) -> &mut Self {
+        let width = data
+            .0
+            .iter()
+            .flatten()
+            .map(|v| v.width())
+            .max()
+            .unwrap_or(1)
+            .max(1);
+        self.data = data
+            .0
+            .into_iter()
+            .map(|row| {
+                row.into_iter()
+                    .map(|glyph| {
+                        // Pad narrow glyphs so every cell takes the same columns.
+                        Glyph::new(&format!(
+                            "{}{}",
+                            glyph,
+                            " ".repeat(width - glyph.width().min(width))
+                        ))
+                    })
+                    .collect()
+            })
+            .collect();
+        self.rebuild_luts();
+        self
+    }
+    /// Terminal columns every glyph of the charset takes up: 2 for emoji
+    /// and other wide characters, else 1. Narrower glyphs are padded with
+    /// spaces to match.
+    pub fn glyph_width(&self) -> u32 {
+        self.data
+            .iter()
+            .flatten()
+            .map(Glyph::width)
+            .max()
+            .unwrap_or(1)
+            .max(1) as u32
+    }
+    /// Returns the glyph representing the brightness levels of two pixels.
+    fn get_char(&self, t: Rgb<u8>, b: Rgb<u8>) -> &Glyph {
+        let (row, col) = self.levels(t, b, None);
+        &self.data[row][col]
+    }
+    /// Returns the charset indices `(row, column)` selected for a cell.
+    ///
+    /// For 1-D charsets the column follows the average brightness of both
+    /// pixels. For 2-D charsets the top pixel selects the column and the
+    /// bottom pixel the row.
+    fn levels(
+        &self,
+        Rgb([tr, tg, tb]): Rgb<u8>,
+        Rgb([br, bg, bb]): Rgb<u8>,
+        threshold: Option<f32>,
+    ) -> (usize, usize) {
+        let top_intensity = self.luminance.luma([tr, tg, tb]);
+        let bottom_intensity = self.luminance.luma([br, bg, bb]);
+
+        self.levels_for(top_intensity, bottom_intensity, threshold)
+    }
+    /// Like `levels`, from already computed top and bottom intensities: the
+    /// top one indexes along a row of the charset (the column), the bottom
+    /// one across the rows.
+    ///
+    /// `threshold` is the dither threshold of the cell, if any.
+    fn levels_for(
+        &self,
+        top_intensity: u8,
+        bottom_intensity: u8,
+        threshold: Option<f32>,
+    ) -> (usize, usize) {
         let rows = self.data.len(); // Number of character rows
         let cols = self.data[0].len(); // Number of character columns
 
-        // Compute grayscale intensity for both pixels using an average of RGB values
-        let top_intensity = ((tr as u16 + tg as u16 + tb as u16) / 3) as u8;
-        let bottom_intensity = ((br as u16 + bg as u16 + bb as u16) / 3) as u8;
+        if rows == 1 {
+            let avg = ((top_intensity as u16 + bottom_intensity as u16) / 2) as u8;
+            return (
+                0,
+                self.col_lut[dither(avg, cols, threshold) as usize] as usize,
+            );
+        }
 
-        // Map intensity to row and column indices, ensuring they stay within bounds
-        let row_index = std::cmp::min((top_intensity as usize * cols) / u8::MAX as usize, cols - 1);
-        let col_index = std::cmp::min(
-            (bottom_intensity as usize * rows) / u8::MAX as usize,
-            rows - 1,
-        );
+        // Each axis goes through its own table, scaled to its own length, so
+        // non-square charsets stay within bounds.
+        let col_index = self.col_lut[dither(top_intensity, cols, threshold) as usize] as usize;
+        let row_index = self.row_lut[dither(bottom_intensity, rows, threshold) as usize] as usize;
 
-        self.data[col_index][row_index]
+        (row_index, col_index)
     }
-    /// Returns a character representing the average brightness of two pixels.
-    fn get_char_single_raw(&self, Rgb([tr, tg, tb]): Rgb<u8>, Rgb([br, bg, bb]): Rgb<u8>) -> char {
+    /// Like `levels_for`, for [`DitherMode::FloydSteinberg`]: adds the
+    /// error carried into column `i` of `errors` before the lookup and
+    /// spreads what the picked levels are off by to the cells ahead.
+    fn levels_diffused(
+        &self,
+        top_intensity: u8,
+        bottom_intensity: u8,
+        errors: &mut Diffusion,
+        i: usize,
+    ) -> (usize, usize) {
+        let rows = self.data.len();
         let cols = self.data[0].len();
-
-        let top_intensity = (tr as u16 + tg as u16 + tb as u16) / 3;
-        let bottom_intensity = (br as u16 + bg as u16 + bb as u16) / 3;
-
-        let avg_intensity = (top_intensity + bottom_intensity) / 2;
-
-        let col_index = std::cmp::min((avg_intensity as usize * cols) / u8::MAX as usize, cols - 1);
-
-        self.data[0][col_index]
+        let [col_err, row_err] = errors.carried(i);
+        // Errors are kept after `adjust`, where levels are evenly spaced.
+        let adjusted = |v: u8| self.adjust(v) as f32;
+        if rows == 1 {
+            let avg = ((top_intensity as u16 + bottom_intensity as u16) / 2) as u8;
+            let (col, err) = self.quantize(adjusted(avg) + col_err, cols);
+            errors.spread(i, [err, 0.0]);
+            return (0, col);
+        }
+        let (col, col_err) = self.quantize(adjusted(top_intensity) + col_err, cols);
+        let (row, row_err) = self.quantize(adjusted(bottom_intensity) + row_err, rows);
+        errors.spread(i, [col_err, row_err]);
+        (row, col)
+    }
+    /// Level of the adjusted intensity `v` along an axis of `n` levels, and
+    /// how far `v` is from the intensity that level stands for.
+    fn quantize(&self, v: f32, n: usize) -> (usize, f32) {
+        let v = v.clamp(0.0, u8::MAX as f32);
+        let level = self.level(v.round() as u8, n);
+        (level, v - self.level_intensity(level, n) as f32)
     }
+    /// Every character some intensity picks, with the intensities picking
+    /// it, from dark to bright. Thresholds, gamma and inversion are taken into
+    /// account, dithering isn't.
+    ///
+    /// 2-D charsets get an entry for every combination of a top and a
+    /// bottom range.
+    pub fn legend(&self) -> Vec<LegendEntry> {
+        // Consecutive intensities mapping to the same level, as
+        // `(level, first, last)`.
+        let runs = |lut: &[u16; 256]| {
+            let mut runs: Vec<(u16, u8, u8)> = Vec::new();
+            for v in 0..=u8::MAX {
+                match runs.last_mut() {
+                    Some((level, _, last)) if *level == lut[v as usize] => *last = v,
+                    _ => runs.push((lut[v as usize], v, v)),
+                }
+            }
+            runs
+        };
+        let entry = |row: u16, col: u16, range, row_range| LegendEntry {
+            ch: self.data[row as usize][col as usize].clone(),
+            level: (row, col),
+            range,
+            row_range,
+        };
+        if self.data.len() == 1 {
+            return runs(&self.col_lut)
+                .into_iter()
+                .map(|(col, lo, hi)| entry(0, col, (lo, hi), None))
+                .collect();
+        }
+        let cols = runs(&self.col_lut);
+        runs(&self.row_lut)
+            .into_iter()
+            .flat_map(|(row, row_lo, row_hi)| {
+                cols.iter()
+                    .map(move |&(col, lo, hi)| entry(row, col, (lo, hi), Some((row_lo, row_hi))))
+            })
+            .collect()
+    }
+    /// The intensity a level stands for: the middle of the intensity range
+    /// that maps to level `index` of an `n`-level axis.
+    pub fn level_intensity(&self, index: usize, n: usize) -> f64 {
+        match &self.thresholds {
+            Some(t) if t.len() + 1 == n => {
+                let lo = if index == 0 { 0.0 } else { t[index - 1] as f64 };
+                let hi = t.get(index).map_or(256.0, |v| *v as f64);
+                (lo + hi - 1.0) / 2.0
+            }
+            _ => ((index as f64 + 0.5) * u8::MAX as f64 / n as f64).min(u8::MAX as f64),
+        }
+    }
+    /// Rebuilds a cell-resolution grayscale approximation of the render and
+    /// the matching (adjusted) source intensities.
+    ///
+    /// Returns `(source, render, width, height)` with planes in row-major order.
+    pub fn reconstruct(&self, img: &DynamicImage) -> (Vec<f64>, Vec<f64>, usize, usize) {
+        let rows = self.data.len();
+        let cols = self.data[0].len();
+        let mut source = Vec::new();
+        let mut render = Vec::new();
+        let mut height = 0;
+        for line in self.levels_with_luma(img, None) {
+            height += 1;
+            for ((row, col), p1, p2) in line {
+                let (t, b) = (Rgb(p1.into()), Rgb(p2.into()));
+                let intensity = |Rgb(c): Rgb<u8>| self.luminance.luma(c) as u16;
+                let avg = self.adjust(((intensity(t) + intensity(b)) / 2) as u8);
+                source.push(avg as f64);
+                let (row, col) = (row as usize, col as usize);
+                render.push(if rows == 1 {
+                    self.level_intensity(col, cols)
+                } else {
+                    (self.level_intensity(col, cols) + self.level_intensity(row, rows)) / 2.0
+                });
+            }
+        }
+        let width = source.len().checked_div(height).unwrap_or(0);
+        (source, render, width, height)
+    }
+    /// Fills the lookup tables from [`level`](Self::level) and
+    /// [`adjust`](Self::adjust), so the per-cell lookup is a single index.
+    fn rebuild_luts(&mut self) {
+        let rows = self.data.len().max(1);
+        let cols = self.data.first().map_or(0, |v| v.len()).max(1);
+        for v in 0..=u8::MAX {
+            let intensity = self.adjust(v);
+            self.col_lut[v as usize] = self.level(intensity, cols) as u16;
+            self.row_lut[v as usize] = self.level(intensity, rows) as u16;
+        }
+        self.shapes = (0..self.data.len())
+            .flat_map(|row| (0..self.data[row].len()).map(move |col| (row, col)))
+            .filter_map(|(row, col)| {
+                let shape = glyph_bitmap(self.data[row][col].as_char()?)?;
+                Some(((row as u16, col as u16), shape))
+            })
+            .collect();
+    }
+    /// Average UTF-8 length of the charset's glyphs.
+    fn glyph_size(&self) -> f64 {
+        let (bytes, count) = self.data.iter().flatten().fold((0, 0), |(b, n), glyph| {
+            (b + glyph.encode_utf8(&mut [0; 4]).len(), n + 1)
+        });
+        bytes as f64 / count.max(1) as f64
+    }
+    /// Maps an intensity to one of `n` levels, using the explicit thresholds
+    /// when they fit this axis.
+    #[inline(always)]
+    fn level(&self, intensity: u8, n: usize) -> usize {
+        match &self.thresholds {
+            Some(t) if t.len() + 1 == n => t.partition_point(|v| *v <= intensity),
+            _ => std::cmp::min((intensity as usize * n) / u8::MAX as usize, n - 1),
+        }
+    }
+    /// Applies intensity adjustments (gamma, then inversion) before character
+    /// lookup.
+    #[inline(always)]
+    fn adjust(&self, intensity: u8) -> u8 {
+        let intensity = match self.gamma {
+            1.0 => intensity,
+            gamma => (u8::MAX as f32 * (intensity as f32 / u8::MAX as f32).powf(1.0 / gamma))
+                .round() as u8,
+        };
+        if self.invert {
+            u8::MAX - intensity
+        } else {
+            intensity
+        }
+    }
+}
+
+/// A glyph of a charset and the intensities that pick it, see
+/// [`PixtData::legend`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LegendEntry {
+    pub ch: Glyph,
+    /// Charset indices `(row, column)` of `ch`.
+    pub level: (u16, u16),
+    /// First and last intensity picking the column: of the average of both
+    /// pixels for 1-D charsets, of the top pixel for 2-D ones.
+    pub range: (u8, u8),
+    /// First and last intensity of the bottom pixel picking the row of a
+    /// 2-D charset.
+    pub row_range: Option<(u8, u8)>,
 }
 
+/// One source pixel sampled for a cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Pixel {
     pub x: u32,
     pub y: u32,
     pub color: (u8, u8, u8),
+    /// Opacity of the source pixel, `0` is fully transparent. `color` is
+    /// not premultiplied, but already composited when
+    /// [`PixtData::set_alpha_bg`] is set; backends that can't express
+    /// transparency ignore it.
+    pub alpha: u8,
 }
 
 impl From<Pixel> for [u8; 3] {
     fn from(value: Pixel) -> Self {
         let Pixel {
-            x: _,
-            y: _,
-            color: (r, g, b),
+            color: (r, g, b), ..
         } = value;
         [r, g, b]
     }
 }
-impl From<Pixel> for (u8, u8, u8) {
+impl From<Pixel> for [u8; 4] {
     fn from(value: Pixel) -> Self {
         let Pixel {
-            x: _,
-            y: _,
-            color: v,
+            color: (r, g, b),
+            alpha,
+            ..
         } = value;
+        [r, g, b, alpha]
+    }
+}
+impl From<Pixel> for (u8, u8, u8) {
+    fn from(value: Pixel) -> Self {
+        let Pixel { color: v, .. } = value;
         v
     }
 }
 impl PixtData {
+    /// Size of the grid [`chars`](Self::chars) yields for `img`, as
+    /// `(columns, rows)`. Every cell covers two source rows; an odd last
+    /// row makes a row of cells of its own, standing in for both halves.
+    /// [`Subpixels`] cells cover [`Subpixels::size`] instead.
+    pub fn grid_size(&self, img: &DynamicImage) -> (u32, u32) {
+        self.grid_dims(img.width(), img.height())
+    }
+    /// Intensities of `img` the charset is picked from, before gamma and
+    /// inversion.
+    fn intensity_plane(&self, img: &DynamicImage) -> GrayImage {
+        match self.alpha_bg {
+            Some(backdrop) if img.color().has_alpha() => intensity_plane(
+                &DynamicImage::ImageRgb8(flatten(img, backdrop)),
+                self.luminance,
+            ),
+            _ => intensity_plane(img, self.luminance),
+        }
+    }
+    /// Source pixels `(columns, rows)` every cell covers.
+    pub fn cell_pixels(&self) -> (u32, u32) {
+        match (self.selection, self.subpixels) {
+            (SelectionStrategy::Structural, _) => BITMAP_SIZE,
+            (_, Some(subpixels)) => subpixels.size(),
+            (_, None) => (1, 2),
+        }
+    }
+    /// Grid `(columns, rows)` of cells covering `width`×`height` pixels.
+    fn grid_dims(&self, width: u32, height: u32) -> (u32, u32) {
+        let (cols, rows) = self.cell_pixels();
+        (width.div_ceil(cols), height.div_ceil(rows))
+    }
+    /// Glyphs picked for every cell of `img`, with the top and bottom pixel.
     pub fn chars(
         &self,
         img: &DynamicImage,
-    ) -> impl Iterator<Item = impl Iterator<Item = (char, Pixel, Pixel)>> {
+    ) -> impl Iterator<Item = impl Iterator<Item = (Glyph, Pixel, Pixel)>> {
+        self.chars_with_luma(img, None)
+    }
+    /// Like [`chars`](Self::chars), but when `luma` is given (an intensity
+    /// plane the same size as `img`) characters are picked from its values
+    /// instead of from the pixel colors. Colors still come from `img`.
+    pub fn chars_with_luma<'a, 'b>(
+        &'b self,
+        img: &'a DynamicImage,
+        luma: Option<&'a GrayImage>,
+    ) -> impl Iterator<Item = impl Iterator<Item = (Glyph, Pixel, Pixel)> + use<'a, 'b>> + use<'a, 'b>
+    {
+        self.levels_with_luma(img, luma).map(|line| {
+            line.map(|((row, col), p1, p2)| (self.data[row as usize][col as usize].clone(), p1, p2))
+        })
+    }
+    /// Like [`chars_with_luma`](Self::chars_with_luma), but yields the
+    /// charset indices `(row, column)` selected for each cell instead of the
+    /// character. For 1-D charsets the row is always `0`.
+    pub fn levels_with_luma<'a, 'b>(
+        &'b self,
+        img: &'a DynamicImage,
+        luma: Option<&'a GrayImage>,
+    ) -> impl Iterator<Item = impl Iterator<Item = ((u16, u16), Pixel, Pixel)> + use<'a, 'b>> + use<'a, 'b>
+    {
+        let (w, h) = self.grid_size(img);
+        self.levels_in(img, luma, CellRect { x: 0, y: 0, w, h })
+    }
+    /// Like [`levels_with_luma`](Self::levels_with_luma), limited to the
+    /// cells inside `region`. Pixels outside it are never sampled; parts of
+    /// `region` beyond the grid are ignored.
+    pub fn levels_in<'a, 'b>(
+        &'b self,
+        img: &'a DynamicImage,
+        luma: Option<&'a GrayImage>,
+        region: CellRect,
+    ) -> impl Iterator<Item = impl Iterator<Item = ((u16, u16), Pixel, Pixel)> + use<'a, 'b>> + use<'a, 'b>
+    {
+        /// Where cell intensities come from.
+        enum Intensity<'a> {
+            /// A precomputed plane, e.g. after CLAHE, or a grayscale source
+            /// whose values already are the intensities.
+            Plane(&'a GrayImage),
+            /// A grayscale source with alpha; the gray channel is used as is.
+            GrayAlpha(&'a GrayAlphaImage),
+            Rgb(Cow<'a, RgbImage>, Luminance),
+        }
+        impl Intensity<'_> {
+            /// Intensities of source row `y`, columns `x0..x1`.
+            fn row(&self, y: u32, x0: u32, x1: u32) -> Vec<u8> {
+                let (x0, x1) = (x0 as usize, x1 as usize);
+                match self {
+                    Self::Plane(plane) => {
+                        let start = y as usize * plane.width() as usize;
+                        plane.as_raw()[start + x0..start + x1].to_vec()
+                    }
+                    Self::GrayAlpha(img) => {
+                        let start = y as usize * img.width() as usize;
+                        img.as_raw()[(start + x0) * 2..(start + x1) * 2]
+                            .iter()
+                            .step_by(2)
+                            .copied()
+                            .collect()
+                    }
+                    Self::Rgb(rgb, formula) => {
+                        let start = y as usize * rgb.width() as usize;
+                        let mut out = vec![0; x1 - x0];
+                        let src = &rgb.as_raw()[(start + x0) * 3..(start + x1) * 3];
+                        luma_row(src, &mut out, *formula);
+                        out
+                    }
+                }
+            }
+        }
+
         struct ItrImgOuter<'a, 'b> {
-            y: u32,
+            /// Next cell row.
+            row: u32,
+            region: CellRect,
             img: &'a DynamicImage,
+            intensity: Intensity<'a>,
+            /// Errors carried between rows with
+            /// [`DitherMode::FloydSteinberg`].
+            diffusion: Option<Diffusion>,
+            /// Intensity from which block pixels are lit, when the same for
+            /// the whole image.
+            level: Option<u8>,
             pixt_img: &'b PixtData,
         }
         impl<'a, 'b> ItrImgOuter<'a, 'b> {
-            fn new(img: &'a DynamicImage, pixt_img: &'b PixtData) -> Self {
+            fn new(
+                img: &'a DynamicImage,
+                luma: Option<&'a GrayImage>,
+                region: CellRect,
+                pixt_img: &'b PixtData,
+            ) -> Self {
+                // Every formula maps three equal channels to the gray value
+                // itself, so grayscale sources skip the RGB round trip.
+                let formula = pixt_img.luminance;
+                let intensity = match (luma, img) {
+                    (Some(luma), _) => Intensity::Plane(luma),
+                    (None, img) if img.color().has_alpha() && pixt_img.alpha_bg.is_some() => {
+                        let backdrop = pixt_img.alpha_bg.unwrap_or_default();
+                        Intensity::Rgb(Cow::Owned(flatten(img, backdrop)), formula)
+                    }
+                    (None, DynamicImage::ImageLuma8(gray)) => Intensity::Plane(gray),
+                    (None, DynamicImage::ImageLumaA8(gray)) => Intensity::GrayAlpha(gray),
+                    (None, DynamicImage::ImageRgb8(rgb)) => {
+                        Intensity::Rgb(Cow::Borrowed(rgb), formula)
+                    }
+                    (None, _) => Intensity::Rgb(Cow::Owned(img.to_rgb8()), formula),
+                };
+                let diffusion = (pixt_img.dither == DitherMode::FloydSteinberg
+                    && !pixt_img.by_shape())
+                .then(|| {
+                    let x_end = region.x.saturating_add(region.w).min(img.width());
+                    Diffusion::new(x_end.saturating_sub(region.x) as usize)
+                });
+                let level = match pixt_img.block_threshold {
+                    _ if !pixt_img.by_shape() => None,
+                    BlockThreshold::Mean => None,
+                    BlockThreshold::Fixed(v) => Some(v),
+                    BlockThreshold::Otsu => {
+                        let mut histogram = [0u64; 256];
+                        for y in 0..img.height() {
+                            for v in intensity.row(y, 0, img.width()) {
+                                histogram[v as usize] += 1;
+                            }
+                        }
+                        Some(otsu(&histogram))
+                    }
+                };
                 Self {
-                    y: 0,
+                    row: region.y,
+                    region,
                     img,
+                    intensity,
+                    diffusion,
+                    level,
                     pixt_img,
                 }
             }
@@ -132,66 +2026,292 @@ impl PixtData {
 
         struct ItrImgInner<'a, 'b> {
             x: u32,
+            x0: u32,
+            x_end: u32,
+            /// Cell row, and the source row of its top half.
+            row: u32,
             y: u32,
             img: &'a DynamicImage,
+            /// Intensities of the top and bottom source row from `x0` on.
+            rows: (Vec<u8>, Vec<u8>),
+            /// Levels picked up front for the whole row, when error
+            /// diffusion needs them in order.
+            levels: Option<Vec<(usize, usize)>>,
+            level: Option<u8>,
             pixt_img: &'b PixtData,
         }
         impl Iterator for ItrImgInner<'_, '_> {
-            type Item = (char, Pixel, Pixel);
+            type Item = ((u16, u16), Pixel, Pixel);
             fn next(&mut self) -> Option<Self::Item> {
-                if self.x >= self.img.width() || self.y >= self.img.height() {
+                if self.x >= self.x_end {
                     return None;
                 }
-                let t = self.img.get_pixel(self.x, self.y).to_rgb();
-                let b = self.img.get_pixel(self.x, self.y + 1).to_rgb();
-                let p1 = Pixel {
-                    x: self.x,
-                    y: self.y,
-                    color: unwrap_rgb(t),
-                };
-                let p2 = Pixel {
-                    x: self.x,
-                    y: self.y + 1,
-                    color: unwrap_rgb(b),
+                if self.pixt_img.by_shape() {
+                    let cell = self.block();
+                    self.x += 1;
+                    return Some(cell);
+                }
+                // An odd last source row has no partner below and makes
+                // both halves.
+                let y2 = (self.y + 1).min(self.img.height() - 1);
+                let (p1, p2) = (
+                    self.pixel(self.x, self.y, self.img.get_pixel(self.x, self.y)),
+                    self.pixel(self.x, y2, self.img.get_pixel(self.x, y2)),
+                );
+                let i = (self.x - self.x0) as usize;
+                let (row, col) = match &self.levels {
+                    Some(levels) => levels[i],
+                    None => {
+                        let threshold = self.pixt_img.dither.threshold(self.x, self.row);
+                        self.pixt_img
+                            .levels_for(self.rows.0[i], self.rows.1[i], threshold)
+                    }
                 };
                 self.x += 1;
-                if self.pixt_img.data.len() == 1 {
-                    Some((self.pixt_img.get_char_single_raw(t, b), p1, p2))
-                } else {
-                    Some((self.pixt_img.get_char(t, b), p1, p2))
+                Some(((row as u16, col as u16), p1, p2))
+            }
+        }
+        impl ItrImgInner<'_, '_> {
+            fn pixel(&self, x: u32, y: u32, px: Rgba<u8>) -> Pixel {
+                Pixel {
+                    x,
+                    y,
+                    color: match self.pixt_img.alpha_bg {
+                        Some(backdrop) => unwrap_rgb(Rgb(flatten_pixel(px.0, backdrop))),
+                        None => unwrap_rgb(px.to_rgb()),
+                    },
+                    alpha: px.0[3],
                 }
             }
+            /// The pattern of the block under cell column `x`, with the
+            /// mean of the lit pixels as top pixel and of the unlit ones as
+            /// bottom pixel. Pixels past the edges are transparent.
+            fn block(&self) -> ((u16, u16), Pixel, Pixel) {
+                let (cols, rows) = self.pixt_img.cell_pixels();
+                let (x0, y0) = (self.x * cols, self.y);
+                let (w, h) = self.img.dimensions();
+                let px: Vec<Pixel> = (y0..y0 + rows)
+                    .flat_map(|y| (x0..x0 + cols).map(move |x| (x, y)))
+                    .map(|(x, y)| {
+                        let px = if x < w && y < h {
+                            self.img.get_pixel(x, y)
+                        } else {
+                            Rgba([0, 0, 0, 0])
+                        };
+                        self.pixel(x, y, px)
+                    })
+                    .collect();
+                let colors: Vec<[u8; 3]> = px.iter().map(|&p| p.into()).collect();
+                let data = self.pixt_img;
+                let (level, mask) = match (data.selection, data.subpixels) {
+                    (SelectionStrategy::Structural, _) => {
+                        let bits =
+                            lit_pixels(&colors, data.luminance, data.invert, self.level) as u32;
+                        // The closest shape differs in the fewest pixels.
+                        let (level, shape) = data
+                            .shapes
+                            .iter()
+                            .min_by_key(|(_, shape)| (shape ^ bits).count_ones())
+                            .copied()
+                            .unwrap_or_default();
+                        (level, shape as u64)
+                    }
+                    (_, subpixels) => {
+                        let subpixels = subpixels.unwrap_or(Subpixels::Quadrant);
+                        let mask =
+                            subpixels.pattern(&colors, data.luminance, data.invert, self.level);
+                        ((0, mask as u16), mask as u64)
+                    }
+                };
+                let all = u64::MAX >> (64 - px.len());
+                // A uniform block has no unlit (or lit) pixels; both halves
+                // then take the mean of all of them.
+                let mean = |bits: u64| {
+                    let bits = if bits == 0 { all } else { bits };
+                    let picked: Vec<Pixel> = (0..px.len())
+                        .filter(|i| bits & (1 << i) != 0)
+                        .map(|i| px[i])
+                        .collect();
+                    let n = picked.len() as u32;
+                    let avg = |f: fn(&Pixel) -> u8| {
+                        (picked.iter().map(|p| f(p) as u32).sum::<u32>() / n) as u8
+                    };
+                    Pixel {
+                        color: (avg(|p| p.color.0), avg(|p| p.color.1), avg(|p| p.color.2)),
+                        alpha: avg(|p| p.alpha),
+                        ..picked[0]
+                    }
+                };
+                (level, mean(mask), mean(!mask & all))
+            }
         }
         impl<'a, 'b> Iterator for ItrImgOuter<'a, 'b> {
             type Item = ItrImgInner<'a, 'b>;
             fn next(&mut self) -> Option<Self::Item> {
-                let y = self.y;
-                if y + 1 >= self.img.height() {
+                let row = self.row;
+                if row >= self.pixt_img.grid_size(self.img).1
+                    || row >= self.region.y.saturating_add(self.region.h)
+                {
                     return None;
                 }
-                self.y += 1;
+                self.row += 1;
+                let y = row * self.pixt_img.cell_pixels().1;
+                let x_end = self
+                    .region
+                    .x
+                    .saturating_add(self.region.w)
+                    .min(self.pixt_img.grid_size(self.img).0);
+                let x0 = self.region.x.min(x_end);
+                let rows = if self.pixt_img.by_shape() {
+                    // Block cells are picked from the pixels themselves.
+                    (Vec::new(), Vec::new())
+                } else {
+                    let top = self.intensity.row(y, x0, x_end);
+                    let bottom = if y + 1 < self.img.height() {
+                        self.intensity.row(y + 1, x0, x_end)
+                    } else {
+                        top.clone()
+                    };
+                    (top, bottom)
+                };
+                let levels = self.diffusion.as_mut().map(|errors| {
+                    let levels = (0..rows.0.len())
+                        .map(|i| {
+                            self.pixt_img
+                                .levels_diffused(rows.0[i], rows.1[i], errors, i)
+                        })
+                        .collect();
+                    errors.next_row();
+                    levels
+                });
                 Some(ItrImgInner {
-                    x: 0,
+                    x: x0,
+                    x0,
+                    x_end,
+                    row,
                     y,
                     img: self.img,
+                    rows,
+                    levels,
+                    level: self.level,
                     pixt_img: self.pixt_img,
                 })
             }
         }
-        ItrImgOuter::new(img, self)
+        ItrImgOuter::new(img, luma, region, self)
     }
 }
 
 impl<T: IntoPixtData> From<T> for PixtData {
     fn from(value: T) -> Self {
-        Self {
-            data: value.into_pixt_data(),
+        Self::new(value)
+    }
+}
+
+/// One entry of a charset: a single character, or a grapheme cluster of
+/// several code points (a flag, an emoji with a skin tone, a letter with
+/// combining marks) or padded to [`PixtData::glyph_width`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Glyph {
+    Char(char),
+    Cluster(Arc<str>),
+}
+
+impl Glyph {
+    /// The glyph of `text`, a [`Char`](Self::Char) if it is one character.
+    pub fn new(text: &str) -> Self {
+        let mut chars = text.chars();
+        match (chars.next(), chars.next()) {
+            (Some(ch), None) => Self::Char(ch),
+            _ => Self::Cluster(text.into()),
         }
     }
+    /// The character, if the glyph is a single one.
+    pub fn as_char(&self) -> Option<char> {
+        match self {
+            Self::Char(ch) => Some(*ch),
+            Self::Cluster(_) => None,
+        }
+    }
+    /// The text of the glyph; `buf` holds it for a single character.
+    pub fn encode_utf8<'a>(&'a self, buf: &'a mut [u8; 4]) -> &'a str {
+        match self {
+            Self::Char(ch) => ch.encode_utf8(buf),
+            Self::Cluster(cluster) => cluster,
+        }
+    }
+    /// Terminal columns the glyph takes up.
+    pub fn width(&self) -> usize {
+        self.encode_utf8(&mut [0; 4]).width()
+    }
+    /// Whether the glyph is nothing but spaces.
+    pub fn is_blank(&self) -> bool {
+        self.encode_utf8(&mut [0; 4]).bytes().all(|v| v == b' ')
+    }
+}
+
+impl Default for Glyph {
+    fn default() -> Self {
+        Self::Char(' ')
+    }
+}
+
+impl From<char> for Glyph {
+    fn from(ch: char) -> Self {
+        Self::Char(ch)
+    }
+}
+
+impl fmt::Display for Glyph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.encode_utf8(&mut [0; 4]))
+    }
+}
+
+/// Charset rows of single characters as glyphs.
+fn glyphs(rows: Vec<Vec<char>>) -> Vec<Vec<Glyph>> {
+    rows.into_iter()
+        .map(|row| row.into_iter().map(Glyph::Char).collect())
+        .collect()
+}
+
+/// Charset rows split into grapheme clusters, so characters made of several
+/// code points (flags, emoji with skin tones, letters with combining marks)
+/// are one level each. Converts into [`PixtData`]; collections of `char`
+/// still go through [`IntoPixtData`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Graphemes(pub Vec<Vec<String>>);
+
+impl Graphemes {
+    /// Splits every row of `rows` into grapheme clusters.
+    pub fn new<S: AsRef<str>>(rows: &[S]) -> Self {
+        Self(
+            rows.iter()
+                .map(|row| row.as_ref().graphemes(true).map(String::from).collect())
+                .collect(),
+        )
+    }
+    /// Splits a single row of text.
+    pub fn row(text: &str) -> Self {
+        Self::new(&[text])
+    }
+}
+
+impl From<Graphemes> for PixtData {
+    fn from(value: Graphemes) -> Self {
+        let mut data = Self::new(Vec::<Vec<char>>::new());
+        data.set_graphemes(value);
+        data
+    }
 }
 
 pub trait IntoPixtData {
     fn into_pixt_data(self) -> Vec<Vec<char>>;
+    /// Whether the charset is [`Subpixels::glyphs`], picked per block of
+    /// pixels by pattern instead of by intensity.
+    fn subpixels(&self) -> Option<Subpixels> {
+        None
+    }
 }
 
 impl IntoPixtData for Vec<Vec<char>> {
@@ -240,18 +2360,113 @@ pub enum ColorType {
     FgTopBgDown,
     /// upper pixel color as background, lower pixel color as forground
     BgTopFgDown,
+    /// Upper pixel color applied as forground color, keeping thin lines
+    /// that averaging would wash out
+    TopFgOnly,
+    /// Lower pixel color applied as forground color
+    BottomFgOnly,
     /// default color
     #[default]
     None,
 }
 
+impl ColorType {
+    pub const ALL: [Self; 7] = [
+        Self::None,
+        Self::AvgFgOnly,
+        Self::AvgBgOnly,
+        Self::FgTopBgDown,
+        Self::BgTopFgDown,
+        Self::TopFgOnly,
+        Self::BottomFgOnly,
+    ];
+    /// Canonical name, as used by [`Display`](fmt::Display) and
+    /// [`FromStr`]: the variant in kebab-case.
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::AvgFgOnly => "avg-fg-only",
+            Self::AvgBgOnly => "avg-bg-only",
+            Self::FgTopBgDown => "fg-top-bg-down",
+            Self::BgTopFgDown => "bg-top-fg-down",
+            Self::TopFgOnly => "top-fg-only",
+            Self::BottomFgOnly => "bottom-fg-only",
+        }
+    }
+    /// Foreground and background color of a cell whose upper pixel is
+    /// `top` and lower pixel is `bottom`; `None` for what this color type
+    /// leaves alone.
+    pub fn colors(&self, top: [u8; 3], bottom: [u8; 3]) -> (Option<[u8; 3]>, Option<[u8; 3]>) {
+        match self {
+            Self::None => (None, None),
+            Self::AvgFgOnly => (Some(avg_color(top, bottom)), None),
+            Self::AvgBgOnly => (None, Some(avg_color(top, bottom))),
+            Self::FgTopBgDown => (Some(top), Some(bottom)),
+            Self::BgTopFgDown => (Some(bottom), Some(top)),
+            Self::TopFgOnly => (Some(top), None),
+            Self::BottomFgOnly => (Some(bottom), None),
+        }
+    }
+}
+
+impl fmt::Display for ColorType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Also accepts `avg-fg`, `avg-bg`, `top-fg` and `bottom-fg`.
+impl FromStr for ColorType {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Self> {
+        let aliases = [
+            ("avg-fg", Self::AvgFgOnly),
+            ("avg-bg", Self::AvgBgOnly),
+            ("top-fg", Self::TopFgOnly),
+            ("bottom-fg", Self::BottomFgOnly),
+        ];
+        parse_name("color type", s, &Self::ALL, Self::name, &aliases)
+    }
+}
+
+/// Looks `value` up by the `name` of each of `variants`, then in `aliases`,
+/// ignoring case and surrounding whitespace. The error lists the canonical
+/// names.
+pub(crate) fn parse_name<T: Clone>(
+    kind: &str,
+    value: &str,
+    variants: &[T],
+    name: impl Fn(&T) -> &'static str,
+    aliases: &[(&str, T)],
+) -> io::Result<T> {
+    let key = value.trim().to_ascii_lowercase();
+    variants
+        .iter()
+        .find(|v| name(v) == key)
+        .or_else(|| aliases.iter().find(|(a, _)| *a == key).map(|(_, v)| v))
+        .cloned()
+        .ok_or_else(|| {
+            let names: Vec<_> = variants.iter().map(name).collect();
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "unknown {} '{}', expected one of: {}",
+                    kind,
+                    value,
+                    names.join(", ")
+                ),
+            )
+        })
+}
+
 /// Output type
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum OutputType {
     Text(ColorType),
     Term(ColorType),
     Html(ColorType),
-    Svg(ColorType), // TODO: Implement proper SVG rendering
+    Svg(ColorType),
 }
 
 impl Default for OutputType {
@@ -260,24 +2475,62 @@ impl Default for OutputType {
     }
 }
 
+/// The format name only, the color type is not included: `text`, `ansi`,
+/// `html` or `svg`.
+impl fmt::Display for OutputType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.format_name())
+    }
+}
+
+/// Parses the names [`Display`](fmt::Display) writes, plus the aliases
+/// `txt`, `term` and `htm`. The color type is the default one.
+impl FromStr for OutputType {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Self> {
+        let all = [Self::text(), Self::term(), Self::html(), Self::svg()];
+        let aliases = [
+            ("txt", Self::text()),
+            ("term", Self::term()),
+            ("htm", Self::html()),
+        ];
+        parse_name("output format", s, &all, Self::format_name, &aliases)
+    }
+}
+
+/// Plain text for paths without a known extension, see
+/// [`OutputType::from_path`].
 impl<T: AsRef<Path>> From<T> for OutputType {
     fn from(path: T) -> Self {
-        let ext = path
-            .as_ref()
-            .extension()
-            .unwrap_or_default()
-            .to_str()
-            .unwrap_or_default();
-        let color = ColorType::default();
-        match ext.to_lowercase().as_str() {
-            "html" | "htm" => Self::Html(color),
-            "svg" => Self::Svg(color),
-            _ => Self::Term(color),
-        }
+        Self::from_path(path).unwrap_or_else(Self::text)
     }
 }
 
 impl OutputType {
+    /// Format of a file at `path` going by its extension, ignoring case:
+    /// `txt` is plain text, `ans` ANSI text, `html`/`htm` HTML and `svg`
+    /// SVG. `None` for anything else, including no extension.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let ext = path.as_ref().extension()?.to_str()?.to_lowercase();
+        let color = ColorType::default();
+        match ext.as_str() {
+            "txt" => Some(Self::Text(color)),
+            "ans" => Some(Self::Term(color)),
+            "html" | "htm" => Some(Self::Html(color)),
+            "svg" => Some(Self::Svg(color)),
+            _ => None,
+        }
+    }
+    /// Name of the format, see the [`Display`](fmt::Display) impl.
+    pub const fn format_name(&self) -> &'static str {
+        match self {
+            Self::Text(_) => "text",
+            Self::Term(_) => "ansi",
+            Self::Html(_) => "html",
+            Self::Svg(_) => "svg",
+        }
+    }
     pub const fn text() -> Self {
         Self::Text(ColorType::None)
     }
@@ -290,6 +2543,58 @@ impl OutputType {
     pub fn svg() -> Self {
         Self::Svg(ColorType::default())
     }
+    /// Color the art is usually shown on: the `#191919` page of the HTML and
+    /// SVG headers, black for terminals and text.
+    pub const fn backdrop(&self) -> [u8; 3] {
+        match self {
+            Self::Html(_) | Self::Svg(_) => [0x19; 3],
+            Self::Text(_) | Self::Term(_) => [0; 3],
+        }
+    }
+    /// Width:height ratio of one character cell as displayed by this output.
+    pub fn cell_aspect(&self) -> f32 {
+        match self {
+            // The HTML header uses a 10px monospace font (~6px advance) with a
+            // line-height of 1.2 when uncolored and 0.6 when colored.
+            // SVG output uses the same metrics, see `svg::line_height`.
+            Self::Html(ColorType::None) | Self::Svg(ColorType::None) => 6.0 / 12.0,
+            Self::Html(_) | Self::Svg(_) => 1.0,
+            _ => DEFAULT_CELL_ASPECT,
+        }
+    }
+    /// Bytes of HTML markup around one cell's glyph, and bytes written per
+    /// line break. ANSI escapes vary with the color and are not included.
+    fn markup_size(&self) -> (usize, usize) {
+        match self {
+            Self::Term(ColorType::None) => (0, 1),
+            Self::Term(_) => (0, 5),
+            Self::Html(ColorType::AvgFgOnly | ColorType::TopFgOnly | ColorType::BottomFgOnly) => {
+                (37, 7)
+            }
+            Self::Html(ColorType::AvgBgOnly) => (47, 7),
+            Self::Html(ColorType::FgTopBgDown | ColorType::BgTopFgDown) => (61, 7),
+            // Worst case: a `<tspan>` per cell, plus a `<rect>` per cell with
+            // a background; every row is a `<text>`.
+            Self::Svg(ColorType::None) => (0, 32),
+            Self::Svg(ColorType::AvgFgOnly | ColorType::TopFgOnly | ColorType::BottomFgOnly) => {
+                (29, 32)
+            }
+            Self::Svg(_) => (90, 32),
+            _ => (0, 1),
+        }
+    }
+    /// Whether cells are emitted with a background color, making spaces visible.
+    pub fn paints_background(&self) -> bool {
+        let (Self::Text(color) | Self::Term(color) | Self::Html(color) | Self::Svg(color)) = self;
+        matches!(
+            color,
+            ColorType::AvgBgOnly | ColorType::FgTopBgDown | ColorType::BgTopFgDown
+        ) && !matches!(self, Self::Text(_))
+    }
+    pub fn color_type(&self) -> &ColorType {
+        let (Self::Text(color) | Self::Term(color) | Self::Html(color) | Self::Svg(color)) = self;
+        color
+    }
     pub fn color(mut self, color: ColorType) -> Self {
         self = match self {
             Self::Text(_) => Self::Text(color),
@@ -318,12 +2623,11 @@ impl OutputType {
             },
             Self::Html(ColorType::None) => |mut w: W| w.write_all(b"\n"),
             Self::Html(_) => |mut stdout: W| stdout.write_all(b"<br />\n"),
-            Self::Svg(ColorType::None) => |_| todo!(),
-            Self::Svg(_) => |_| todo!(),
+            Self::Svg(_) => |mut w: W| w.write_all(b"\n"),
         }
     }
     #[allow(clippy::type_complexity)]
-    pub fn print_pixel<W: io::Write>(&self) -> impl Fn(W, (char, Pixel, Pixel)) -> io::Result<()> {
+    pub fn print_pixel<W: io::Write>(&self) -> fn(W, (&str, Pixel, Pixel)) -> io::Result<()> {
         match self {
             Self::Text(_) | Self::Term(ColorType::None) => |mut out: W, (v, _, _)| {
                 #[cfg(not(target_arch = "wasm32"))]
@@ -335,7 +2639,7 @@ impl OutputType {
                     write!(out, "{}", v)
                 }
             },
-            Self::Term(ColorType::AvgFgOnly) => |mut out: W, (ch, c1, c2): (char, Pixel, Pixel)| {
+            Self::Term(ColorType::AvgFgOnly) => |mut out: W, (ch, c1, c2): (&str, Pixel, Pixel)| {
                 let c1: [u8; 3] = c1.into();
                 let c2: [u8; 3] = c2.into();
                 #[cfg(not(target_arch = "wasm32"))]
@@ -351,7 +2655,29 @@ impl OutputType {
                     Err(io::Error::other("This features is not available for web"))
                 }
             },
-            Self::Term(ColorType::AvgBgOnly) => |mut out: W, (ch, c1, c2): (char, Pixel, Pixel)| {
+            Self::Term(ColorType::TopFgOnly) => |mut out: W, (ch, c1, _): (&str, Pixel, Pixel)| {
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    execute!(out, SetForegroundColor(rgb_to_true_color(c1)), Print(ch))
+                }
+                #[cfg(target_arch = "wasm32")]
+                {
+                    Err(io::Error::other("This features is not available for web"))
+                }
+            },
+            Self::Term(ColorType::BottomFgOnly) => {
+                |mut out: W, (ch, _, c2): (&str, Pixel, Pixel)| {
+                    #[cfg(not(target_arch = "wasm32"))]
+                    {
+                        execute!(out, SetForegroundColor(rgb_to_true_color(c2)), Print(ch))
+                    }
+                    #[cfg(target_arch = "wasm32")]
+                    {
+                        Err(io::Error::other("This features is not available for web"))
+                    }
+                }
+            }
+            Self::Term(ColorType::AvgBgOnly) => |mut out: W, (ch, c1, c2): (&str, Pixel, Pixel)| {
                 #[cfg(not(target_arch = "wasm32"))]
                 {
                     execute!(
@@ -366,7 +2692,7 @@ impl OutputType {
                 }
             },
             Self::Term(ColorType::FgTopBgDown) => {
-                |mut out: W, (ch, c1, c2): (char, Pixel, Pixel)| {
+                |mut out: W, (ch, c1, c2): (&str, Pixel, Pixel)| {
                     #[cfg(not(target_arch = "wasm32"))]
                     {
                         execute!(
@@ -383,7 +2709,7 @@ impl OutputType {
                 }
             }
             Self::Term(ColorType::BgTopFgDown) => {
-                |mut out: W, (ch, c1, c2): (char, Pixel, Pixel)| {
+                |mut out: W, (ch, c1, c2): (&str, Pixel, Pixel)| {
                     #[cfg(not(target_arch = "wasm32"))]
                     {
                         execute!(
@@ -400,9 +2726,9 @@ impl OutputType {
                 }
             }
             Self::Html(ColorType::None) => {
-                |mut out: W, (ch, _, _): (char, Pixel, Pixel)| write!(out, "{}", ch)
+                |mut out: W, (ch, _, _): (&str, Pixel, Pixel)| write!(out, "{}", ch)
             }
-            Self::Html(ColorType::AvgFgOnly) => |mut out: W, (ch, c1, c2): (char, Pixel, Pixel)| {
+            Self::Html(ColorType::AvgFgOnly) => |mut out: W, (ch, c1, c2): (&str, Pixel, Pixel)| {
                 let color = avg_color(c1.into(), c2.into());
                 write!(
                     out,
@@ -411,7 +2737,25 @@ impl OutputType {
                     ch
                 )
             },
-            Self::Html(ColorType::AvgBgOnly) => |mut out: W, (ch, c1, c2): (char, Pixel, Pixel)| {
+            Self::Html(ColorType::TopFgOnly) => |mut out: W, (ch, c1, _): (&str, Pixel, Pixel)| {
+                write!(
+                    out,
+                    "<span style=\"color: {};\">{}</span>",
+                    rgb_to_css_hex(c1),
+                    ch
+                )
+            },
+            Self::Html(ColorType::BottomFgOnly) => {
+                |mut out: W, (ch, _, c2): (&str, Pixel, Pixel)| {
+                    write!(
+                        out,
+                        "<span style=\"color: {};\">{}</span>",
+                        rgb_to_css_hex(c2),
+                        ch
+                    )
+                }
+            }
+            Self::Html(ColorType::AvgBgOnly) => |mut out: W, (ch, c1, c2): (&str, Pixel, Pixel)| {
                 let color = avg_color(c1.into(), c2.into());
                 write!(
                     out,
@@ -421,7 +2765,7 @@ impl OutputType {
                 )
             },
             Self::Html(ColorType::FgTopBgDown) => {
-                |mut out: W, (ch, c1, c2): (char, Pixel, Pixel)| {
+                |mut out: W, (ch, c1, c2): (&str, Pixel, Pixel)| {
                     write!(
                         out,
                         "<span style=\"color:{};background-color:{};\">{}</span>",
@@ -432,7 +2776,7 @@ impl OutputType {
                 }
             }
             Self::Html(ColorType::BgTopFgDown) => {
-                |mut out: W, (ch, c1, c2): (char, Pixel, Pixel)| {
+                |mut out: W, (ch, c1, c2): (&str, Pixel, Pixel)| {
                     write!(
                         out,
                         "<span style=\"color:{};background-color:{};\">{}</span>",
@@ -442,69 +2786,85 @@ impl OutputType {
                     )
                 }
             }
-            Self::Svg(ColorType::None) => {
-                todo!()
+            // A lone `<tspan>` has no background; whole rows get theirs from
+            // `svg::write_row`.
+            Self::Svg(ColorType::None | ColorType::AvgBgOnly) => {
+                |out: W, (ch, _, _): (&str, Pixel, Pixel)| write_escaped(out, ch)
+            }
+            Self::Svg(ColorType::AvgFgOnly) => |out: W, (ch, c1, c2): (&str, Pixel, Pixel)| {
+                svg_tspan(out, ch, avg_color(c1.into(), c2.into()))
+            },
+            Self::Svg(ColorType::FgTopBgDown | ColorType::TopFgOnly) => {
+                |out: W, (ch, c1, _): (&str, Pixel, Pixel)| svg_tspan(out, ch, c1.into())
+            }
+            Self::Svg(ColorType::BgTopFgDown | ColorType::BottomFgOnly) => {
+                |out: W, (ch, _, c2): (&str, Pixel, Pixel)| svg_tspan(out, ch, c2.into())
             }
-            Self::Svg(ColorType::AvgFgOnly) => {
-                todo!()
+        }
+    }
+    /// Prints a space with no colors applied.
+    pub fn print_blank<W: io::Write>(&self, out: W) -> io::Result<()> {
+        self.print_plain(out, " ")
+    }
+    /// Prints `ch` with no colors applied.
+    pub fn print_plain<W: io::Write>(&self, mut out: W, ch: &str) -> io::Result<()> {
+        match self {
+            Self::Term(color) if *color != ColorType::None => {
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    execute!(out, ResetColor, Print(ch))
+                }
+                #[cfg(target_arch = "wasm32")]
+                {
+                    Err(io::Error::other("This features is not available for web"))
+                }
             }
-            _ => todo!(),
+            Self::Html(_) | Self::Svg(_) => write_escaped(out, ch),
+            _ => write!(out, "{}", ch),
+        }
+    }
+    /// Prints `ch` in the color of `fg` without touching the background.
+    pub fn print_fg_only<W: io::Write>(&self, mut out: W, ch: &str, fg: Pixel) -> io::Result<()> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Self::Term(_) = self {
+            execute!(out, ResetColor)?;
         }
+        let fg_only = self.clone().color(ColorType::AvgFgOnly);
+        let print = fg_only.print_pixel();
+        print(out, (ch, fg, fg))
+    }
+    /// Writes what precedes the rows: the page head for HTML, nothing for
+    /// text. HTML gets the default [`HtmlOptions`].
+    pub fn write_header<W: io::Write>(&self, width: u32, height: u32, out: W) -> io::Result<()> {
+        self.write_header_with(width, height, &HtmlOptions::default(), out)
     }
-    pub fn write_header<W: io::Write>(
+    /// Like [`write_header`](Self::write_header), with HTML sized and
+    /// minified as `html` asks for a grid of `cols`×`rows` characters.
+    pub fn write_header_with<W: io::Write>(
         &self,
-        _width: u32,
-        _height: u32,
+        cols: u32,
+        rows: u32,
+        html: &HtmlOptions,
         mut out: W,
     ) -> io::Result<()> {
         match self {
-            Self::Html(color) => {
-                let margin = 0;
-                let padding = 0;
-                let font_size = 10; // px
-                let line_height = match color {
-                    ColorType::None => 1.2,
-                    _ => 0.6,
-                };
-                let buf = format!(
-                    "<!DOCTYPE html>
-<html lang=\"en\">
-  <head>
-    <meta charset=\"UTF-8\">
-    <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">
-    <style>
-    * {{
-        color: #fff;
-        background-color: #191919;
-        font-family: monospace;
-    }}
-    pre {{
-        line-height: {line_height};
-        margin: {margin};
-        padding: {padding};
-        font-size: {font_size}px;
-    }}
-    </style>
-  </head>
-  <body>
-    <pre>"
-                );
-                out.write_all(buf.as_bytes())
-            }
-            Self::Svg(_) => {
-                todo!()
-            }
+            Self::Html(color) => html::write_head(html, color, cols, rows, out),
+            Self::Svg(color) => svg::write_header(cols, rows, color, html.minify, out),
             _ => Ok(()),
         }
     }
 
+    /// Written between two renders sharing one output.
+    pub fn write_separator<W: io::Write>(&self, mut out: W) -> io::Result<()> {
+        match self {
+            Self::Html(_) => out.write_all(b"<hr/>\n"),
+            _ => out.write_all(b"\n"),
+        }
+    }
     pub fn write_footer<W: io::Write>(&self, file: &mut W) -> io::Result<()> {
         match self {
             Self::Html(_) => file.write_all(b"    </pre>\n  </body>\n</html>\n")?,
-            Self::Svg(_) => {
-                todo!()
-                // file.write_all(b"    </text>\n</svg>")?;
-            }
+            Self::Svg(_) => file.write_all(b"</svg>\n")?,
             _ => {}
         }
         Ok(())
@@ -538,14 +2898,661 @@ fn avg_color([r1, g1, b1]: [u8; 3], [r2, g2, b2]: [u8; 3]) -> [u8; 3] {
     let b = (b1 as u16 + b2 as u16) / 2;
     [r as u8, g as u8, b as u8]
 }
-fn rgb_to_css_hex<T: Into<[u8; 3]>>(color: T) -> String {
-    let color: [u8; 3] = color.into();
-    unsafe {
-        format!(
-            "#{:02X}{:02X}{:02X}",
-            color.get_unchecked(0),
-            color.get_unchecked(1),
-            color.get_unchecked(2)
-        )
+/// Average number of decimal digits of the RGB channels of `img`.
+fn channel_digits(img: &DynamicImage) -> f64 {
+    let (sum, n) = img.pixels().fold((0u64, 0u64), |(sum, n), (_, _, px)| {
+        let digits = px.0[..3]
+            .iter()
+            .map(|v| match v {
+                0..10 => 1,
+                10..100 => 2,
+                _ => 3,
+            })
+            .sum::<u64>();
+        (sum + digits, n + 3)
+    });
+    sum as f64 / n.max(1) as f64
+}
+/// `#RRGGBB` of `color`, formatted where it is written instead of into a
+/// `String`.
+fn rgb_to_css_hex<T: Into<[u8; 3]>>(color: T) -> CssHex {
+    CssHex(css_hex(color.into()))
+}
+/// `#RRGGBB` digits, see [`rgb_to_css_hex`].
+struct CssHex([u8; 7]);
+
+impl fmt::Display for CssHex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0
+            .iter()
+            .try_for_each(|v| fmt::Write::write_char(f, *v as char))
+    }
+}
+/// Writes `ch` as an SVG `<tspan>` filled with `fg`.
+fn svg_tspan(mut out: impl io::Write, ch: &str, fg: [u8; 3]) -> io::Result<()> {
+    out.write_all(b"<tspan fill=\"")?;
+    write_hex(&mut out, fg)?;
+    out.write_all(b"\">")?;
+    write_escaped(&mut out, ch)?;
+    out.write_all(b"</tspan>")
+}
+/// Glyph and foreground and background color of `cell` in SVG output,
+/// leaving out transparent halves like [`RowWriter`] does for the others.
+fn svg_cell<'a>(
+    cell: &'a Cell,
+    color: &ColorType,
+) -> (&'a Glyph, Option<[u8; 3]>, Option<[u8; 3]>) {
+    let &Cell {
+        ref ch,
+        top,
+        bottom,
+        background,
+        ..
+    } = cell;
+    let (c1, c2): ([u8; 3], [u8; 3]) = (top.into(), bottom.into());
+    let (fg, bg) = match (background, color) {
+        ((true, true), _) => return (ch, None, None),
+        ((true, false), ColorType::BgTopFgDown) => (Some(c2), None),
+        ((false, true), ColorType::FgTopBgDown) => (Some(c1), None),
+        (_, color) => color.colors(c1, c2),
+    };
+    (ch, fg, bg)
+}
+/// Colors of the top and bottom half of `cell` in [`SvgMode::Rects`]
+/// output, leaving out background halves. Color types with one color per
+/// cell paint both halves with it; uncolored output uses the average.
+fn svg_rect_colors(cell: &Cell, color: &ColorType) -> svg::RectColumn {
+    let (c1, c2): ([u8; 3], [u8; 3]) = (cell.top.into(), cell.bottom.into());
+    let (top, bottom) = match color {
+        ColorType::FgTopBgDown | ColorType::BgTopFgDown => (c1, c2),
+        ColorType::TopFgOnly => (c1, c1),
+        ColorType::BottomFgOnly => (c2, c2),
+        ColorType::None | ColorType::AvgFgOnly | ColorType::AvgBgOnly => {
+            (avg_color(c1, c2), avg_color(c1, c2))
+        }
+    };
+    let (bg_top, bg_bottom) = cell.background;
+    (
+        Some(top).filter(|_| !bg_top),
+        Some(bottom).filter(|_| !bg_bottom),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use image::RgbaImage;
+
+    use super::*;
+    use crate::style::ImgStyle;
+
+    /// A `width`×`height` image of one gray level per column, from black
+    /// on the left to white on the right.
+    fn gradient(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, _| {
+            let v = (x * 255 / (width - 1).max(1)) as u8;
+            Rgb([v, v, v])
+        }))
+    }
+
+    fn solid(width: u32, height: u32, color: [u8; 3]) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(width, height, Rgb(color)))
+    }
+
+    fn render(pi: &PixtImg, img: &DynamicImage) -> String {
+        let mut out = Vec::new();
+        pi.print(img, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    /// The glyph `data` picks for a cell whose top pixel is gray `top` and
+    /// bottom pixel gray `bottom`.
+    fn corner(data: &PixtData, top: u8, bottom: u8) -> char {
+        let glyph = data.get_char(Rgb([top; 3]), Rgb([bottom; 3]));
+        glyph.as_char().unwrap()
+    }
+
+    #[test]
+    fn wide_grid_top_picks_column_bottom_picks_row() {
+        let data = PixtData::new(vec![
+            "abcde".chars().collect::<Vec<_>>(),
+            "fghij".chars().collect(),
+            "klmno".chars().collect(),
+        ]);
+        assert_eq!(corner(&data, 0, 0), 'a');
+        assert_eq!(corner(&data, 0, 255), 'k');
+        assert_eq!(corner(&data, 255, 0), 'e');
+        assert_eq!(corner(&data, 255, 255), 'o');
+    }
+
+    #[test]
+    fn tall_grid_top_picks_column_bottom_picks_row() {
+        let data = PixtData::new(vec![
+            "abc".chars().collect::<Vec<_>>(),
+            "def".chars().collect(),
+            "ghi".chars().collect(),
+            "jkl".chars().collect(),
+            "mno".chars().collect(),
+        ]);
+        assert_eq!(corner(&data, 0, 0), 'a');
+        assert_eq!(corner(&data, 0, 255), 'm');
+        assert_eq!(corner(&data, 255, 0), 'c');
+        assert_eq!(corner(&data, 255, 255), 'o');
+    }
+
+    #[test]
+    fn odd_last_row_stands_in_for_both_halves() {
+        let pi = PixtImg::new(ImgStyle::Ascii, OutputType::text());
+        let img = solid(4, 3, [255; 3]);
+        assert_eq!(render(&pi, &img), "@@@@\n@@@@\n");
+        let cells = pi.cells(&img);
+        assert_eq!(cells.len(), 2);
+        for cell in &cells[1] {
+            assert_eq!(
+                (cell.top.color, cell.bottom.color),
+                ((255, 255, 255), (255, 255, 255))
+            );
+        }
+    }
+
+    #[test]
+    fn invert_turns_white_from_densest_to_blank() {
+        let pi = PixtImg::new(ImgStyle::Ascii, OutputType::text());
+        assert_eq!(render(&pi, &solid(4, 2, [255; 3])), "@@@@\n");
+        let pi = pi.with_invert(true);
+        assert!(render(&pi, &solid(4, 2, [255; 3])).trim().is_empty());
+
+        // Solid and HalfBlock only carry color, they have no ramp to reverse.
+        // 8×12 fills whole cells of every block and braille grid.
+        for style in ImgStyle::ALL {
+            if matches!(style, ImgStyle::Solid | ImgStyle::HalfBlock) {
+                continue;
+            }
+            let blank = |invert| {
+                let pi = PixtImg::new(style, OutputType::text()).with_invert(invert);
+                let cells = pi.cells(&solid(8, 12, [255; 3]));
+                cells.iter().flatten().all(|cell| {
+                    cell.ch
+                        .as_char()
+                        .is_some_and(|c| c == ' ' || c == '\u{2800}')
+                })
+            };
+            assert!(!blank(false), "{:?} renders white blank", style);
+            assert!(blank(true), "{:?} --invert renders white visibly", style);
+        }
+    }
+
+    #[test]
+    fn rec601_tells_green_from_blue_where_average_cannot() {
+        let mut data = PixtData::new(ImgStyle::Ascii.into_pixt_data());
+        let level = |data: &PixtData, c: [u8; 3]| data.levels(Rgb(c), Rgb(c), None);
+        let (green, blue) = ([0, 255, 0], [0, 0, 255]);
+        assert_eq!(data.luminance, Luminance::Rec601);
+        assert_eq!(level(&data, green), (0, 5));
+        assert_eq!(level(&data, blue), (0, 1));
+        data.set_luminance(Luminance::Average);
+        assert_eq!(level(&data, green), (0, 3));
+        assert_eq!(level(&data, blue), (0, 3));
+    }
+
+    #[test]
+    fn gamma_brightens_midtones_before_the_lookup() {
+        let mut data = PixtData::new(ImgStyle::Ascii.into_pixt_data());
+        assert_eq!(data.adjust(64), 64);
+        assert_eq!(corner(&data, 128, 128), '+');
+        data.set_gamma(2.2);
+        // 255 * (64 / 255)^(1 / 2.2)
+        assert_eq!(data.adjust(64), 136);
+        assert_eq!((data.adjust(0), data.adjust(255)), (0, 255));
+        assert_eq!(corner(&data, 128, 128), '%');
+        data.set_gamma(-1.0);
+        assert_eq!(data.adjust(64), 64);
+    }
+
+    /// `img` as it comes back from a PNG file.
+    fn png_round_trip(img: RgbaImage) -> DynamicImage {
+        let mut png = io::Cursor::new(Vec::new());
+        img.write_to(&mut png, image::ImageFormat::Png).unwrap();
+        image::load_from_memory(png.get_ref()).unwrap()
+    }
+
+    #[test]
+    fn transparent_png_takes_the_backdrop() {
+        // Transparent, but storing white underneath.
+        let img = png_round_trip(RgbaImage::from_pixel(4, 4, Rgba([255, 255, 255, 0])));
+        let pi = PixtImg::new(ImgStyle::Ascii, OutputType::text());
+        let cells = pi.cells(&img);
+        assert!(
+            cells
+                .iter()
+                .flatten()
+                .all(|cell| cell.ch == Glyph::Char(' '))
+        );
+        assert!(render(&pi, &img).trim().is_empty());
+        // Ignoring alpha shows the stored white.
+        let pi = pi.with_alpha_bg(None);
+        assert_eq!(render(&pi, &img), "@@@@\n@@@@\n");
+
+        let img = png_round_trip(RgbaImage::from_pixel(2, 2, Rgba([255, 255, 255, 128])));
+        let pi = PixtImg::new(ImgStyle::Ascii, OutputType::text()).with_alpha_bg(Some([0, 0, 255]));
+        let cell = &pi.cells(&img)[0][0];
+        assert_eq!(cell.top.color, (128, 128, 255));
+    }
+
+    /// `text` without its CSI escape sequences.
+    fn strip_ansi(text: &str) -> String {
+        let mut out = String::new();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.by_ref().find(|c| c.is_ascii_alphabetic());
+            } else {
+                out.push(c);
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn wide_lines_are_twice_as_long() {
+        // Bright on the right, so trimming trailing spaces keeps full lines.
+        let img = gradient(10, 6);
+        for out_type in [
+            OutputType::text(),
+            OutputType::Term(ColorType::AvgFgOnly),
+            OutputType::Term(ColorType::FgTopBgDown),
+        ] {
+            let pi = PixtImg::new(ImgStyle::Ascii, out_type);
+            let narrow = strip_ansi(&render(&pi, &img));
+            let wide = strip_ansi(&render(&pi.with_wide(true), &img));
+            let lengths = |text: &str| text.lines().map(|l| l.chars().count()).collect::<Vec<_>>();
+            assert_eq!(lengths(&narrow), vec![10; 3], "{:?}", narrow);
+            assert_eq!(lengths(&wide), vec![20; 3], "{:?}", wide);
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn print_async_matches_print_through_a_slow_pipe() {
+        use tokio::io::AsyncReadExt;
+
+        let img = gradient(40, 20);
+        let pi = PixtImg::new(ImgStyle::Block, OutputType::Term(ColorType::FgTopBgDown));
+        let mut expected = Vec::new();
+        pi.print(&img, &mut expected).unwrap();
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let (received, reads) = runtime.block_on(async {
+            // Far smaller than a row, so the writer has to wait on the reader.
+            let (writer, mut reader) = tokio::io::duplex(16);
+            let reading = tokio::spawn(async move {
+                let (mut received, mut reads) = (Vec::new(), 0);
+                let mut chunk = [0; 7];
+                loop {
+                    match reader.read(&mut chunk).await.unwrap() {
+                        0 => break (received, reads),
+                        n => received.extend_from_slice(&chunk[..n]),
+                    }
+                    reads += 1;
+                    tokio::task::yield_now().await;
+                }
+            });
+            pi.print_async(&img, writer).await.unwrap();
+            reading.await.unwrap()
+        });
+        assert_eq!(received, expected);
+        assert!(reads >= expected.len() / 7);
+    }
+
+    #[test]
+    fn luts_match_the_direct_computation() {
+        /// The level of intensity `v` on an axis of `n`, computed from
+        /// scratch for every lookup.
+        fn direct(v: u8, n: usize, gamma: f32, invert: bool, thresholds: &[u8]) -> usize {
+            let mut v = match gamma {
+                1.0 => v,
+                _ => (255.0 * (v as f32 / 255.0).powf(1.0 / gamma)).round() as u8,
+            };
+            if invert {
+                v = 255 - v;
+            }
+            match thresholds.len() + 1 == n {
+                true => thresholds.iter().filter(|t| **t <= v).count(),
+                false => (v as usize * n / 255).min(n - 1),
+            }
+        }
+        let charsets = [
+            ImgStyle::Ascii.into_pixt_data(),
+            ImgStyle::Braills.into_pixt_data(),
+            vec!["abc".chars().collect(), "def".chars().collect()],
+        ];
+        let thresholds: [&[u8]; 3] = [&[], &[10, 20, 40, 80, 160, 200, 230, 250], &[100]];
+        for rows in charsets {
+            for (gamma, invert, thresholds) in [
+                (1.0, false, thresholds[0]),
+                (2.2, false, thresholds[0]),
+                (0.45, true, thresholds[0]),
+                (1.0, false, thresholds[1]),
+                (1.8, true, thresholds[2]),
+            ] {
+                let (n_rows, n_cols) = (rows.len(), rows[0].len());
+                let mut data = PixtData::new(rows.clone());
+                data.set_gamma(gamma).set_invert(invert);
+                if !thresholds.is_empty() {
+                    data.set_thresholds(thresholds.to_vec());
+                }
+                for v in 0..=u8::MAX {
+                    let expected = direct(v, n_cols, gamma, invert, thresholds);
+                    assert_eq!(
+                        data.col_lut[v as usize] as usize, expected,
+                        "column of {}",
+                        v
+                    );
+                    let expected = direct(v, n_rows, gamma, invert, thresholds);
+                    assert_eq!(data.row_lut[v as usize] as usize, expected, "row of {}", v);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn render_into_is_bit_identical_to_print() {
+        let colorful = DynamicImage::ImageRgb8(RgbImage::from_fn(24, 14, |x, y| {
+            Rgb([(x * 10) as u8, (y * 18) as u8, ((x + y) * 6) as u8])
+        }));
+        let small = gradient(5, 3);
+        let mut scratch = RenderScratch::new();
+        for style in ImgStyle::ALL {
+            for out_type in [
+                OutputType::text(),
+                OutputType::Term(ColorType::FgTopBgDown),
+                OutputType::Html(ColorType::AvgFgOnly),
+                OutputType::Svg(ColorType::AvgBgOnly),
+            ] {
+                let pi = PixtImg::new(style, out_type.clone());
+                // The same scratch across sizes, as animations reuse it.
+                for img in [&colorful, &small, &colorful] {
+                    let mut printed = Vec::new();
+                    pi.print(img, &mut printed).unwrap();
+                    let mut rendered = Vec::new();
+                    pi.render_into(img, &mut scratch, &mut rendered).unwrap();
+                    assert!(printed == rendered, "{:?} as {}", style, out_type);
+                    assert_eq!(scratch.output(), &printed[..]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tiny_images_still_render() {
+        for (w, h) in [(1, 1), (5, 1), (1, 5)] {
+            let img = solid(w, h, [255; 3]);
+            let pi = PixtImg::new(ImgStyle::Ascii, OutputType::text());
+            let line = "@".repeat(w as usize) + "\n";
+            assert_eq!(render(&pi, &img), line.repeat(h.div_ceil(2) as usize));
+            for style in ImgStyle::ALL {
+                for out_type in [OutputType::Term(ColorType::FgTopBgDown), OutputType::svg()] {
+                    let pi = PixtImg::new(style, out_type.clone());
+                    let out = render(&pi, &img);
+                    assert!(
+                        !out.trim().is_empty(),
+                        "{:?} as {} of {}x{}",
+                        style,
+                        out_type,
+                        w,
+                        h
+                    );
+                    assert!(!pi.cells(&img).is_empty());
+                }
+            }
+        }
+    }
+
+    /// Every one of `all` parses back from its display name, also in other
+    /// cases and padded, and every alias parses to its variant.
+    fn round_trips<T>(all: &[T], aliases: &[(&str, T)])
+    where
+        T: FromStr<Err = io::Error> + fmt::Display + fmt::Debug + PartialEq,
+    {
+        for v in all {
+            let name = v.to_string();
+            assert_eq!(&name.parse::<T>().unwrap(), v);
+            assert_eq!(
+                &format!(" {} ", name.to_uppercase()).parse::<T>().unwrap(),
+                v
+            );
+        }
+        for (alias, v) in aliases {
+            assert_eq!(&alias.parse::<T>().unwrap(), v, "alias {}", alias);
+        }
+        let err = "no-such-name".parse::<T>().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn names_round_trip_and_aliases_parse() {
+        use crate::{
+            blocks::SelectionStrategy,
+            color::{ColorDepth, FilterPreset},
+        };
+
+        round_trips(
+            &ImgStyle::ALL,
+            &[
+                ("braille", ImgStyle::Braills),
+                ("halfblock", ImgStyle::HalfBlock),
+            ],
+        );
+        round_trips(
+            &ColorType::ALL,
+            &[
+                ("avg-fg", ColorType::AvgFgOnly),
+                ("avg-bg", ColorType::AvgBgOnly),
+                ("top-fg", ColorType::TopFgOnly),
+                ("bottom-fg", ColorType::BottomFgOnly),
+            ],
+        );
+        round_trips(
+            &[
+                OutputType::text(),
+                OutputType::term(),
+                OutputType::html(),
+                OutputType::svg(),
+            ],
+            &[
+                ("txt", OutputType::text()),
+                ("term", OutputType::term()),
+                ("htm", OutputType::html()),
+            ],
+        );
+        let ordered = |size| DitherMode::Ordered { size };
+        round_trips(
+            &[DitherMode::ALL.as_slice(), &[ordered(2), ordered(8)]].concat(),
+            &[
+                ("bluenoise", DitherMode::BlueNoise),
+                ("fs", DitherMode::FloydSteinberg),
+                ("ordered", ordered(4)),
+            ],
+        );
+        round_trips(
+            &Luminance::ALL,
+            &[("bt601", Luminance::Rec601), ("bt709", Luminance::Rec709)],
+        );
+        round_trips(
+            &FilterPreset::ALL,
+            &[("greyscale", FilterPreset::Grayscale)],
+        );
+        round_trips(
+            &ColorDepth::ALL,
+            &[
+                ("24bit", ColorDepth::TrueColor),
+                ("ansi256", ColorDepth::Ansi256),
+                ("ansi16", ColorDepth::Ansi16),
+            ],
+        );
+        round_trips(&SelectionStrategy::ALL, &[]);
+        round_trips(
+            &[
+                BlockThreshold::Mean,
+                BlockThreshold::Otsu,
+                BlockThreshold::Fixed(128),
+            ],
+            &[],
+        );
+    }
+
+    #[test]
+    fn skin_tone_emoji_are_one_level_each() {
+        let ramp = "👋🏻👋🏼👋🏽👋🏾👋🏿";
+        let pi = PixtImg::new(Graphemes::row(ramp), OutputType::text());
+        let img = gradient(5, 2);
+        let cells = pi.cells(&img);
+        let glyphs: Vec<Glyph> = cells[0].iter().map(|cell| cell.ch.clone()).collect();
+        let expected: Vec<Glyph> = ramp.graphemes(true).map(Glyph::new).collect();
+        assert_eq!(glyphs, expected);
+        assert!(glyphs.iter().all(|v| matches!(v, Glyph::Cluster(_))));
+        assert_eq!(render(&pi, &img), format!("{}\n", ramp));
+    }
+
+    #[test]
+    fn combining_accents_stay_on_their_letter() {
+        let ramp = "e\u{301}o\u{308}a\u{30a}\u{323}";
+        let pi = PixtImg::new(Graphemes::row(ramp), OutputType::text());
+        let img = gradient(3, 2);
+        let glyphs: Vec<String> = pi.cells(&img)[0]
+            .iter()
+            .map(|cell| cell.ch.to_string())
+            .collect();
+        assert_eq!(glyphs, ["e\u{301}", "o\u{308}", "a\u{30a}\u{323}"]);
+        assert_eq!(render(&pi, &img), format!("{}\n", ramp));
+    }
+
+    #[test]
+    fn narrow_glyphs_are_padded_to_the_widest() {
+        let pi = PixtImg::new(Graphemes::row("e\u{301}👋🏿"), OutputType::text());
+        let img = gradient(2, 2);
+        assert_eq!(pi.cell_width(), 2);
+        assert_eq!(render(&pi, &img), "e\u{301} 👋🏿\n");
+    }
+
+    #[test]
+    fn html_escapes_the_whole_cluster() {
+        let pi = PixtImg::new(Graphemes::row("<\u{338}&"), OutputType::html()).with_fragment(true);
+        let out = render(&pi, &gradient(2, 2));
+        assert!(out.contains("&lt;\u{338}&amp;"), "{}", out);
+    }
+
+    /// A `width`×`height` checkerboard of `square`-pixel squares, white in
+    /// the top left.
+    fn checkerboard(width: u32, height: u32, square: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+            let v = if (x / square + y / square).is_multiple_of(2) {
+                255
+            } else {
+                0
+            };
+            Rgb([v, v, v])
+        }))
+    }
+
+    #[test]
+    fn braille_dots_follow_a_checkerboard() {
+        for threshold in [
+            BlockThreshold::Mean,
+            BlockThreshold::Otsu,
+            BlockThreshold::Fixed(128),
+        ] {
+            let pi = PixtImg::new(ImgStyle::BrailleDots, OutputType::text())
+                .with_block_threshold(threshold);
+            // Single pixels: dots 1, 3, 5 and 8 in every cell.
+            assert_eq!(
+                render(&pi, &checkerboard(8, 8, 1)),
+                "⢕⢕⢕⢕\n⢕⢕⢕⢕\n",
+                "{}",
+                threshold
+            );
+            // Squares of 2: the top half lit, then the bottom half.
+            assert_eq!(
+                render(&pi, &checkerboard(8, 8, 2)),
+                "⠛⣤⠛⣤\n⠛⣤⠛⣤\n",
+                "{}",
+                threshold
+            );
+            // Inverted, the dark pixels are the dots.
+            let pi = pi.with_invert(true);
+            assert_eq!(
+                render(&pi, &checkerboard(8, 8, 1)),
+                "⡪⡪⡪⡪\n⡪⡪⡪⡪\n",
+                "{}",
+                threshold
+            );
+            assert_eq!(
+                render(&pi, &checkerboard(8, 8, 2)),
+                "⣤⠛⣤⠛\n⣤⠛⣤⠛\n",
+                "{}",
+                threshold
+            );
+        }
+    }
+
+    #[test]
+    fn ansi16_depth_writes_the_exact_palette_entries() {
+        let out_type = OutputType::Term(ColorType::FgTopBgDown);
+        let pi = PixtImg::new(ImgStyle::HalfBlock, out_type).with_color_depth(ColorDepth::Ansi16);
+        for (top, bottom) in (0..16).zip((0..16).rev()) {
+            let img = DynamicImage::ImageRgb8(RgbImage::from_fn(1, 2, |_, y| {
+                Rgb(Palette::VGA[if y == 0 { top } else { bottom }])
+            }));
+            let out = render(&pi, &img);
+            let fg = format!("\x1b[{}m", ansi16_code(top as u8, false));
+            let bg = format!("\x1b[{}m", ansi16_code(bottom as u8, true));
+            assert!(
+                out.contains(&fg) && out.contains(&bg),
+                "{} over {}: {:?}",
+                top,
+                bottom,
+                out
+            );
+        }
+    }
+
+    /// A writer that fails once, on the write that would take it past
+    /// `fail_at` bytes, and takes everything after that.
+    struct FailingWriter {
+        written: Vec<u8>,
+        fail_at: usize,
+        failed: bool,
+    }
+
+    impl io::Write for FailingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if !self.failed && self.written.len() + buf.len() > self.fail_at {
+                self.failed = true;
+                return Err(io::Error::new(io::ErrorKind::BrokenPipe, "gone"));
+            }
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn failed_write_still_resets_the_colors() {
+        let pi = PixtImg::new(ImgStyle::Block, OutputType::Term(ColorType::FgTopBgDown));
+        let img = gradient(16, 8);
+        let full = render(&pi, &img).len();
+        for fail_at in (0..full).step_by(7) {
+            let mut out = FailingWriter {
+                written: Vec::new(),
+                fail_at,
+                failed: false,
+            };
+            let err = pi.print(&img, &mut out).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+            assert!(out.written.ends_with(b"\x1b[0m"), "failing at {}", fail_at);
+        }
     }
 }
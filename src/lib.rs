@@ -1,6 +1,25 @@
+pub mod anim;
+pub mod blocks;
+pub mod charset;
+pub mod color;
+pub mod dither;
+pub mod edges;
+#[cfg(all(feature = "ffi", not(target_arch = "wasm32")))]
+pub mod ffi;
+#[cfg(feature = "font")]
+pub mod font;
+pub mod grid;
+pub mod html;
 pub mod img;
+pub mod mask;
+pub mod metadata;
+pub mod metrics;
+pub mod preprocess;
+pub mod quantize;
 pub mod render;
+pub mod size;
 pub mod style;
+pub mod svg;
 
 #[cfg(target_arch = "wasm32")]
 use web_sys::{
@@ -12,8 +31,10 @@ use web_sys::{
 
 #[cfg(target_arch = "wasm32")]
 use crate::{
-    img::{OutputType, PixtImg},
-    render::render,
+    color::FilterPreset,
+    img::{Graphemes, OutputType, PixtImg, RenderScratch},
+    preprocess::{Luminance, trim_transparent},
+    size::{scale_dimension, validate_dimension},
     style::ImgStyle,
 };
 
@@ -79,28 +100,41 @@ impl MyPage {
             .unwrap()
             .dyn_into::<HtmlSelectElement>()
             .unwrap();
+        // Kept across conversions so re-rendering reuses its buffers.
+        let mut scratch = RenderScratch::new();
         let f = Closure::<dyn FnMut()>::new(move || {
             let global = web_sys::js_sys::global();
             let img_data = match js_sys::Reflect::get(&global, &JsValue::from_str("image_data")) {
                 Ok(v) => v,
                 Err(_) => return,
             };
+            let (width, height) = match (get_img_width(&document), get_img_height(&document)) {
+                (Ok(width), Ok(height)) => (width, height),
+                (Err(err), _) | (_, Err(err)) => {
+                    output.set_inner_text(&format!("Error: {}", err));
+                    return;
+                }
+            };
             output.set_inner_text("Converting........");
             let array_buffer = js_sys::Uint8Array::new(&img_data);
-            let img = image::load_from_memory(&array_buffer.to_vec())
-                .unwrap()
-                .resize(
-                    get_img_width(&document),
-                    get_img_height(&document),
-                    image::imageops::FilterType::CatmullRom,
-                );
-            let mut out = Vec::new();
+            let img = match image::load_from_memory(&array_buffer.to_vec()) {
+                Ok(img) => img,
+                Err(err) => {
+                    output.set_inner_text(&format!("Error: {}", err));
+                    return;
+                }
+            };
+            let img = match get_checked(&document, "trim") {
+                true => match trim_transparent(&img, 0) {
+                    Some(img) => img,
+                    None => {
+                        output.set_inner_text("Error: the image is fully transparent");
+                        return;
+                    }
+                },
+                false => img,
+            };
             let pix_img: PixtImg = match select.value().as_str() {
-                "ascii" => PixtImg::new(ImgStyle::Ascii, OutputType::text()),
-                "block" => PixtImg::new(ImgStyle::Block, OutputType::text()),
-                "pixel" => PixtImg::new(ImgStyle::Pixel, OutputType::text()),
-                "braills" => PixtImg::new(ImgStyle::Braills, OutputType::text()),
-                "dots" => PixtImg::new(ImgStyle::Dots, OutputType::text()),
                 "custom" => {
                     let e = document
                         .get_element_by_id("custom_ascii_input")
@@ -111,13 +145,28 @@ impl MyPage {
                     if v.is_empty() {
                         return;
                     }
-                    PixtImg::new(v.chars().collect::<Vec<char>>(), OutputType::text())
+                    PixtImg::new(Graphemes::row(&v), OutputType::text())
                 }
-                _ => unreachable!(),
+                name => match name.parse::<ImgStyle>() {
+                    Ok(style) => PixtImg::new(style, OutputType::text()),
+                    Err(err) => {
+                        output.set_inner_text(&format!("Error: {}", err));
+                        return;
+                    }
+                },
             };
-            render(&pix_img, &img, &mut out).unwrap();
-            let out = unsafe { str::from_utf8_unchecked(&out) };
-            output.set_inner_text(out);
+            let pix_img = pix_img
+                .with_filter(get_filter_preset(&document))
+                .with_luminance(get_luminance(&document))
+                .with_invert(get_checked(&document, "invert"));
+            let (px_cols, px_rows) = pix_img.cell_pixels();
+            let (width, height) = (width * px_cols, height * px_rows / 2);
+            let img = img.resize(width, height, image::imageops::FilterType::CatmullRom);
+            if let Err(err) = pix_img.render_into(&img, &mut scratch, std::io::sink()) {
+                output.set_inner_text(&format!("Error: {}", err));
+                return;
+            }
+            output.set_inner_text(&String::from_utf8_lossy(scratch.output()));
         });
         convert_btn.set_onclick(Some(f.as_ref().unchecked_ref()));
         f.forget();
@@ -142,7 +191,12 @@ impl MyPage {
             .unwrap()
             .dyn_into::<HtmlInputElement>()
             .unwrap();
-        let img_resolution = self.document.get_element_by_id("img_resolution").unwrap();
+        let img_resolution = self
+            .document
+            .get_element_by_id("img_resolution")
+            .unwrap()
+            .dyn_into::<HtmlElement>()
+            .unwrap();
         let image_input_clone = image_input.clone();
         let on_change = Closure::<dyn FnMut(_)>::new(move |_event: Event| {
             let img_width = img_width.clone();
@@ -164,7 +218,14 @@ impl MyPage {
                     .unwrap();
                     let result = reader_clone.result().unwrap();
                     let array_buffer = js_sys::Uint8Array::new(&result);
-                    let img = image::load_from_memory(&array_buffer.to_vec()).unwrap();
+                    // Left unset on failure, so converting does nothing.
+                    let img = match image::load_from_memory(&array_buffer.to_vec()) {
+                        Ok(img) => img,
+                        Err(err) => {
+                            img_resolution.set_inner_text(&format!("Error: {}", err));
+                            return;
+                        }
+                    };
                     let default_input_width = std::cmp::min(img.width(), 150);
                     js_sys::Reflect::set(&global, &JsValue::from_str("image_data"), &array_buffer)
                         .unwrap();
@@ -180,14 +241,16 @@ impl MyPage {
                         &JsValue::from_f64(img.height() as f64),
                     )
                     .unwrap();
-                    img_resolution.set_inner_html(
-                        format!("Image Resolution: {} x {}", img.width(), img.height()).as_str(),
-                    );
+                    img_resolution.set_inner_text(&format!(
+                        "Image Resolution: {} x {}",
+                        img.width(),
+                        img.height()
+                    ));
                     img_width.set_value(default_input_width.to_string().as_str());
+                    // Left empty if it overflows; converting then reports it.
                     img_height.set_value(
-                        ((default_input_width * img.height()) / img.width())
-                            .to_string()
-                            .as_str(),
+                        &scale_dimension(default_input_width, img.height(), img.width())
+                            .map_or_else(|_| String::new(), |v| v.to_string()),
                     );
                 });
                 reader.set_onload(Some(onload.as_ref().unchecked_ref()));
@@ -203,21 +266,57 @@ impl MyPage {
 }
 
 #[cfg(target_arch = "wasm32")]
-fn get_img_width(document: &Document) -> u32 {
-    let img_width = document
-        .get_element_by_id("widthInput")
+fn get_img_width(document: &Document) -> std::io::Result<u32> {
+    read_dimension(document, "widthInput", "width")
+}
+
+#[cfg(target_arch = "wasm32")]
+fn get_img_height(document: &Document) -> std::io::Result<u32> {
+    read_dimension(document, "heightInput", "height")
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read_dimension(document: &Document, id: &str, name: &str) -> std::io::Result<u32> {
+    let input = document
+        .get_element_by_id(id)
         .unwrap()
         .dyn_into::<HtmlInputElement>()
         .unwrap();
-    img_width.value().parse().unwrap_or_default()
+    let value = input.value().trim().parse().map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("{} must be a whole number", name),
+        )
+    })?;
+    validate_dimension(name, value)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn get_filter_preset(document: &Document) -> FilterPreset {
+    let select = document
+        .get_element_by_id("filter_preset")
+        .unwrap()
+        .dyn_into::<HtmlSelectElement>()
+        .unwrap();
+    select.value().parse().unwrap_or_default()
 }
 
 #[cfg(target_arch = "wasm32")]
-fn get_img_height(document: &Document) -> u32 {
-    let img_width = document
-        .get_element_by_id("heightInput")
+fn get_checked(document: &Document, id: &str) -> bool {
+    document
+        .get_element_by_id(id)
         .unwrap()
         .dyn_into::<HtmlInputElement>()
+        .unwrap()
+        .checked()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn get_luminance(document: &Document) -> Luminance {
+    let select = document
+        .get_element_by_id("luminance")
+        .unwrap()
+        .dyn_into::<HtmlSelectElement>()
         .unwrap();
-    img_width.value().parse().unwrap_or_default()
+    select.value().parse().unwrap_or_default()
 }
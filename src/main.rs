@@ -1,12 +1,14 @@
-#[cfg(not(target_arch = "wasm32"))]
-use clap::Parser;
-
+#[cfg(all(feature = "camera", target_os = "linux"))]
+mod camera;
 #[cfg(not(target_arch = "wasm32"))]
 mod cli;
+#[cfg(not(target_arch = "wasm32"))]
+mod man;
 
 fn main() {
     #[cfg(not(target_arch = "wasm32"))]
-    if let Err(err) = cli::Cli::parse().run() {
+    if let Err(err) = cli::Cli::parse_args().run() {
         eprintln!("{}", err);
+        std::process::exit(1);
     }
 }
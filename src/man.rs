@@ -0,0 +1,110 @@
+//! Man page generated from the real [`Cli`] definition, so it can't drift
+//! from the actual flags. Sections clap knows nothing about are written by
+//! hand below.
+
+use std::io::{self, Write};
+
+use clap::CommandFactory;
+
+use crate::cli::Cli;
+
+/// Invocations listed under EXAMPLES, as `(arguments, description)`.
+pub const EXAMPLES: &[(&str, &str)] = &[
+    (
+        "pixt -c photo.png",
+        "Print an image in color, sized to the terminal.",
+    ),
+    (
+        "pixt -s ascii -w 60 photo.png",
+        "Render 60 columns of ASCII characters.",
+    ),
+    (
+        "pixt -s custom ' .:-=+*#%@' photo.png",
+        "Use custom characters, ordered from darkest to brightest.",
+    ),
+    (
+        "pixt -c -o photo.html photo.png",
+        "Write a colored HTML page.",
+    ),
+    (
+        "pixt -c -o photo.ans -o photo.html photo.png",
+        "Render once and write both ANSI colored text and an HTML page.",
+    ),
+    (
+        "pixt -c --reveal=16 --reveal-order center-out photo.png",
+        "Animate the image appearing from the center, 16 cells per frame.",
+    ),
+    (
+        "pixt charset save -s block block.toml",
+        "Save the block style as an editable charset file.",
+    ),
+    (
+        "pixt --charset-file block.toml photo.png",
+        "Render with the characters of a charset file.",
+    ),
+];
+
+const CHARSET_FORMAT: &str = r#".SH "CHARSET FORMAT"
+A charset file given to \fB\-\-charset\-file\fR or \fB\-s from\-file\fR is read in one of two formats.
+.PP
+The plain format holds one row of characters per line, ordered from darkest to brightest.
+Surrounding whitespace is trimmed and blank lines are skipped.
+With several rows the charset is two dimensional: the top pixel of a cell picks the character within a row and the bottom pixel picks the row.
+.PP
+Files ending in \fI.toml\fR, or whose first meaningful line is a \fIkey = value\fR pair, use the TOML format:
+.PP
+.RS 4
+.nf
+name = "ascii"
+rows = [" .\-~+*%#@"]
+thresholds = [20, 50, 80, 110, 140, 170, 200, 230]
+orientation = "columns"
+cell = { w = 1, h = 2 }
+wide = false
+.fi
+.RE
+.TP
+\fBrows\fR
+Characters ordered from darkest to brightest, one string per row.
+The only required key.
+.TP
+\fBthresholds\fR
+Ascending intensity boundaries between the characters of a row: a cell with intensity below the \fIi\fR\-th value uses character \fIi\fR.
+Must have one entry fewer than each row has characters.
+Evenly spaced when omitted.
+.TP
+\fBorientation\fR
+\fIcolumns\fR (default) lets the top pixel pick the column and the bottom pixel the row, \fIrows\fR the other way round.
+.TP
+\fBcell\fR
+Number of source pixels covered by one character, \fI{ w = 1, h = 2 }\fR by default.
+.TP
+\fBwide\fR
+Print every character twice so each rendered pixel is roughly square, like \fB\-\-wide\fR.
+"#;
+
+/// Escapes `text` for use in a roff paragraph.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\e").replace('-', "\\-")
+}
+
+/// Writes the roff man page for `pixt`.
+pub fn write_manpage<W: Write>(out: &mut W) -> io::Result<()> {
+    let man = clap_mangen::Man::new(Cli::command());
+    man.render_title(out)?;
+    man.render_name_section(out)?;
+    man.render_synopsis_section(out)?;
+    man.render_description_section(out)?;
+    man.render_options_section(out)?;
+    man.render_subcommands_section(out)?;
+    writeln!(out, ".SH EXAMPLES")?;
+    for (args, description) in EXAMPLES {
+        writeln!(
+            out,
+            ".TP\n\\fB{}\\fR\n{}",
+            escape(args),
+            escape(description)
+        )?;
+    }
+    out.write_all(CHARSET_FORMAT.as_bytes())
+}
@@ -0,0 +1,73 @@
+//! Masks that cut the art into arbitrary shapes.
+
+use image::{DynamicImage, GrayImage};
+
+use crate::preprocess::{Luminance, intensity_plane};
+
+/// Mask brightness below which cells are hidden, unless set otherwise with
+/// [`Mask::with_threshold`].
+pub const DEFAULT_MASK_THRESHOLD: u8 = 128;
+
+/// A grayscale stencil laid over the source: cells drawn over mask pixels
+/// darker than [`threshold`](Self::threshold) are replaced by
+/// [`fill`](Self::fill), or cells over lighter ones when
+/// [`invert`](Self::invert)ed.
+///
+/// The mask has to be sampled exactly like the image it covers, so that one
+/// mask pixel lies under each source pixel; see
+/// [`PixtImg::with_mask`](crate::img::PixtImg::with_mask).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mask {
+    plane: GrayImage,
+    /// Brightness from which a mask pixel lets the art through.
+    pub threshold: u8,
+    /// Hides the light parts of the mask instead of the dark ones.
+    pub invert: bool,
+    /// Drawn uncolored in place of hidden cells; a space leaves them blank.
+    pub fill: char,
+}
+
+impl Mask {
+    /// A mask from the brightness of `img` (by the default [`Luminance`]),
+    /// hiding cells over its dark parts
+    /// with spaces.
+    pub fn new(img: &DynamicImage) -> Self {
+        Self {
+            plane: intensity_plane(img, Luminance::default()),
+            threshold: DEFAULT_MASK_THRESHOLD,
+            invert: false,
+            fill: ' ',
+        }
+    }
+    /// Sets the brightness from which the mask lets the art through.
+    pub fn with_threshold(mut self, threshold: u8) -> Self {
+        self.threshold = threshold;
+        self
+    }
+    /// Hides the light parts of the mask instead of the dark ones.
+    pub fn with_invert(mut self, invert: bool) -> Self {
+        self.invert = invert;
+        self
+    }
+    /// Sets the character hidden cells are drawn with.
+    pub fn with_fill(mut self, fill: char) -> Self {
+        self.fill = fill;
+        self
+    }
+    /// Width and height of the mask in pixels.
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.plane.dimensions()
+    }
+    /// Whether the cell drawn from the source pixels at `top` and `bottom`
+    /// (`(x, y)` each) is hidden. The two mask pixels are averaged; ones
+    /// outside the mask are left out, and a cell entirely outside it stays.
+    pub fn hides(&self, top: (u32, u32), bottom: (u32, u32)) -> bool {
+        let luma = |(x, y): (u32, u32)| self.plane.get_pixel_checked(x, y).map(|p| p.0[0] as u32);
+        let luma = match (luma(top), luma(bottom)) {
+            (Some(a), Some(b)) => (a + b) / 2,
+            (Some(v), None) | (None, Some(v)) => v,
+            (None, None) => return false,
+        };
+        (luma < self.threshold as u32) != self.invert
+    }
+}
@@ -0,0 +1,97 @@
+//! Settings recorded at the top of generated files, so a render can be
+//! traced back to how it was made.
+
+use std::io;
+
+use serde::{Deserialize, Serialize};
+
+use crate::img::OutputType;
+
+/// Version of pixt written to [`Metadata::version`].
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// How an output was generated. Written as TOML inside a comment, see
+/// [`write_comment`](Self::write_comment):
+///
+/// ```toml
+/// version = "0.1.0"
+/// source = "cat.png"
+/// source_size = [640, 480]
+/// size = [80, 30]
+/// style = "pixel"
+/// color = "FgTopBgDown"
+/// args = ["cat.png", "-c", "-o", "cat.html"]
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Metadata {
+    /// Version of pixt that wrote the output.
+    pub version: String,
+    /// File name of the source image.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Width and height of the source image in pixels.
+    pub source_size: [u32; 2],
+    /// Width and height of the output in characters.
+    pub size: [u32; 2],
+    pub style: String,
+    pub color: String,
+    /// Command-line arguments the output was generated with.
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+impl Metadata {
+    /// Parses the TOML format.
+    pub fn from_toml(input: &str) -> io::Result<Self> {
+        toml::from_str(input).map_err(|err| invalid(err.to_string()))
+    }
+
+    /// Serializes the metadata to TOML.
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("Metadata is always representable as TOML")
+    }
+
+    /// Writes the metadata as a comment of `out_type`: `<!-- ... -->` for
+    /// HTML and SVG, `# ` lines for text and terminal output.
+    ///
+    /// Comments in markup can't contain `--`, so it is written as `- -`.
+    pub fn write_comment(&self, out_type: &OutputType, mut out: impl io::Write) -> io::Result<()> {
+        let toml = self.to_toml();
+        match out_type {
+            OutputType::Html(_) | OutputType::Svg(_) => {
+                write!(out, "<!--\n{}-->\n", toml.replace("--", "- -"))
+            }
+            OutputType::Text(_) | OutputType::Term(_) => {
+                for line in toml.lines() {
+                    writeln!(out, "# {}", line)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Reads back the comment [`write_comment`](Self::write_comment) put at
+    /// the start of `text`.
+    pub fn from_comment(text: &str) -> io::Result<Self> {
+        if let Some(rest) = text.strip_prefix("<!--") {
+            let end = rest
+                .find("-->")
+                .ok_or_else(|| invalid("unterminated metadata comment"))?;
+            return Self::from_toml(&rest[..end].replace("- -", "--"));
+        }
+        let toml: String = text
+            .lines()
+            .map_while(|line| line.strip_prefix('#'))
+            .map(|line| format!("{}\n", line.strip_prefix(' ').unwrap_or(line)))
+            .collect();
+        if toml.is_empty() {
+            return Err(invalid("no metadata comment found"));
+        }
+        Self::from_toml(&toml)
+    }
+}
+
+fn invalid<E: Into<Box<dyn std::error::Error + Send + Sync>>>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
@@ -0,0 +1,232 @@
+use std::fmt;
+
+use image::DynamicImage;
+
+use crate::preprocess::{Luminance, luma_row};
+
+/// Side length of the windows SSIM is computed over.
+const SSIM_WINDOW: usize = 8;
+
+/// How closely a render approximates its source.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quality {
+    /// Mean squared error of the intensities, `0.0` is a perfect match.
+    pub mse: f64,
+    /// Mean structural similarity, `1.0` is a perfect match.
+    pub ssim: f64,
+}
+
+impl fmt::Display for Quality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MSE {:.2}, SSIM {:.4}", self.mse, self.ssim)
+    }
+}
+
+impl Quality {
+    /// Compares two `width`×`height` intensity planes (values in `0..=255`).
+    pub fn compare(source: &[f64], render: &[f64], width: usize, height: usize) -> Self {
+        Self {
+            mse: mse(source, render),
+            ssim: ssim(source, render, width, height),
+        }
+    }
+}
+
+/// Number of buckets of an [`IntensityHistogram`], each 8 intensities wide.
+pub const HISTOGRAM_BUCKETS: usize = 32;
+
+/// Percentiles reported by [`IntensityHistogram`].
+pub const PERCENTILES: [u8; 5] = [5, 25, 50, 75, 95];
+
+/// Distribution of the pixel intensities of an image, for judging how the
+/// brightness ramp will be used before rendering.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntensityHistogram {
+    /// Pixel counts of equal intensity ranges, darkest first.
+    pub buckets: [u64; HISTOGRAM_BUCKETS],
+    pub min: u8,
+    pub max: u8,
+    pub mean: f64,
+    /// The intensity at each of [`PERCENTILES`].
+    pub percentiles: [u8; PERCENTILES.len()],
+}
+
+impl IntensityHistogram {
+    /// Measures `img` with intensities computed by `formula`. An empty
+    /// image gives all zeros.
+    pub fn of(img: &DynamicImage, formula: Luminance) -> Self {
+        let rgb = img.to_rgb8();
+        let w = rgb.width() as usize;
+        let mut counts = [0u64; 256];
+        let mut row = vec![0; w];
+        if w > 0 {
+            for src in rgb.as_raw().chunks_exact(w * 3) {
+                luma_row(src, &mut row, formula);
+                for v in &row {
+                    counts[*v as usize] += 1;
+                }
+            }
+        }
+        Self::from_counts(&counts)
+    }
+
+    /// Builds the histogram from the number of pixels at every intensity.
+    pub fn from_counts(counts: &[u64; 256]) -> Self {
+        let total: u64 = counts.iter().sum();
+        let mut buckets = [0; HISTOGRAM_BUCKETS];
+        for (v, count) in counts.iter().enumerate() {
+            buckets[v * HISTOGRAM_BUCKETS / 256] += count;
+        }
+        let present = |v: &usize| counts[*v] > 0;
+        let min = (0..256).find(present).unwrap_or(0) as u8;
+        let max = (0..256).rev().find(present).unwrap_or(0) as u8;
+        let sum: u64 = (0..256).map(|v| v as u64 * counts[v]).sum();
+        let mean = sum as f64 / total.max(1) as f64;
+        let percentiles = PERCENTILES.map(|p| {
+            // The smallest intensity at least `p` percent of the pixels
+            // don't exceed.
+            let rank = (total * p as u64).div_ceil(100).max(1);
+            let mut seen = 0;
+            (0..256)
+                .find(|v| {
+                    seen += counts[*v];
+                    seen >= rank
+                })
+                .unwrap_or(0) as u8
+        });
+        Self {
+            buckets,
+            min,
+            max,
+            mean,
+            percentiles,
+        }
+    }
+}
+
+/// Mean squared error between two equally sized planes.
+pub fn mse(a: &[f64], b: &[f64]) -> f64 {
+    if a.is_empty() {
+        return 0.0;
+    }
+    a.iter().zip(b).map(|(x, y)| (x - y).powi(2)).sum::<f64>() / a.len() as f64
+}
+
+/// Mean SSIM of two `width`×`height` planes with values in `0..=255`,
+/// computed over 8×8 windows with a stride of 4 (or one window covering the
+/// whole plane when it is smaller than that).
+pub fn ssim(a: &[f64], b: &[f64], width: usize, height: usize) -> f64 {
+    if width == 0 || height == 0 {
+        return 1.0;
+    }
+    let (ww, wh) = (SSIM_WINDOW.min(width), SSIM_WINDOW.min(height));
+    let step = (SSIM_WINDOW / 2).max(1);
+    let mut total = 0.0;
+    let mut count = 0;
+    let mut y = 0;
+    loop {
+        let mut x = 0;
+        loop {
+            total += ssim_window(a, b, width, x, y, ww, wh);
+            count += 1;
+            if x + ww >= width {
+                break;
+            }
+            x = (x + step).min(width - ww);
+        }
+        if y + wh >= height {
+            break;
+        }
+        y = (y + step).min(height - wh);
+    }
+    total / count as f64
+}
+
+fn ssim_window(
+    a: &[f64],
+    b: &[f64],
+    stride: usize,
+    x0: usize,
+    y0: usize,
+    w: usize,
+    h: usize,
+) -> f64 {
+    const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+    let n = (w * h) as f64;
+    let pixels = || {
+        (y0..y0 + h)
+            .flat_map(move |y| (x0..x0 + w).map(move |x| (a[y * stride + x], b[y * stride + x])))
+    };
+    let (mut mean_a, mut mean_b) = (0.0, 0.0);
+    for (va, vb) in pixels() {
+        mean_a += va;
+        mean_b += vb;
+    }
+    mean_a /= n;
+    mean_b /= n;
+    let (mut var_a, mut var_b, mut cov) = (0.0, 0.0, 0.0);
+    for (va, vb) in pixels() {
+        var_a += (va - mean_a).powi(2);
+        var_b += (vb - mean_b).powi(2);
+        cov += (va - mean_a) * (vb - mean_b);
+    }
+    var_a /= n;
+    var_b /= n;
+    cov /= n;
+    ((2.0 * mean_a * mean_b + C1) * (2.0 * cov + C2))
+        / ((mean_a.powi(2) + mean_b.powi(2) + C1) * (var_a + var_b + C2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Vertical stripes, alternating black and white columns.
+    const STRIPES: [f64; 4] = [0.0, 255.0, 0.0, 255.0];
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn identical_planes_match_perfectly() {
+        let plane: Vec<f64> = (0..20 * 13).map(|i| (i * 37 % 256) as f64).collect();
+        let quality = Quality::compare(&plane, &plane, 20, 13);
+        assert_eq!(quality.mse, 0.0);
+        assert!(close(quality.ssim, 1.0), "{}", quality.ssim);
+        let flat = [42.0; 16];
+        assert!(close(ssim(&flat, &flat, 4, 4), 1.0));
+    }
+
+    #[test]
+    fn known_pairs_score_as_computed_by_hand() {
+        // Same structure 50 brighter: only the luminance term drops,
+        // (2·127.5·177.5 + C1) / (127.5² + 177.5² + C1).
+        let brighter = STRIPES.map(|v| v + 50.0);
+        assert!(close(ssim(&STRIPES, &brighter, 2, 2), 0.9476648062726452));
+        assert_eq!(mse(&STRIPES, &brighter), 2500.0);
+        // Inverted: same means and variances, perfectly anti-correlated,
+        // (C2 - 2·127.5²) / (2·127.5² + C2).
+        let inverted = STRIPES.map(|v| 255.0 - v);
+        assert!(close(ssim(&STRIPES, &inverted, 2, 2), -0.9964064683569576));
+        assert_eq!(mse(&STRIPES, &inverted), 255.0 * 255.0);
+    }
+
+    #[test]
+    fn windows_are_averaged() {
+        // The left quarter of a 16×8 plane differs, so of the three 8×8 windows
+        // at x = 0, 4 and 8 only the last one matches.
+        let a: Vec<f64> = (0..16 * 8).map(|i| ((i % 16) * 16) as f64).collect();
+        let b: Vec<f64> = a
+            .iter()
+            .enumerate()
+            .map(|(i, v)| if i % 16 < 4 { 255.0 - v } else { *v })
+            .collect();
+        let whole = ssim(&a, &b, 16, 8);
+        let windows = [0, 4, 8].map(|x| ssim_window(&a, &b, 16, x, 0, 8, 8));
+        assert!(close(windows[2], 1.0));
+        assert!(close(whole, windows.iter().sum::<f64>() / 3.0));
+        assert!(whole < 1.0);
+    }
+}
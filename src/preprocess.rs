@@ -0,0 +1,458 @@
+use std::{fmt, io, str::FromStr};
+
+use image::{DynamicImage, GrayImage, Luma, Rgb, RgbImage, RgbaImage, imageops::FilterType};
+
+use crate::img::parse_name;
+
+/// Downsamples `img` to `width`×`height` where every output pixel is the
+/// true average of all source pixels it covers.
+///
+/// Unlike the interpolating filters this never skips source pixels, so fine
+/// textures average out instead of aliasing. Column sums are accumulated per
+/// output row and turned into a running (prefix) sum, so the cost is linear
+/// in the source size and only one source row of sums is kept in memory.
+pub fn area_resize(img: &DynamicImage, width: u32, height: u32) -> DynamicImage {
+    let src = img.to_rgba8();
+    let (sw, sh) = src.dimensions();
+    let (width, height) = (width.max(1), height.max(1));
+    let mut out = RgbaImage::new(width, height);
+    if sw == 0 || sh == 0 {
+        return DynamicImage::ImageRgba8(out);
+    }
+    let span = |i: u32, n: u32, src_len: u32| -> (u32, u32) {
+        let start = (i as u64 * src_len as u64 / n as u64) as u32;
+        let end = ((i as u64 + 1) * src_len as u64).div_ceil(n as u64) as u32;
+        (start.min(src_len - 1), end.clamp(start + 1, src_len))
+    };
+    let raw = src.as_raw();
+    let stride = sw as usize * 4;
+    // prefix[x * 4 + c] holds the sum of channel `c` over columns `0..x`.
+    let mut prefix = vec![0u64; (sw as usize + 1) * 4];
+    for y in 0..height {
+        let (y0, y1) = span(y, height, sh);
+        prefix.fill(0);
+        for row in raw[y0 as usize * stride..y1 as usize * stride].chunks_exact(stride) {
+            for (i, v) in row.iter().enumerate() {
+                prefix[i + 4] += *v as u64;
+            }
+        }
+        for i in 4..prefix.len() {
+            prefix[i] += prefix[i - 4];
+        }
+        for x in 0..width {
+            let (x0, x1) = span(x, width, sw);
+            let area = (x1 - x0) as u64 * (y1 - y0) as u64;
+            let p = out.get_pixel_mut(x, y);
+            for c in 0..4 {
+                let sum = prefix[x1 as usize * 4 + c] - prefix[x0 as usize * 4 + c];
+                p.0[c] = (sum / area) as u8;
+            }
+        }
+    }
+    DynamicImage::ImageRgba8(out)
+}
+
+/// Averages `block_w`×`block_h` blocks of source pixels into single cells,
+/// without any resampling filter.
+///
+/// Every block becomes one pixel column and two pixel rows of the result (the
+/// average of its top half and of its bottom half), which is exactly what one
+/// output character samples. Blocks one pixel tall use the same row for both
+/// halves. Partial blocks at the right and bottom edges are averaged over the
+/// pixels they do cover, or dropped when `truncate` is set. Returns `None`
+/// when no block remains.
+pub fn block_average(
+    img: &DynamicImage,
+    block_w: u32,
+    block_h: u32,
+    truncate: bool,
+) -> Option<DynamicImage> {
+    let src = img.to_rgba8();
+    let (sw, sh) = src.dimensions();
+    let (block_w, block_h) = (block_w.max(1), block_h.max(1));
+    let (cols, rows) = if truncate {
+        (sw / block_w, sh / block_h)
+    } else {
+        (sw.div_ceil(block_w), sh.div_ceil(block_h))
+    };
+    if cols == 0 || rows == 0 {
+        return None;
+    }
+    let average = |x0: u32, x1: u32, y0: u32, y1: u32| -> [u8; 4] {
+        let mut sum = [0u64; 4];
+        for y in y0..y1 {
+            for x in x0..x1 {
+                for (s, v) in sum.iter_mut().zip(src.get_pixel(x, y).0) {
+                    *s += v as u64;
+                }
+            }
+        }
+        let area = ((x1 - x0) as u64 * (y1 - y0) as u64).max(1);
+        sum.map(|s| (s / area) as u8)
+    };
+    let mut out = RgbaImage::new(cols, rows * 2);
+    for row in 0..rows {
+        let y0 = row * block_h;
+        let y1 = (y0 + block_h).min(sh);
+        let mid = if y1 - y0 > 1 { y0 + (y1 - y0) / 2 } else { y1 };
+        for col in 0..cols {
+            let x0 = col * block_w;
+            let x1 = (x0 + block_w).min(sw);
+            let top = average(x0, x1, y0, mid);
+            let bottom = if mid < y1 {
+                average(x0, x1, mid, y1)
+            } else {
+                top
+            };
+            out.put_pixel(col, row * 2, image::Rgba(top));
+            out.put_pixel(col, row * 2 + 1, image::Rgba(bottom));
+        }
+    }
+    Some(DynamicImage::ImageRgba8(out))
+}
+
+/// How [`blend`] combines a channel of the top image with the base.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    /// The top image as is.
+    #[default]
+    Normal,
+    /// Product of both: darkens, white leaves the base unchanged.
+    Multiply,
+    /// Inverse product of the inverses: lightens, black leaves the base
+    /// unchanged.
+    Screen,
+    /// Multiply where the base is dark, screen where it is light, which
+    /// raises contrast.
+    Overlay,
+}
+
+impl BlendMode {
+    /// Blends channel `top` onto channel `base`.
+    pub fn apply(&self, base: u8, top: u8) -> u8 {
+        let (b, t) = (base as u32, top as u32);
+        let multiply = |b: u32, t: u32| (b * t + 127) / 255;
+        let screen = |b: u32, t: u32| 255 - multiply(255 - b, 255 - t);
+        (match self {
+            Self::Normal => t,
+            Self::Multiply => multiply(b, t),
+            Self::Screen => screen(b, t),
+            Self::Overlay if b < 128 => multiply(2 * b, t),
+            Self::Overlay => screen(2 * b - 255, t),
+        }) as u8
+    }
+}
+
+/// Bounding box `(x, y, width, height)` of the pixels of `img` with an alpha
+/// above `threshold`; the whole image when it has no alpha channel. `None`
+/// when no pixel is that opaque.
+pub fn opaque_bounds(img: &DynamicImage, threshold: u8) -> Option<(u32, u32, u32, u32)> {
+    let (w, h) = (img.width(), img.height());
+    if !img.color().has_alpha() {
+        return (w > 0 && h > 0).then_some((0, 0, w, h));
+    }
+    let rgba = img.to_rgba8();
+    let (mut x0, mut y0, mut x1, mut y1) = (u32::MAX, u32::MAX, 0, 0);
+    for (x, y, px) in rgba.enumerate_pixels() {
+        if px.0[3] > threshold {
+            (x0, y0) = (x0.min(x), y0.min(y));
+            (x1, y1) = (x1.max(x), y1.max(y));
+        }
+    }
+    (x0 <= x1).then(|| (x0, y0, x1 - x0 + 1, y1 - y0 + 1))
+}
+
+/// `img` cropped to its [`opaque_bounds`], so transparent margins don't
+/// take up cells. `None` when no pixel has an alpha above `threshold`.
+pub fn trim_transparent(img: &DynamicImage, threshold: u8) -> Option<DynamicImage> {
+    let (x, y, w, h) = opaque_bounds(img, threshold)?;
+    Some(match (w, h) == (img.width(), img.height()) {
+        true => img.clone(),
+        false => img.crop_imm(x, y, w, h),
+    })
+}
+
+/// Composites the straight-alpha `color` over the opaque `backdrop`.
+#[inline(always)]
+pub fn flatten_pixel([r, g, b, a]: [u8; 4], backdrop: [u8; 3]) -> [u8; 3] {
+    let a = a as u32;
+    let mix = |c: u8, bg: u8| ((c as u32 * a + bg as u32 * (255 - a) + 127) / 255) as u8;
+    let [br, bg, bb] = backdrop;
+    [mix(r, br), mix(g, bg), mix(b, bb)]
+}
+
+/// `img` composited over the opaque `backdrop`, so transparent parts take its
+/// color instead of whatever RGB they happen to store.
+pub fn flatten(img: &DynamicImage, backdrop: [u8; 3]) -> RgbImage {
+    let rgba = img.to_rgba8();
+    RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        Rgb(flatten_pixel(rgba.get_pixel(x, y).0, backdrop))
+    })
+}
+
+/// Composites `top` over `base` with `mode` at `opacity` (`0.0..=1.0`),
+/// stretching `top` to the size of `base` first.
+///
+/// Transparent parts of `top` let the base through; the result keeps the
+/// alpha of `base`.
+pub fn blend(
+    base: &DynamicImage,
+    top: &DynamicImage,
+    mode: BlendMode,
+    opacity: f32,
+) -> DynamicImage {
+    let mut out = base.to_rgba8();
+    let (w, h) = out.dimensions();
+    let top = match (top.width(), top.height()) == (w, h) {
+        true => top.to_rgba8(),
+        false => top.resize_exact(w, h, FilterType::CatmullRom).to_rgba8(),
+    };
+    let opacity = opacity.clamp(0.0, 1.0);
+    for (b, t) in out.pixels_mut().zip(top.pixels()) {
+        let alpha = opacity * t.0[3] as f32 / 255.0;
+        for c in 0..3 {
+            let blended = mode.apply(b.0[c], t.0[c]) as f32;
+            b.0[c] = (b.0[c] as f32 * (1.0 - alpha) + blended * alpha).round() as u8;
+        }
+    }
+    DynamicImage::ImageRgba8(out)
+}
+
+/// Side length, in tiles, of the grid [`clahe`] equalizes over.
+pub const CLAHE_TILES: u32 = 8;
+
+/// How RGB channels are weighted into a single intensity.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Luminance {
+    /// Plain average of the three channels. Saturated blues come out far
+    /// brighter, and greens darker, than they look.
+    Average,
+    /// ITU-R BT.601 luma weights.
+    #[default]
+    Rec601,
+    /// ITU-R BT.709 luma weights.
+    Rec709,
+}
+
+impl Luminance {
+    pub const ALL: [Self; 3] = [Self::Average, Self::Rec601, Self::Rec709];
+    /// Canonical name, as used by [`Display`](fmt::Display) and
+    /// [`FromStr`].
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Average => "average",
+            Self::Rec601 => "rec601",
+            Self::Rec709 => "rec709",
+        }
+    }
+    /// Channel weights in 8-bit fixed point, summing to 256.
+    const fn weights(&self) -> Option<[u16; 3]> {
+        match self {
+            Self::Average => None,
+            Self::Rec601 => Some([77, 150, 29]),
+            Self::Rec709 => Some([54, 183, 19]),
+        }
+    }
+    /// Intensity of a single pixel, identical to what [`luma_row`] computes.
+    #[inline(always)]
+    pub fn luma(&self, [r, g, b]: [u8; 3]) -> u8 {
+        match self.weights() {
+            None => ((r as u16 + g as u16 + b as u16) / 3) as u8,
+            Some([wr, wg, wb]) => {
+                ((r as u16 * wr + g as u16 * wg + b as u16 * wb + 128) >> 8) as u8
+            }
+        }
+    }
+}
+
+impl fmt::Display for Luminance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Also accepts `bt601` and `bt709`.
+impl FromStr for Luminance {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Self> {
+        parse_name(
+            "luminance",
+            s,
+            &Self::ALL,
+            Self::name,
+            &[("bt601", Self::Rec601), ("bt709", Self::Rec709)],
+        )
+    }
+}
+
+/// Computes the intensity of every pixel of a packed RGB row into `out`.
+///
+/// Only integer arithmetic is used and the formula is picked once per row,
+/// so the loops autovectorize. Stops at whichever of `rgb` (in pixels) and
+/// `out` is shorter.
+pub fn luma_row(rgb: &[u8], out: &mut [u8], formula: Luminance) {
+    let pixels = rgb.chunks_exact(3).zip(out.iter_mut());
+    match formula.weights() {
+        None => {
+            for (px, v) in pixels {
+                *v = ((px[0] as u16 + px[1] as u16 + px[2] as u16) / 3) as u8;
+            }
+        }
+        Some([wr, wg, wb]) => {
+            for (px, v) in pixels {
+                *v = ((px[0] as u16 * wr + px[1] as u16 * wg + px[2] as u16 * wb + 128) >> 8) as u8;
+            }
+        }
+    }
+}
+
+/// Intensity plane of `img` by `formula`; with the formula of the
+/// [`PixtImg`](crate::img::PixtImg), the values characters are picked by.
+pub fn intensity_plane(img: &DynamicImage, formula: Luminance) -> GrayImage {
+    if let DynamicImage::ImageLuma8(gray) = img {
+        return gray.clone();
+    }
+    let rgb = img.to_rgb8();
+    let mut out = GrayImage::new(rgb.width(), rgb.height());
+    let w = rgb.width() as usize;
+    if w > 0 {
+        for (src, dst) in rgb
+            .as_raw()
+            .chunks_exact(w * 3)
+            .zip(out.chunks_exact_mut(w))
+        {
+            luma_row(src, dst, formula);
+        }
+    }
+    out
+}
+
+/// Contrast-limited adaptive histogram equalization of `gray`.
+///
+/// The plane is split into a grid of up to 8×8 tiles, each tile's histogram
+/// is clipped at `clip_limit` times the mean bin height (the excess spread
+/// evenly over all bins) and turned into an equalizing mapping. Every pixel
+/// then bilinearly interpolates between the mappings of the four nearest
+/// tile centers, so tile borders don't show.
+pub fn clahe(gray: &GrayImage, clip_limit: f32) -> GrayImage {
+    let (w, h) = gray.dimensions();
+    if w == 0 || h == 0 {
+        return gray.clone();
+    }
+    let (tx, ty) = (CLAHE_TILES.min(w), CLAHE_TILES.min(h));
+    let tile_span = |i: u32, n: u32, len: u32| (i * len / n, (i + 1) * len / n);
+    let mut maps = Vec::with_capacity((tx * ty) as usize);
+    for j in 0..ty {
+        let (y0, y1) = tile_span(j, ty, h);
+        for i in 0..tx {
+            let (x0, x1) = tile_span(i, tx, w);
+            let mut hist = [0u32; 256];
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    hist[gray.get_pixel(x, y).0[0] as usize] += 1;
+                }
+            }
+            let area = (x1 - x0) * (y1 - y0);
+            maps.push(equalize(&mut hist, area, clip_limit));
+        }
+    }
+    // Tile centers along an axis, in pixel coordinates.
+    let center = |i: u32, n: u32, len: u32| {
+        let (a, b) = tile_span(i, n, len);
+        (a + b) as f32 / 2.0
+    };
+    // Neighbouring tiles around `p` and the weight of the second one.
+    let neighbours = |p: u32, n: u32, len: u32| -> (u32, u32, f32) {
+        let p = p as f32 + 0.5;
+        if p <= center(0, n, len) {
+            return (0, 0, 0.0);
+        }
+        for i in 0..n - 1 {
+            let (c0, c1) = (center(i, n, len), center(i + 1, n, len));
+            if p <= c1 {
+                return (i, i + 1, (p - c0) / (c1 - c0));
+            }
+        }
+        (n - 1, n - 1, 0.0)
+    };
+    GrayImage::from_fn(w, h, |x, y| {
+        let v = gray.get_pixel(x, y).0[0] as usize;
+        let (i0, i1, fx) = neighbours(x, tx, w);
+        let (j0, j1, fy) = neighbours(y, ty, h);
+        let m = |i: u32, j: u32| maps[(j * tx + i) as usize][v] as f32;
+        let top = m(i0, j0) * (1.0 - fx) + m(i1, j0) * fx;
+        let bottom = m(i0, j1) * (1.0 - fx) + m(i1, j1) * fx;
+        Luma([(top * (1.0 - fy) + bottom * fy).round().clamp(0.0, 255.0) as u8])
+    })
+}
+
+/// Clips `hist` (of `area` samples) and returns its cumulative mapping.
+fn equalize(hist: &mut [u32; 256], area: u32, clip_limit: f32) -> [u8; 256] {
+    let limit = ((clip_limit.max(1.0) * area as f32 / 256.0).ceil() as u32).max(1);
+    let mut excess = 0;
+    for bin in hist.iter_mut() {
+        if *bin > limit {
+            excess += *bin - limit;
+            *bin = limit;
+        }
+    }
+    let (share, rest) = (excess / 256, (excess % 256) as usize);
+    for (i, bin) in hist.iter_mut().enumerate() {
+        *bin += share + (i < rest) as u32;
+    }
+    let mut map = [0u8; 256];
+    let mut sum = 0u64;
+    for (v, bin) in map.iter_mut().zip(hist.iter()) {
+        sum += *bin as u64;
+        *v = (sum * 255 / area.max(1) as u64) as u8;
+    }
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgb, RgbImage};
+
+    use super::*;
+
+    #[test]
+    fn luma_row_matches_the_per_pixel_formula() {
+        // Every third value of each channel, and the top end.
+        let levels: Vec<u8> = (0..=255).step_by(3).chain([254, 255]).collect();
+        let mut rgb = Vec::new();
+        for &r in &levels {
+            for &g in &levels {
+                for &b in &levels {
+                    rgb.extend_from_slice(&[r, g, b]);
+                }
+            }
+        }
+        for formula in Luminance::ALL {
+            let mut out = vec![0; rgb.len() / 3];
+            luma_row(&rgb, &mut out, formula);
+            for (px, v) in rgb.chunks_exact(3).zip(&out) {
+                assert_eq!(
+                    *v,
+                    formula.luma([px[0], px[1], px[2]]),
+                    "{} of {:?}",
+                    formula,
+                    px
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn intensity_plane_matches_the_per_pixel_formula() {
+        let img = RgbImage::from_fn(37, 5, |x, y| {
+            Rgb([(x * 7) as u8, (y * 51) as u8, (x * y * 13) as u8])
+        });
+        for formula in Luminance::ALL {
+            let plane = intensity_plane(&DynamicImage::ImageRgb8(img.clone()), formula);
+            for (x, y, px) in img.enumerate_pixels() {
+                assert_eq!(plane.get_pixel(x, y).0[0], formula.luma(px.0));
+            }
+        }
+    }
+}
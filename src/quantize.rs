@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+
+use image::{DynamicImage, RgbaImage};
+
+/// Reduces `img` to at most `n` colors using median cut.
+///
+/// Returns the quantized image together with the palette that was used.
+/// Alpha is preserved untouched. If the image already has `n` or fewer
+/// distinct colors it is returned unchanged.
+pub fn quantize(img: &DynamicImage, n: usize) -> (DynamicImage, Vec<[u8; 3]>) {
+    let mut img: RgbaImage = img.to_rgba8();
+    let histogram = histogram(&img);
+    let palette = median_cut(&histogram, n);
+    let mut cache: HashMap<[u8; 3], [u8; 3]> = HashMap::with_capacity(histogram.len());
+    for p in img.pixels_mut() {
+        let [r, g, b, _] = p.0;
+        let c = *cache
+            .entry([r, g, b])
+            .or_insert_with(|| nearest(&palette, [r, g, b]));
+        p.0[..3].copy_from_slice(&c);
+    }
+    (DynamicImage::ImageRgba8(img), palette)
+}
+
+/// The at most `n` colors median cut picks for `img`, like [`quantize`]
+/// does, each with the share (`0.0..=1.0`) of pixels closest to it. The most
+/// common color comes first.
+pub fn dominant_colors(img: &DynamicImage, n: usize) -> Vec<([u8; 3], f64)> {
+    let histogram = histogram(&img.to_rgba8());
+    let palette = median_cut(&histogram, n);
+    let mut counts = vec![0u64; palette.len()];
+    let mut total = 0u64;
+    for (color, count) in &histogram {
+        let closest = nearest(&palette, *color);
+        if let Some(i) = palette.iter().position(|v| *v == closest) {
+            counts[i] += *count as u64;
+        }
+        total += *count as u64;
+    }
+    let mut colors: Vec<_> = palette
+        .into_iter()
+        .zip(counts)
+        .filter(|(_, count)| *count > 0)
+        .map(|(color, count)| (color, count as f64 / total as f64))
+        .collect();
+    colors.sort_by(|a, b| b.1.total_cmp(&a.1));
+    colors
+}
+
+/// Counts how many times every distinct color occurs.
+pub fn histogram(img: &RgbaImage) -> Vec<([u8; 3], u32)> {
+    let mut counts: HashMap<[u8; 3], u32> = HashMap::new();
+    for p in img.pixels() {
+        let [r, g, b, _] = p.0;
+        *counts.entry([r, g, b]).or_default() += 1;
+    }
+    let mut colors: Vec<_> = counts.into_iter().collect();
+    colors.sort_unstable();
+    colors
+}
+
+/// Builds a palette of at most `n` colors from a color histogram.
+pub fn median_cut(histogram: &[([u8; 3], u32)], n: usize) -> Vec<[u8; 3]> {
+    if n == 0 || histogram.is_empty() {
+        return Vec::new();
+    }
+    if histogram.len() <= n {
+        return histogram.iter().map(|(c, _)| *c).collect();
+    }
+    let mut boxes = vec![histogram.to_vec()];
+    while boxes.len() < n {
+        // Split the box with the widest single-channel range.
+        let Some((index, channel)) = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| {
+                let (channel, range) = widest_channel(b);
+                (i, channel, range)
+            })
+            .max_by_key(|(_, _, range)| *range)
+            .map(|(i, channel, _)| (i, channel))
+        else {
+            break;
+        };
+        let mut b = boxes.swap_remove(index);
+        b.sort_unstable_by_key(|(c, _)| c[channel]);
+        let total: u64 = b.iter().map(|(_, w)| *w as u64).sum();
+        let mut acc = 0;
+        let mut split = 1;
+        for (i, (_, w)) in b.iter().enumerate() {
+            acc += *w as u64;
+            if acc * 2 >= total {
+                split = (i + 1).clamp(1, b.len() - 1);
+                break;
+            }
+        }
+        let rest = b.split_off(split);
+        boxes.push(b);
+        boxes.push(rest);
+    }
+    boxes.iter().map(|b| mean(b)).collect()
+}
+
+/// Returns the palette entry closest to `color` (squared RGB distance).
+pub fn nearest(palette: &[[u8; 3]], color: [u8; 3]) -> [u8; 3] {
+    palette
+        .iter()
+        .copied()
+        .min_by_key(|p| distance_sq(*p, color))
+        .unwrap_or(color)
+}
+
+#[inline(always)]
+fn distance_sq(a: [u8; 3], b: [u8; 3]) -> u32 {
+    a.iter()
+        .zip(b.iter())
+        .map(|(x, y)| (*x as i32 - *y as i32).pow(2) as u32)
+        .sum()
+}
+
+fn widest_channel(colors: &[([u8; 3], u32)]) -> (usize, u8) {
+    (0..3)
+        .map(|ch| {
+            let min = colors.iter().map(|(c, _)| c[ch]).min().unwrap_or(0);
+            let max = colors.iter().map(|(c, _)| c[ch]).max().unwrap_or(0);
+            (ch, max - min)
+        })
+        .max_by_key(|(_, range)| *range)
+        .unwrap_or((0, 0))
+}
+
+fn mean(colors: &[([u8; 3], u32)]) -> [u8; 3] {
+    let mut sum = [0u64; 3];
+    let mut total = 0u64;
+    for (c, w) in colors {
+        for ch in 0..3 {
+            sum[ch] += c[ch] as u64 * *w as u64;
+        }
+        total += *w as u64;
+    }
+    let total = total.max(1);
+    [
+        (sum[0] / total) as u8,
+        (sum[1] / total) as u8,
+        (sum[2] / total) as u8,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use image::{Rgb, RgbImage, Rgba};
+
+    use super::*;
+
+    fn distinct(img: &DynamicImage) -> HashSet<[u8; 3]> {
+        histogram(&img.to_rgba8())
+            .into_iter()
+            .map(|(c, _)| c)
+            .collect()
+    }
+
+    #[test]
+    fn gradient_reduces_to_exactly_n_colors() {
+        let img = DynamicImage::ImageRgb8(RgbImage::from_fn(64, 8, |x, y| {
+            Rgb([(x * 4) as u8, (y * 32) as u8, 255 - (x * 4) as u8])
+        }));
+        for n in [1, 2, 5, 16, 64] {
+            let (out, palette) = quantize(&img, n);
+            assert_eq!(palette.len(), n);
+            let colors = distinct(&out);
+            assert_eq!(colors.len(), n);
+            assert!(colors.iter().all(|c| palette.contains(c)));
+        }
+    }
+
+    #[test]
+    fn single_color_gives_one_entry() {
+        let img = DynamicImage::ImageRgb8(RgbImage::from_pixel(5, 5, Rgb([10, 200, 30])));
+        for n in [1, 8] {
+            let (out, palette) = quantize(&img, n);
+            assert_eq!(palette, vec![[10, 200, 30]]);
+            assert_eq!(out.to_rgb8(), img.to_rgb8());
+        }
+    }
+
+    #[test]
+    fn few_colors_stay_identical() {
+        let colors = [
+            [255, 0, 0, 255],
+            [0, 255, 0, 128],
+            [0, 0, 255, 0],
+            [9, 9, 9, 255],
+        ];
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(4, 4, |x, y| {
+            Rgba(colors[((x + y) % 4) as usize])
+        }));
+        let (out, palette) = quantize(&img, 4);
+        assert_eq!(palette.len(), 4);
+        assert_eq!(out.to_rgba8(), img.to_rgba8());
+    }
+}
@@ -1,9 +1,40 @@
 use std::io::{self, Write};
 
+#[cfg(not(target_arch = "wasm32"))]
+use crossterm::terminal;
 use image::DynamicImage;
 
+#[cfg(not(target_arch = "wasm32"))]
+use crate::anim::FrameDiff;
 use crate::img::PixtImg;
 
 pub fn render(p: &PixtImg, img: &DynamicImage, out: impl Write) -> io::Result<()> {
     p.print(img, out)
 }
+
+/// Plays `frames` on the terminal `out`, drawing each over the last from
+/// the top-left corner of the screen through `diff`: only the cells that
+/// changed are written, and those off the screen are left out.
+/// `delay(i)` is called once frame `i` is shown and waits until the next
+/// one is due, returning `false` to stop early (e.g. on a key press).
+///
+/// Cells are positioned with the cursor instead of line breaks, so this
+/// also works in raw mode, on the alternate screen of
+/// [`Stage`](crate::anim::Stage).
+#[cfg(not(target_arch = "wasm32"))]
+pub fn render_frames(
+    p: &PixtImg,
+    frames: impl Iterator<Item = DynamicImage>,
+    mut diff: FrameDiff,
+    mut out: impl Write,
+    mut delay: impl FnMut(usize) -> io::Result<bool>,
+) -> io::Result<()> {
+    let screen = terminal::size().unwrap_or((u16::MAX, u16::MAX));
+    for (i, frame) in frames.enumerate() {
+        diff.draw(p, p.cells(&frame), p.output_type(), screen, &mut out)?;
+        if !delay(i)? {
+            break;
+        }
+    }
+    Ok(())
+}
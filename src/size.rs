@@ -0,0 +1,224 @@
+use std::io;
+
+/// Typical width:height ratio of a terminal character cell.
+pub const DEFAULT_CELL_ASPECT: f32 = 0.5;
+
+/// Largest width or height, in cells, accepted for a render. Anything bigger
+/// is almost certainly a typo and would take ages and gigabytes to print.
+pub const MAX_DIMENSION: u32 = 10_000;
+
+/// Checks a user-provided width or height (`name` is used in the error):
+/// it must be at least 1 and at most [`MAX_DIMENSION`].
+pub fn validate_dimension(name: &str, value: u32) -> io::Result<u32> {
+    match value {
+        0 => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} must be at least 1", name),
+        )),
+        v if v > MAX_DIMENSION => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} {} exceeds the maximum of {}", name, v, MAX_DIMENSION),
+        )),
+        v => Ok(v),
+    }
+}
+
+/// Scales `value` by `num / den` in 64-bit integer math, rounding down.
+///
+/// Fails with [`io::ErrorKind::InvalidInput`] when the result doesn't fit a
+/// `u32` (or `den` is zero) instead of wrapping around.
+pub fn scale_dimension(value: u32, num: u32, den: u32) -> io::Result<u32> {
+    (value as u64 * num as u64)
+        .checked_div(den as u64)
+        .and_then(|v| u32::try_from(v).ok())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} * {} / {} does not fit a dimension", value, num, den),
+            )
+        })
+}
+
+/// Rounds a dimension of `value` pixels derived in floating point, checking
+/// it against [`MAX_DIMENSION`] cells of `cell_px` pixels each before it gets
+/// truncated to `u32`.
+fn derived_dimension(name: &str, value: f64, cell_px: u32) -> io::Result<u32> {
+    let value = value.round();
+    if value.is_nan() || value > (MAX_DIMENSION * cell_px) as f64 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "derived {} {:.0} exceeds the maximum of {}",
+                name,
+                (value / cell_px as f64).ceil(),
+                MAX_DIMENSION
+            ),
+        ));
+    }
+    Ok(value as u32)
+}
+
+/// Computes the size (in source pixels) an image of `img_w`×`img_h` should be
+/// resized to before rendering.
+///
+/// Every output cell covers one pixel column and two pixel rows, so the
+/// returned height is twice the number of output rows. `cell_aspect` is the
+/// width:height ratio of one cell as displayed (0.5 for most terminal fonts);
+/// it is used to derive the missing dimension so the art keeps the source's
+/// proportions. When `cols` is not given, `term_cols` is queried for the
+/// available width; with `rows` given it only caps the derived width, so a
+/// failing query is ignored.
+///
+/// With `clamp` set (output goes to a terminal), an explicit `cols` wider
+/// than the terminal is reduced to fit, scaling an explicit `rows` along so
+/// the proportions survive; otherwise each row would wrap. If the terminal
+/// size can't be queried the request is honored as is.
+///
+/// Given dimensions are checked with [`validate_dimension`], and a derived
+/// one beyond [`MAX_DIMENSION`] is an error too.
+pub fn target_size(
+    (img_w, img_h): (u32, u32),
+    cols: Option<u32>,
+    rows: Option<u32>,
+    cell_aspect: f32,
+    clamp: bool,
+    mut term_cols: impl FnMut() -> io::Result<u32>,
+) -> io::Result<(u32, u32)> {
+    if let Some(cols) = cols {
+        validate_dimension("width", cols)?;
+    }
+    if let Some(rows) = rows {
+        validate_dimension("height", rows)?;
+    }
+    let ratio = img_h as f64 / img_w.max(1) as f64;
+    let px_per_col = 2.0 * cell_aspect as f64;
+    let (cols, rows) = match cols {
+        Some(requested) if clamp => {
+            let cols = term_cols().map_or(requested, |max| requested.min(max.max(1)));
+            let rows = match rows {
+                Some(r) => Some(scale_dimension(r, cols, requested)?.max(1)),
+                None => None,
+            };
+            (Some(cols), rows)
+        }
+        _ => (cols, rows),
+    };
+    // Heights are in pixels, two per output row.
+    let (w, h) = match (cols, rows) {
+        (Some(cols), Some(rows)) => (cols, rows * 2),
+        (Some(cols), None) => (
+            cols,
+            derived_dimension("height", cols as f64 * ratio * px_per_col, 2)?,
+        ),
+        (None, Some(rows)) => {
+            let h = rows * 2;
+            let w = derived_dimension("width", h as f64 / ratio / px_per_col, 1)
+                .unwrap_or(MAX_DIMENSION);
+            (term_cols().map_or(w, |max| w.min(max)), h)
+        }
+        (None, None) => {
+            let cols = term_cols()?.min(MAX_DIMENSION);
+            (
+                cols,
+                derived_dimension("height", cols as f64 * ratio * px_per_col, 2)?,
+            )
+        }
+    };
+    // At least one cell, i.e. two pixel rows.
+    Ok((w.max(1), h.max(2)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_terminal() -> io::Result<u32> {
+        Err(io::Error::other("not a terminal"))
+    }
+
+    #[test]
+    fn cell_aspect_keeps_the_proportions() {
+        // Square source, 40 columns: cells twice as tall as wide need half as
+        // many rows, i.e. 20 rows of two pixels.
+        let size = |aspect| target_size((100, 100), Some(40), None, aspect, false, no_terminal);
+        assert_eq!(size(DEFAULT_CELL_ASPECT).unwrap(), (40, 40));
+        // 1:1.8 fonts.
+        assert_eq!(size(1.0 / 1.8).unwrap(), (40, 44));
+        // Square cells, one row per column.
+        assert_eq!(size(1.0).unwrap(), (40, 80));
+        // A 2:1 source gets half the rows.
+        let wide = target_size((200, 100), Some(40), None, 0.5, false, no_terminal);
+        assert_eq!(wide.unwrap(), (40, 20));
+        // Deriving the width instead gives the same shape back.
+        let tall = target_size((200, 100), None, Some(10), 0.5, false, no_terminal);
+        assert_eq!(tall.unwrap(), (40, 20));
+    }
+
+    #[test]
+    fn wide_requests_clamp_to_the_terminal() {
+        let size = |clamp| target_size((100, 100), Some(200), Some(50), 0.5, clamp, || Ok(80));
+        assert_eq!(size(false).unwrap(), (200, 100));
+        assert_eq!(size(true).unwrap(), (80, 40));
+        // Without a terminal the request is honored.
+        let size = target_size((100, 100), Some(200), None, 0.5, true, no_terminal);
+        assert_eq!(size.unwrap(), (200, 200));
+    }
+
+    #[test]
+    fn zero_and_huge_dimensions_are_rejected() {
+        assert!(validate_dimension("width", 0).is_err());
+        assert_eq!(
+            validate_dimension("width", MAX_DIMENSION).unwrap(),
+            MAX_DIMENSION
+        );
+        assert!(validate_dimension("width", MAX_DIMENSION + 1).is_err());
+        let size = |cols, rows| target_size((100, 100), cols, rows, 0.5, false, no_terminal);
+        assert!(size(Some(0), None).is_err());
+        assert!(size(None, Some(0)).is_err());
+        assert!(size(Some(MAX_DIMENSION + 1), None).is_err());
+        // Fine width, but a derived height beyond the cap.
+        let thin = target_size((1, 1000), Some(100), None, 0.5, false, no_terminal);
+        assert!(thin.is_err());
+    }
+
+    #[test]
+    fn scaling_uses_64_bits() {
+        // 20,000 * 300,000 overflows u32, the result doesn't.
+        assert_eq!(scale_dimension(20_000, 300_000, 20_000).unwrap(), 300_000);
+        assert_eq!(
+            scale_dimension(u32::MAX, u32::MAX, u32::MAX).unwrap(),
+            u32::MAX
+        );
+        assert_eq!(scale_dimension(70_000, 70_000, 1 << 20).unwrap(), 4_673);
+        assert!(scale_dimension(u32::MAX, 2, 1).is_err());
+        assert!(scale_dimension(1, 1, 0).is_err());
+    }
+
+    #[test]
+    fn huge_sources_neither_wrap_nor_panic() {
+        let size = |img, cols, rows| target_size(img, cols, rows, 0.5, false, no_terminal);
+        // A 20,000×20,000 scan at the widest allowed width.
+        assert_eq!(
+            size((20_000, 20_000), Some(MAX_DIMENSION), None).unwrap(),
+            (MAX_DIMENSION, MAX_DIMENSION)
+        );
+        // A wide panorama gets a sliver, not a wrapped height.
+        assert_eq!(size((u32::MAX, 1), Some(300), None).unwrap(), (300, 2));
+        // And a tall one an error instead of a wrapped height.
+        assert!(size((1, u32::MAX), Some(300), None).is_err());
+        assert!(size((u32::MAX, u32::MAX), Some(300_000), None).is_err());
+        // A derived width past the cap is capped.
+        let (w, h) = size((u32::MAX, 1), None, Some(100)).unwrap();
+        assert_eq!((w, h), (MAX_DIMENSION, 200));
+        // Clamping a huge explicit height along with the width.
+        let clamped = target_size(
+            (100, 100),
+            Some(MAX_DIMENSION),
+            Some(MAX_DIMENSION),
+            0.5,
+            true,
+            || Ok(u32::MAX),
+        );
+        assert_eq!(clamped.unwrap(), (MAX_DIMENSION, 2 * MAX_DIMENSION));
+    }
+}
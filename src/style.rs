@@ -1,11 +1,92 @@
-use crate::img::IntoPixtData;
+use std::{fmt, io, str::FromStr};
 
+use crate::{
+    blocks::Subpixels,
+    img::{IntoPixtData, parse_name},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ImgStyle {
     Ascii,
     Block,
     Pixel,
     Braills,
     Dots,
+    /// Only spaces: with [`ColorType::AvgBgOnly`](crate::img::ColorType)
+    /// every cell is a block of its average color, with no glyph shapes for
+    /// fonts to get wrong.
+    Solid,
+    /// `▀` in every cell: with [`ColorType::FgTopBgDown`](crate::img::ColorType)
+    /// the top pixel colors the glyph and the bottom one the background, two
+    /// true-color pixels per character with no intensity ramp in between.
+    HalfBlock,
+    /// Quadrant blocks (`▘`, `▚`, `▌`, `█`, ...): every cell samples 2×2
+    /// pixels, splits them into two clusters and draws the brighter one as
+    /// the glyph. With [`ColorType::FgTopBgDown`](crate::img::ColorType)
+    /// the cluster means color the glyph and the background, doubling the
+    /// horizontal resolution of [`HalfBlock`](Self::HalfBlock).
+    Quadrant,
+    /// Sextants (2×3 pixels) from the Symbols for Legacy Computing block,
+    /// lit by [`BlockThreshold`](crate::blocks::BlockThreshold); colored
+    /// like [`Quadrant`](Self::Quadrant). Needs a Unicode 13 font.
+    Sextant,
+    /// Braille patterns whose 8 dots are 2×4 pixels, lit by
+    /// [`BlockThreshold`](crate::blocks::BlockThreshold). Unlike
+    /// [`Braills`](Self::Braills), which ramps through braille glyphs by
+    /// intensity, every dot follows its own pixel.
+    BrailleDots,
+}
+
+impl ImgStyle {
+    pub const ALL: [Self; 10] = [
+        Self::Ascii,
+        Self::Block,
+        Self::Pixel,
+        Self::Braills,
+        Self::Dots,
+        Self::Solid,
+        Self::HalfBlock,
+        Self::Quadrant,
+        Self::Sextant,
+        Self::BrailleDots,
+    ];
+    /// Canonical name, as used by [`Display`](fmt::Display) and
+    /// [`FromStr`].
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Ascii => "ascii",
+            Self::Block => "block",
+            Self::Pixel => "pixel",
+            Self::Braills => "braills",
+            Self::Dots => "dots",
+            Self::Solid => "solid",
+            Self::HalfBlock => "half-block",
+            Self::Quadrant => "quadrant",
+            Self::Sextant => "sextant",
+            Self::BrailleDots => "braille-dots",
+        }
+    }
+}
+
+impl fmt::Display for ImgStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Also accepts `braille` and `halfblock`.
+impl FromStr for ImgStyle {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Self> {
+        parse_name(
+            "style",
+            s,
+            &Self::ALL,
+            Self::name,
+            &[("braille", Self::Braills), ("halfblock", Self::HalfBlock)],
+        )
+    }
 }
 
 impl IntoPixtData for ImgStyle {
@@ -14,15 +95,30 @@ impl IntoPixtData for ImgStyle {
             Self::Ascii => [' ', '.', '-', '~', '+', '*', '%', '#', '@'].into_pixt_data(),
             Self::Block => [' ', '░', '▒', '▓'].into_pixt_data(),
             Self::Pixel => [' ', '▀', '▞', '▟', '█'].into_pixt_data(),
+            // The column (top pixel) fills the upper four dots and the row
+            // (bottom pixel) the lower four, one dot per step.
             Self::Braills => [
-                [' ', '⠁', '⠉', '⠓', '⠛'],
-                ['⠄', '⠅', '⠩', '⠝', '⠟'],
-                ['⠤', '⠥', '⠭', '⠯', '⠽'],
-                ['⠴', '⠵', '⠽', '⠾', '⠿'],
-                ['⠶', '⠾', '⠾', '⠿', '⠿'],
+                [' ', '⠁', '⠑', '⠙', '⠛'],
+                ['⠄', '⠅', '⠕', '⠝', '⠟'],
+                ['⢄', '⢅', '⢕', '⢝', '⢟'],
+                ['⢤', '⢥', '⢵', '⢽', '⢿'],
+                ['⣤', '⣥', '⣵', '⣽', '⣿'],
             ]
             .into_pixt_data(),
             Self::Dots => [' ', '⠂', '⠒', '⠕', '⠞', '⠟', '⠿'].into_pixt_data(),
+            Self::Solid => [' '].into_pixt_data(),
+            Self::HalfBlock => ['▀'].into_pixt_data(),
+            Self::Quadrant => Subpixels::Quadrant.glyphs().into_pixt_data(),
+            Self::Sextant => Subpixels::Sextant.glyphs().into_pixt_data(),
+            Self::BrailleDots => Subpixels::Braille.glyphs().into_pixt_data(),
+        }
+    }
+    fn subpixels(&self) -> Option<Subpixels> {
+        match self {
+            Self::Quadrant => Some(Subpixels::Quadrant),
+            Self::Sextant => Some(Subpixels::Sextant),
+            Self::BrailleDots => Some(Subpixels::Braille),
+            _ => None,
         }
     }
 }
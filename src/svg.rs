@@ -0,0 +1,272 @@
+//! Layout and helpers that only concern SVG output.
+//!
+//! Every output row is one `<text>` element starting at `x=0`, so glyphs
+//! keep the font's own monospace advance. Colored glyphs become child
+//! `<tspan>`s and background colors `<rect>`s behind the text, one per cell.
+//! Minified, neighbouring cells of equal color share one, the boxes are
+//! shorter `<path>`s, coordinates are rounded to one decimal and colors use
+//! their short form where they have one, as with HTML output.
+
+use std::io;
+
+use crate::{
+    html::{write_escaped, write_hex, write_short_hex},
+    img::{ColorType, Glyph},
+};
+
+/// How cells are drawn in SVG output.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SvgMode {
+    /// Glyphs as `<text>`; the look depends on the viewer's monospace font.
+    #[default]
+    Text,
+    /// Every cell as a colored `<rect>`, or two stacked half-height ones
+    /// for color types with separate top and bottom colors. A mosaic that
+    /// renders the same everywhere.
+    Rects,
+}
+
+/// Font size in user units.
+pub const FONT_SIZE: f64 = 10.0;
+
+/// Advance of one monospace character in user units; most monospace fonts
+/// are close to 0.6 em.
+pub const CHAR_WIDTH: f64 = FONT_SIZE * 0.6;
+
+/// Distance between rows in user units, matching the line-heights of HTML
+/// output: colored rows stack two pixels per cell, so they sit half as far
+/// apart.
+pub fn line_height(color: &ColorType) -> f64 {
+    match color {
+        ColorType::None => FONT_SIZE * 1.2,
+        _ => FONT_SIZE * 0.6,
+    }
+}
+
+/// Baseline of row `row`, placed like a browser centers a line box: half
+/// the leading above, then an ascent of 0.8 em.
+pub fn baseline(row: u32, line_height: f64) -> f64 {
+    row as f64 * line_height + (line_height - FONT_SIZE) / 2.0 + FONT_SIZE * 0.8
+}
+
+/// Rounds `v` to one decimal, as minified output writes coordinates.
+fn round(v: f64) -> f64 {
+    (v * 10.0).round() / 10.0
+}
+
+/// Opens an SVG document sized for `cols`×`rows` characters, with a dark
+/// backdrop like HTML output. Minified, it takes a single line.
+pub fn write_header(
+    cols: u32,
+    rows: u32,
+    color: &ColorType,
+    minify: bool,
+    mut out: impl io::Write,
+) -> io::Result<()> {
+    let width = cols as f64 * CHAR_WIDTH;
+    let height = rows as f64 * line_height(color);
+    let (width, height) = match minify {
+        true => (round(width), round(height)),
+        false => (width, height),
+    };
+    write!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+         viewBox=\"0 0 {width} {height}\" font-family=\"monospace\" font-size=\"{FONT_SIZE}\" \
+         fill=\"#fff\" xml:space=\"preserve\">"
+    )?;
+    if !minify {
+        out.write_all(b"\n")?;
+    }
+    writeln!(
+        out,
+        "<rect width=\"100%\" height=\"100%\" fill=\"#191919\"/>"
+    )
+}
+
+/// Writes a `width`×`height` box at `(x, y)` filled with `fill`: a
+/// `<rect>`, or minified a `<path>` with rounded coordinates.
+fn write_box(
+    (x, y, width, height): (f64, f64, f64, f64),
+    fill: [u8; 3],
+    minify: bool,
+    mut out: impl io::Write,
+) -> io::Result<()> {
+    if minify {
+        let (x, y, width, height) = (round(x), round(y), round(width), round(height));
+        write!(
+            out,
+            "<path d=\"M{x} {y}h{width}v{height}h-{width}z\" fill=\""
+        )?;
+        write_short_hex(&mut out, fill)?;
+    } else {
+        write!(
+            out,
+            "<rect x=\"{x}\" y=\"{y}\" width=\"{width}\" height=\"{height}\" fill=\""
+        )?;
+        write_hex(&mut out, fill)?;
+    }
+    out.write_all(b"\"/>")
+}
+
+/// One column of a row: the glyph drawn there (`None` where a wide glyph
+/// spills over) with the optional foreground and background color.
+pub type Column<'a> = (Option<&'a Glyph>, Option<[u8; 3]>, Option<[u8; 3]>);
+
+/// Writes one row of glyphs as `<text>`, with `<rect>`s behind it for the
+/// background colors; see the [module docs](self) for `minify`.
+pub fn write_row(
+    row: u32,
+    color: &ColorType,
+    cols: &mut [Column],
+    minify: bool,
+    mut out: impl io::Write,
+) -> io::Result<()> {
+    let line = line_height(color);
+    let top = row as f64 * line;
+    let mut x = 0;
+    for run in cols.chunk_by(|a, b| minify && a.2 == b.2) {
+        if let Some(bg) = run[0].2 {
+            let area = (
+                x as f64 * CHAR_WIDTH,
+                top,
+                run.len() as f64 * CHAR_WIDTH,
+                line,
+            );
+            write_box(area, bg, minify, &mut out)?;
+        }
+        x += run.len();
+    }
+    // Spaces show no foreground, so they join the run before them, and so
+    // do the empty columns a wide glyph spills into.
+    let mut last = None;
+    for col in cols.iter_mut() {
+        if col.0.is_none_or(Glyph::is_blank) {
+            col.1 = if minify { last } else { None };
+        } else {
+            last = col.1;
+        }
+    }
+    let y = match minify {
+        true => round(baseline(row, line)),
+        false => baseline(row, line),
+    };
+    write!(out, "<text x=\"0\" y=\"{}\">", y)?;
+    let mut buf = [0; 4];
+    for run in cols.chunk_by(|a, b| a.1 == b.1 && (minify || a.1.is_none())) {
+        if let Some(fg) = run[0].1 {
+            out.write_all(b"<tspan fill=\"")?;
+            match minify {
+                true => write_short_hex(&mut out, fg)?,
+                false => write_hex(&mut out, fg)?,
+            }
+            out.write_all(b"\">")?;
+        }
+        for ch in run.iter().filter_map(|v| v.0) {
+            write_escaped(&mut out, ch.encode_utf8(&mut buf))?;
+        }
+        if run[0].1.is_some() {
+            out.write_all(b"</tspan>")?;
+        }
+    }
+    out.write_all(b"</text>")
+}
+
+/// One column of [`SvgMode::Rects`] output: the colors of its top and
+/// bottom half.
+pub type RectColumn = (Option<[u8; 3]>, Option<[u8; 3]>);
+
+/// Writes one row of [`SvgMode::Rects`] output, `None` halves left to the
+/// backdrop. Minified, neighbouring columns of the same colors share a
+/// `<rect>`; see the [module docs](self).
+pub fn write_rects(
+    row: u32,
+    color: &ColorType,
+    cols: &[RectColumn],
+    minify: bool,
+    mut out: impl io::Write,
+) -> io::Result<()> {
+    let line = line_height(color);
+    let top = row as f64 * line;
+    // Adjacent rects would show hairline seams when antialiased.
+    out.write_all(b"<g shape-rendering=\"crispEdges\">")?;
+    let mut x = 0;
+    for run in cols.chunk_by(|a, b| minify && a == b) {
+        let halves = match run[0] {
+            (Some(t), Some(b)) if t == b => [(Some(t), top, line), (None, 0.0, 0.0)],
+            (t, b) => [(t, top, line / 2.0), (b, top + line / 2.0, line / 2.0)],
+        };
+        for (fill, y, height) in halves {
+            let Some(fill) = fill else {
+                continue;
+            };
+            let area = (
+                x as f64 * CHAR_WIDTH,
+                y,
+                run.len() as f64 * CHAR_WIDTH,
+                height,
+            );
+            write_box(area, fill, minify, &mut out)?;
+        }
+        x += run.len();
+    }
+    out.write_all(b"</g>")
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, Rgb, RgbImage};
+
+    use crate::{
+        html::HtmlOptions,
+        img::{ColorType, OutputType, PixtImg},
+        quantize::quantize,
+        style::ImgStyle,
+    };
+
+    use super::*;
+
+    /// A smooth, colorful image posterized to 16 colors, as `--colors 16`
+    /// would.
+    fn posterized() -> DynamicImage {
+        let img = RgbImage::from_fn(120, 80, |x, y| {
+            Rgb([(x * 2) as u8, (y * 3) as u8, ((x + y) % 256) as u8])
+        });
+        quantize(&DynamicImage::ImageRgb8(img), 16).0
+    }
+
+    fn render(style: ImgStyle, color: ColorType, mode: SvgMode, minify: bool) -> String {
+        let img = posterized();
+        let pi = PixtImg::new(style, OutputType::Svg(color))
+            .with_svg_mode(mode)
+            .with_html_options(HtmlOptions::default().with_minify(minify));
+        let mut out = Vec::new();
+        pi.print(&img, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn minified_svg_is_well_formed_and_a_third_the_size() {
+        for (style, color, mode) in [
+            (ImgStyle::Ascii, ColorType::AvgFgOnly, SvgMode::Text),
+            (ImgStyle::Block, ColorType::FgTopBgDown, SvgMode::Text),
+            (ImgStyle::HalfBlock, ColorType::FgTopBgDown, SvgMode::Rects),
+        ] {
+            let full = render(style, color.clone(), mode, false);
+            let minified = render(style, color, mode, true);
+            for svg in [&full, &minified] {
+                let doc = roxmltree::Document::parse(svg)
+                    .unwrap_or_else(|err| panic!("{:?} {:?}: {}", style, mode, err));
+                assert_eq!(doc.root_element().tag_name().name(), "svg");
+            }
+            assert!(
+                full.len() >= 3 * minified.len(),
+                "{:?} {:?}: {} bytes minified to {}",
+                style,
+                mode,
+                full.len(),
+                minified.len()
+            );
+        }
+    }
+}
@@ -0,0 +1,162 @@
+//! Runs the `pixt` binary with stdout piped, as in `pixt ... | less -R`.
+use std::{
+    env, fs,
+    path::PathBuf,
+    process::{self, Command, Output},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use image::{Rgb, RgbImage};
+
+/// A fresh directory holding `gradient.png`, a small colorful image.
+struct Fixture {
+    dir: PathBuf,
+}
+
+impl Fixture {
+    fn new() -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let dir = env::temp_dir().join(format!(
+            "pixt-cli-{}-{}",
+            process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        RgbImage::from_fn(16, 8, |x, y| Rgb([(x * 16) as u8, (y * 32) as u8, 128]))
+            .save(dir.join("gradient.png"))
+            .unwrap();
+        Self { dir }
+    }
+
+    /// `pixt` run in the fixture directory, with `TERM` set to a color
+    /// terminal and none of the color variables of the caller.
+    fn command(&self) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_pixt"));
+        command
+            .current_dir(&self.dir)
+            .env_remove("NO_COLOR")
+            .env_remove("PIXT_COLOR_MODE")
+            .env("TERM", "xterm-256color");
+        command
+    }
+
+    /// Runs `pixt <args> gradient.png` with stdout piped.
+    fn pixt(&self, args: &[&str], envs: &[(&str, &str)]) -> Output {
+        let output = self
+            .command()
+            .args(args)
+            .arg("gradient.png")
+            .envs(envs.iter().copied())
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "pixt {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        output
+    }
+
+    /// Whether the output of `pixt <args>` holds an escape sequence.
+    fn escapes(&self, args: &[&str], envs: &[(&str, &str)]) -> bool {
+        self.pixt(args, envs).stdout.contains(&0x1b)
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[test]
+fn color_mode_decides_escapes_in_pipes() {
+    let fx = Fixture::new();
+    assert!(!fx.escapes(&["-c", "-w", "8", "--color", "auto"], &[]));
+    assert!(fx.escapes(&["-c", "-w", "8", "--color", "always"], &[]));
+    assert!(!fx.escapes(&["-c", "-w", "8", "--color", "never"], &[]));
+    // `auto` is the default.
+    assert!(!fx.escapes(&["-c", "-w", "8"], &[]));
+    assert!(fx.escapes(&["-c", "-w", "8"], &[("PIXT_COLOR_MODE", "always")]));
+    // Plain art stays plain either way.
+    assert!(!fx.escapes(&["-w", "8", "--color", "always"], &[]));
+}
+
+#[test]
+fn no_color_drops_escapes_unless_forced() {
+    let fx = Fixture::new();
+    let no_color = [("NO_COLOR", "1")];
+    assert!(!fx.escapes(&["-c", "-w", "8"], &no_color));
+    assert!(!fx.escapes(&["-c", "-w", "8", "--color", "auto"], &no_color));
+    assert!(fx.escapes(&["-c", "-w", "8", "--color", "always"], &no_color));
+}
+
+#[test]
+fn redirected_output_is_plain() {
+    let fx = Fixture::new();
+    let out = fx.dir.join("out.txt");
+    let file = fs::File::create(&out).unwrap();
+    let status = fx
+        .command()
+        .args(["-c", "-w", "8", "gradient.png"])
+        .stdout(file)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let text = fs::read(&out).unwrap();
+    assert!(!text.is_empty());
+    assert!(!text.contains(&0x1b));
+}
+
+#[test]
+fn failed_render_keeps_the_original_output() {
+    let fx = Fixture::new();
+    let out = fx.dir.join("out.txt");
+    fs::write(&out, "original\n").unwrap();
+    // The first image renders, the second one fails.
+    let output = fx
+        .command()
+        .args(["-w", "8", "-o", "out.txt", "gradient.png", "missing.png"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert_eq!(fs::read_to_string(&out).unwrap(), "original\n");
+    let names: Vec<_> = fs::read_dir(&fx.dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(names.len(), 2, "left behind: {:?}", names);
+
+    let output = fx
+        .command()
+        .args(["-w", "8", "-o", "out.txt", "gradient.png"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_ne!(fs::read_to_string(&out).unwrap(), "original\n");
+}
+
+#[test]
+fn only_clamped_widths_warn() {
+    let fx = Fixture::new();
+    // Odd widths lose a column to double-width cells, which isn't a clamp.
+    for args in [&["-w", "9", "--wide"][..], &["-w", "500"]] {
+        let output = fx.pixt(args, &[]);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(!stderr.contains("clamped"), "pixt {:?}: {}", args, stderr);
+    }
+}
+
+#[test]
+fn saving_a_custom_charset_needs_its_characters() {
+    let fx = Fixture::new();
+    let output = fx
+        .command()
+        .args(["charset", "save", "-s", "custom"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("use --chars or --from"), "{}", stderr);
+}
@@ -0,0 +1,68 @@
+//! Builds `examples/c/render.c` against the library and runs it.
+#![cfg(all(feature = "ffi", unix))]
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{self, Command},
+};
+
+use image::{Rgb, RgbImage};
+
+/// `target/<profile>/deps`, where cargo builds `libpixt` for this test
+/// with the same features.
+fn lib_dir() -> PathBuf {
+    let exe = env::current_exe().unwrap();
+    exe.parent().unwrap().to_path_buf()
+}
+
+#[test]
+fn checked_in_header_is_current() {
+    let generated = Path::new(env!("OUT_DIR")).join("pixt.h");
+    let checked_in = Path::new(env!("CARGO_MANIFEST_DIR")).join("include/pixt.h");
+    assert!(
+        fs::read_to_string(&generated).unwrap() == fs::read_to_string(&checked_in).unwrap(),
+        "include/pixt.h is out of date, copy {} over it",
+        generated.display()
+    );
+}
+
+#[test]
+fn c_example_renders_an_image() {
+    let dir = env::temp_dir().join(format!("pixt-ffi-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let image = dir.join("gradient.png");
+    RgbImage::from_fn(32, 16, |x, _| Rgb([(x * 8) as u8; 3]))
+        .save(&image)
+        .unwrap();
+    let exe = dir.join("render");
+    let lib_dir = lib_dir();
+    let status = Command::new(env::var("CC").unwrap_or_else(|_| "cc".into()))
+        .arg(Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/c/render.c"))
+        .arg(format!("-I{}", env!("OUT_DIR")))
+        .arg(format!("-L{}", lib_dir.display()))
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .args(["-lpixt", "-o"])
+        .arg(&exe)
+        .status()
+        .expect("a C compiler is needed to build examples/c/render.c");
+    assert!(status.success(), "compiling render.c failed");
+
+    // Cargo's own library path would find the `libpixt` of other builds
+    // first.
+    let output = Command::new(&exe)
+        .arg(&image)
+        .env("LD_LIBRARY_PATH", &lib_dir)
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    let (stdout, stderr) = (
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr),
+    );
+    assert!(output.status.success(), "render.c failed: {}", stderr);
+    assert!(stdout.contains('\x1b'), "no colored output: {:?}", stdout);
+    assert!(stdout.contains('▓'), "no block glyphs: {:?}", stdout);
+    assert!(stderr.contains("expected failure "), "{}", stderr);
+    assert!(!stderr.contains("expected failure 0"), "{}", stderr);
+}